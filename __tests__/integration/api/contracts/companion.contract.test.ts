@@ -199,11 +199,35 @@ describe("Companion API Contracts", () => {
           },
         ],
         unlockedQuests: ["quest_unlocked_1", "quest_unlocked_2"],
+        accepted: ["evt_1", "evt_2"],
+        rejected: ["evt_3"],
+        retryable: [],
       };
 
       expect(mockResponse.synced).toBeGreaterThanOrEqual(0);
       expect(Array.isArray(mockResponse.errors)).toBe(true);
       expect(Array.isArray(mockResponse.unlockedQuests)).toBe(true);
+      expect(Array.isArray(mockResponse.accepted)).toBe(true);
+      expect(Array.isArray(mockResponse.rejected)).toBe(true);
+      expect(Array.isArray(mockResponse.retryable)).toBe(true);
+    });
+
+    it("accepts client event ids, with or without them", () => {
+      const event = {
+        questId: "quest_prapor_debut",
+        status: "FINISHED" as const,
+        timestamp: new Date().toISOString(),
+      };
+
+      const withId = syncEventSchema.safeParse({ ...event, eventId: "a1b2c3" });
+      expect(withId.success).toBe(true);
+      if (withId.success) {
+        expect(withId.data.eventId).toBe("a1b2c3");
+      }
+
+      const empty = syncEventSchema.safeParse({ ...event, eventId: "" });
+      expect(syncEventSchema.safeParse(event).success).toBe(true);
+      expect(empty.success).toBe(false);
     });
   });
 
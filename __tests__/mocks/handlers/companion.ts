@@ -135,6 +135,9 @@ export const companionHandlers = [
       synced: events.length,
       errors: [],
       unlockedQuests: [],
+      accepted: events.flatMap((e) => (e.eventId ? [e.eventId] : [])),
+      rejected: [],
+      retryable: [],
     };
 
     // Simulate some unlocked quests when completing
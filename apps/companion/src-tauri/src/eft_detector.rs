@@ -1,46 +1,65 @@
 //! EFT Installation Detection
 //!
-//! Detects Escape from Tarkov installation path using:
-//! 1. Windows Registry (BSG Launcher stores install path)
-//! 2. Custom user-provided path
-//! 3. Common installation locations
+//! Detects Escape from Tarkov installation path by asking, in rank order:
+//! 1. Custom user-provided path
+//! 2. The folder of a running game process
+//! 3. BSG Launcher settings file (follows installs moved through the launcher)
+//! 4. Windows Registry (BSG Launcher stores install path)
+//! 5. Steam libraries (Steam edition)
+//! 6. Wine, Lutris, Bottles and Proton prefixes (Linux)
+//! 7. Common installation locations
+//! 8. A scan of every mounted volume, run in the background and cached
+//!
+//! Installs with the SPT server next to the client are reported as `GameMode::Spt`.
+
+mod diagnostics;
+mod drive_scan;
+mod install_source;
+mod launcher_settings;
+mod registry;
+mod spt;
+mod steam;
+mod vdf;
+mod version;
+mod wine;
 
+pub use diagnostics::DiagnosticsReport;
+pub use install_source::InstallCandidate;
+pub use spt::{GameMode, SptPaths};
+pub use version::version_from_session_name;
+
+use chrono::Utc;
+use diagnostics::{ProbeLog, RecordingFileSystem, RecordingRegistry};
+use install_source::{
+    validate_install, DefaultLocationSource, DriveScanSource, FileSystem, InstallResolver,
+    LauncherSettingsSource, RealFileSystem, RegistrySource, RunningProcessSource, SteamSource,
+    UserProvidedSource, WinePrefixSource,
+};
 use log::{info, warn};
-use std::path::Path;
-use std::sync::RwLock;
-use winreg::enums::*;
-use winreg::RegKey;
-
-/// Default installation paths to check if registry lookup fails
-const DEFAULT_PATHS: &[&str] = &[
-    "C:\\Battlestate Games\\Escape from Tarkov",
-    "C:\\Battlestate Games\\EFT",
-    "D:\\Battlestate Games\\Escape from Tarkov",
-    "D:\\Battlestate Games\\EFT",
-    "C:\\Games\\Escape from Tarkov",
-    "C:\\Games\\EFT",
-    "D:\\Games\\Escape from Tarkov",
-    "D:\\Games\\EFT",
-    "E:\\Battlestate Games\\Escape from Tarkov",
-    "E:\\Battlestate Games\\EFT",
-];
-
-/// Registry paths where BSG Launcher stores EFT path
-const REGISTRY_PATHS: &[(&str, &str)] = &[
-    // BSG Launcher stores the install path here
-    (
-        "SOFTWARE\\Battlestate Games\\EscapeFromTarkov",
-        "InstallPath",
-    ),
-    (
-        "SOFTWARE\\WOW6432Node\\Battlestate Games\\EscapeFromTarkov",
-        "InstallPath",
-    ),
-];
+use registry::RegistryBackend;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+use version::GameVersion;
+
+/// The resolved install and the client version found in it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallInfo {
+    pub path: String,
+    pub logs_path: String,
+    pub version: Option<GameVersion>,
+    pub mode: GameMode,
+}
 
 pub struct EftDetector {
     detected_path: RwLock<Option<String>>,
     custom_path: RwLock<Option<String>>,
+    scanned_installs: RwLock<Vec<PathBuf>>,
+    running_install: RwLock<Option<PathBuf>>,
+    /// Cancels the current drive scan; each scan gets a fresh token
+    scan_cancel: Mutex<CancellationToken>,
 }
 
 impl EftDetector {
@@ -48,6 +67,9 @@ impl EftDetector {
         let detector = Self {
             detected_path: RwLock::new(None),
             custom_path: RwLock::new(None),
+            scanned_installs: RwLock::new(Vec::new()),
+            running_install: RwLock::new(None),
+            scan_cancel: Mutex::new(CancellationToken::new()),
         };
 
         // Attempt to detect on creation
@@ -61,6 +83,53 @@ impl EftDetector {
         detector
     }
 
+    /// Search all mounted volumes for installs.
+    /// Runs on a background thread, since listing volumes can stall on dead
+    /// network drives. Fresh cached results are used right away; otherwise a
+    /// scan runs within its time budget and refreshes the cache.
+    pub fn start_drive_scan(self: &Arc<Self>, cache_path: PathBuf) {
+        let cancel = CancellationToken::new();
+        *self.scan_cancel.lock().unwrap() = cancel.clone();
+
+        let detector = Arc::clone(self);
+        std::thread::spawn(move || {
+            let volumes = drive_scan::list_volumes(&RealFileSystem);
+            if let Some(cached) = drive_scan::load_cache(&cache_path, &volumes) {
+                info!(
+                    "Using cached drive scan ({} installs)",
+                    cached.installs.len()
+                );
+                detector.apply_drive_scan(cached.installs);
+                return;
+            }
+
+            info!("Scanning {} volumes for EFT installs", volumes.len());
+            let result =
+                drive_scan::scan(&RealFileSystem, &volumes, drive_scan::SCAN_BUDGET, &cancel);
+            if let Err(e) = drive_scan::save_cache(&cache_path, &result) {
+                warn!("Failed to save drive scan cache: {}", e);
+            }
+            detector.apply_drive_scan(result.installs);
+        });
+    }
+
+    /// Stop a running drive scan
+    pub fn cancel_drive_scan(&self) {
+        self.scan_cancel.lock().unwrap().cancel();
+    }
+
+    /// Remember scan results and use them if nothing else found the game
+    fn apply_drive_scan(&self, installs: Vec<PathBuf>) {
+        *self.scanned_installs.write().unwrap() = installs;
+
+        if self.detected_path.read().unwrap().is_none() {
+            if let Some(path) = self.detect_eft_path() {
+                info!("Detected EFT installation at: {}", path);
+                *self.detected_path.write().unwrap() = Some(path);
+            }
+        }
+    }
+
     /// Get the EFT path (custom path takes priority over detected)
     pub fn get_eft_path(&self) -> Option<String> {
         // Custom path takes priority
@@ -74,6 +143,17 @@ impl EftDetector {
         self.detected_path.read().unwrap().clone()
     }
 
+    /// Prefer the install a running game was started from; None once it has exited.
+    /// Runs the install resolver, so call it off the async runtime.
+    pub fn set_running_install(&self, install: Option<PathBuf>) {
+        *self.running_install.write().unwrap() = install;
+
+        if let Some(path) = self.detect_eft_path() {
+            info!("Detected EFT installation at: {}", path);
+            *self.detected_path.write().unwrap() = Some(path);
+        }
+    }
+
     /// Set a custom EFT path
     pub fn set_custom_path(&self, path: Option<String>) {
         *self.custom_path.write().unwrap() = path;
@@ -81,68 +161,94 @@ impl EftDetector {
 
     /// Validate that a path contains EFT installation
     pub fn validate_eft_path(&self, path: &str) -> bool {
-        let path = Path::new(path);
-
-        // Check for EFT executable
-        let exe_path = path.join("EscapeFromTarkov.exe");
-        if !exe_path.exists() {
-            return false;
-        }
-
-        // Logs folder might not exist if game hasn't been run yet, that's ok
-        // But the EFT_Data folder should always exist
-        let data_path = path.join("EscapeFromTarkov_Data");
-        if !data_path.exists() {
-            return false;
-        }
+        validate_install(&RealFileSystem, Path::new(path)).is_ok()
+    }
 
-        true
+    /// Every install found by any source, valid ones first
+    pub fn find_installs(&self) -> Vec<InstallCandidate> {
+        let custom = self.custom_path.read().unwrap().clone();
+        self.resolver(
+            custom,
+            Arc::new(RealFileSystem),
+            registry::system_registry(),
+        )
+        .resolve()
     }
 
     /// Detect EFT installation path
     fn detect_eft_path(&self) -> Option<String> {
-        // Try registry first
-        if let Some(path) = self.detect_from_registry() {
-            if self.validate_eft_path(&path) {
-                return Some(path);
-            }
-        }
-
-        // Try common paths
-        for &default_path in DEFAULT_PATHS {
-            if self.validate_eft_path(default_path) {
-                return Some(default_path.to_string());
-            }
-        }
-
-        None
+        self.resolver(None, Arc::new(RealFileSystem), registry::system_registry())
+            .best()
+            .map(|candidate| candidate.path)
     }
 
-    /// Try to get EFT path from Windows Registry
-    fn detect_from_registry(&self) -> Option<String> {
-        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+    /// Run detection again, recording everything it looks at
+    pub fn diagnose(&self) -> DiagnosticsReport {
+        let log = ProbeLog::new();
+        let fs: Arc<dyn FileSystem> = Arc::new(RecordingFileSystem::new(
+            Arc::new(RealFileSystem),
+            log.clone(),
+        ));
+        let registry = Arc::new(RecordingRegistry::new(
+            registry::system_registry(),
+            log.clone(),
+        ));
 
-        for &(reg_path, value_name) in REGISTRY_PATHS {
-            if let Ok(key) = hklm.open_subkey(reg_path) {
-                if let Ok(path) = key.get_value::<String, _>(value_name) {
-                    info!("Found EFT path in registry: {}", path);
-                    return Some(path);
-                }
-            }
-        }
+        let custom_path = self.custom_path.read().unwrap().clone();
+        let candidates = self
+            .resolver(custom_path.clone(), fs.clone(), registry)
+            .resolve();
+        let resolved_path = self.get_eft_path();
 
-        // Also try HKEY_CURRENT_USER
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        for &(reg_path, value_name) in REGISTRY_PATHS {
-            if let Ok(key) = hkcu.open_subkey(reg_path) {
-                if let Ok(path) = key.get_value::<String, _>(value_name) {
-                    info!("Found EFT path in registry (HKCU): {}", path);
-                    return Some(path);
-                }
+        let (logs, version) = match &resolved_path {
+            Some(path) => {
+                let install = Path::new(path);
+                let logs_dir = install.join("Logs");
+                let mut logs = diagnostics::inspect_logs(fs.as_ref(), &logs_dir);
+                logs.read_only = std::fs::metadata(&logs_dir)
+                    .ok()
+                    .map(|m| m.permissions().readonly());
+                (Some(logs), version::detect_version(fs.as_ref(), install))
             }
+            None => (None, None),
+        };
+
+        DiagnosticsReport {
+            generated_at: Utc::now(),
+            companion_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            custom_path,
+            resolved_path,
+            version,
+            candidates,
+            logs,
+            probes: log.probes(),
         }
+    }
 
-        None
+    /// Install sources in rank order, reading through the given filesystem and registry
+    fn resolver(
+        &self,
+        custom_path: Option<String>,
+        fs: Arc<dyn FileSystem>,
+        registry: Arc<dyn RegistryBackend>,
+    ) -> InstallResolver {
+        let steam_roots = steam::steam_roots(registry.as_ref());
+        InstallResolver::new(fs)
+            .with_source(UserProvidedSource::new(custom_path))
+            .with_source(RunningProcessSource::new(
+                self.running_install.read().unwrap().clone(),
+            ))
+            // The launcher rewrites its settings when an install is moved, while
+            // the registry entry can be left pointing at the old folder
+            .with_source(LauncherSettingsSource::new())
+            .with_source(RegistrySource::new(registry.clone()))
+            .with_source(SteamSource::with_roots(steam_roots.clone()))
+            .with_source(WinePrefixSource::new(steam_roots))
+            .with_source(DefaultLocationSource::new())
+            .with_source(DriveScanSource::new(
+                self.scanned_installs.read().unwrap().clone(),
+            ))
     }
 
     /// Get the logs directory path
@@ -150,6 +256,23 @@ impl EftDetector {
         self.get_eft_path()
             .map(|p| Path::new(&p).join("Logs").to_string_lossy().to_string())
     }
+
+    /// Describe the resolved install, including its client version
+    pub fn get_install_info(&self) -> Option<InstallInfo> {
+        let path = self.get_eft_path()?;
+        let install = Path::new(&path);
+        Some(InstallInfo {
+            logs_path: install.join("Logs").to_string_lossy().to_string(),
+            version: version::detect_version(&RealFileSystem, install),
+            mode: spt::game_mode(&RealFileSystem, install),
+            path,
+        })
+    }
+}
+
+/// Game mode of an install folder
+pub fn game_mode(install: &Path) -> GameMode {
+    spt::game_mode(&RealFileSystem, install)
 }
 
 impl Default for EftDetector {
@@ -179,4 +302,31 @@ mod tests {
         let custom = detector.custom_path.read().unwrap();
         assert_eq!(*custom, Some("C:\\Test\\Path".to_string()));
     }
+
+    #[test]
+    fn test_scan_after_cancel_gets_fresh_token() {
+        let detector = Arc::new(EftDetector::new());
+        detector.cancel_drive_scan();
+
+        let dir = tempfile::tempdir().unwrap();
+        detector.start_drive_scan(dir.path().join("drive_scan.json"));
+        assert!(!detector.scan_cancel.lock().unwrap().is_cancelled());
+
+        detector.cancel_drive_scan();
+        assert!(detector.scan_cancel.lock().unwrap().is_cancelled());
+    }
+
+    #[test]
+    fn test_running_install_is_cleared_on_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("EscapeFromTarkov.exe"), "").unwrap();
+        std::fs::create_dir(dir.path().join("EscapeFromTarkov_Data")).unwrap();
+
+        let detector = EftDetector::new();
+        detector.set_running_install(Some(dir.path().to_path_buf()));
+        assert_eq!(detector.get_eft_path().as_deref(), dir.path().to_str());
+
+        detector.set_running_install(None);
+        assert!(detector.running_install.read().unwrap().is_none());
+    }
 }
@@ -0,0 +1,306 @@
+//! Install Diagnostics
+//!
+//! Explains what detection did, for bug reports. Detection is re-run with a
+//! filesystem and registry that record every probe, and the report adds:
+//! 1. Every candidate with its source and why it was rejected
+//! 2. Every registry value, file and folder looked at, and whether it existed
+//! 3. Access to the Logs folder and the newest session in it
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::install_source::{FileSystem, InstallCandidate};
+use super::registry::{Hive, RegistryBackend};
+use super::version::{self, GameVersion};
+
+/// What was probed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProbeKind {
+    Registry,
+    Path,
+    Directory,
+    File,
+    DirectoryListing,
+}
+
+/// One lookup made during detection
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Probe {
+    pub kind: ProbeKind,
+    pub target: String,
+    pub found: bool,
+    /// The value read or the error hit, if any
+    pub detail: Option<String>,
+}
+
+/// Probes recorded in order, without repeats
+#[derive(Default)]
+pub struct ProbeLog {
+    probes: Mutex<Vec<Probe>>,
+}
+
+impl ProbeLog {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    fn record(&self, kind: ProbeKind, target: String, found: bool, detail: Option<String>) {
+        let mut probes = self.probes.lock().unwrap();
+        if !probes.iter().any(|p| p.kind == kind && p.target == target) {
+            probes.push(Probe {
+                kind,
+                target,
+                found,
+                detail,
+            });
+        }
+    }
+
+    pub fn probes(&self) -> Vec<Probe> {
+        self.probes.lock().unwrap().clone()
+    }
+}
+
+/// A filesystem that records every lookup it passes on
+pub struct RecordingFileSystem {
+    inner: Arc<dyn FileSystem>,
+    log: Arc<ProbeLog>,
+}
+
+impl RecordingFileSystem {
+    pub fn new(inner: Arc<dyn FileSystem>, log: Arc<ProbeLog>) -> Self {
+        Self { inner, log }
+    }
+}
+
+fn error_detail<T>(result: &io::Result<T>) -> Option<String> {
+    result.as_ref().err().map(|e| e.to_string())
+}
+
+impl FileSystem for RecordingFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        let found = self.inner.exists(path);
+        self.log.record(ProbeKind::Path, display(path), found, None);
+        found
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let found = self.inner.is_dir(path);
+        self.log
+            .record(ProbeKind::Directory, display(path), found, None);
+        found
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let result = self.inner.read_to_string(path);
+        self.log.record(
+            ProbeKind::File,
+            display(path),
+            result.is_ok(),
+            error_detail(&result),
+        );
+        result
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let result = self.inner.read(path);
+        self.log.record(
+            ProbeKind::File,
+            display(path),
+            result.is_ok(),
+            error_detail(&result),
+        );
+        result
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        let result = self.inner.file_size(path);
+        self.log.record(
+            ProbeKind::File,
+            display(path),
+            result.is_ok(),
+            error_detail(&result),
+        );
+        result
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let result = self.inner.read_dir(path);
+        let detail = match &result {
+            Ok(entries) => Some(format!("{} entries", entries.len())),
+            Err(e) => Some(e.to_string()),
+        };
+        self.log.record(
+            ProbeKind::DirectoryListing,
+            display(path),
+            result.is_ok(),
+            detail,
+        );
+        result
+    }
+}
+
+/// A registry that records every value it is asked for
+pub struct RecordingRegistry {
+    inner: Arc<dyn RegistryBackend>,
+    log: Arc<ProbeLog>,
+}
+
+impl RecordingRegistry {
+    pub fn new(inner: Arc<dyn RegistryBackend>, log: Arc<ProbeLog>) -> Self {
+        Self { inner, log }
+    }
+}
+
+impl RegistryBackend for RecordingRegistry {
+    fn read_string(&self, hive: Hive, key: &str, value: &str) -> Option<String> {
+        let result = self.inner.read_string(hive, key, value);
+        let root = match hive {
+            Hive::LocalMachine => "HKLM",
+            Hive::CurrentUser => "HKCU",
+        };
+        self.log.record(
+            ProbeKind::Registry,
+            format!("{}\\{}\\{}", root, key, value),
+            result.is_some(),
+            result.clone(),
+        );
+        result
+    }
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// State of an install's Logs folder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsDiagnostics {
+    pub path: String,
+    pub exists: bool,
+    /// Whether the folder could be listed
+    pub readable: bool,
+    /// Whether the folder is marked read-only, if it could be checked
+    pub read_only: Option<bool>,
+    pub entries: usize,
+    pub newest_session: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Check that the Logs folder can be read and find its newest session
+pub fn inspect_logs(fs: &dyn FileSystem, logs_dir: &Path) -> LogsDiagnostics {
+    let listing = fs.read_dir(logs_dir);
+    LogsDiagnostics {
+        path: display(logs_dir),
+        exists: fs.is_dir(logs_dir),
+        readable: listing.is_ok(),
+        read_only: None,
+        entries: listing.as_ref().map(|e| e.len()).unwrap_or(0),
+        newest_session: version::newest_session(fs, logs_dir)
+            .and_then(|session| session.file_name().map(|n| n.to_string_lossy().to_string())),
+        error: error_detail(&listing),
+    }
+}
+
+/// Everything detection tried, ready to paste into a bug report
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsReport {
+    pub generated_at: DateTime<Utc>,
+    pub companion_version: String,
+    pub os: String,
+    pub custom_path: Option<String>,
+    pub resolved_path: Option<String>,
+    pub version: Option<GameVersion>,
+    pub candidates: Vec<InstallCandidate>,
+    pub logs: Option<LogsDiagnostics>,
+    pub probes: Vec<Probe>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::install_source::fake::FakeFileSystem;
+    use super::super::install_source::{InstallResolver, RegistrySource, UserProvidedSource};
+    use super::super::registry::fake::FakeRegistry;
+    use super::*;
+
+    #[test]
+    fn test_records_probes_without_repeats() {
+        let log = ProbeLog::new();
+        let mut fake = FakeFileSystem::new();
+        fake.add_file("/eft/EscapeFromTarkov.exe", "");
+        let fs = RecordingFileSystem::new(Arc::new(fake), log.clone());
+
+        assert!(fs.exists(Path::new("/eft/EscapeFromTarkov.exe")));
+        assert!(fs.exists(Path::new("/eft/EscapeFromTarkov.exe")));
+        assert!(fs.read_to_string(Path::new("/eft/missing.txt")).is_err());
+
+        let probes = log.probes();
+        assert_eq!(probes.len(), 2);
+        assert!(probes[0].found);
+        assert_eq!(probes[1].kind, ProbeKind::File);
+        assert!(!probes[1].found);
+        assert!(probes[1].detail.is_some());
+    }
+
+    #[test]
+    fn test_records_registry_lookups() {
+        let log = ProbeLog::new();
+        let mut fake = FakeRegistry::new();
+        fake.set(
+            Hive::LocalMachine,
+            "SOFTWARE\\Battlestate Games\\EscapeFromTarkov",
+            "InstallPath",
+            "C:\\Old\\EFT",
+        );
+        let registry = RecordingRegistry::new(Arc::new(fake), log.clone());
+
+        let mut fs = FakeFileSystem::new();
+        fs.add_dir("C:\\Old\\EFT");
+        let resolver = InstallResolver::new(Arc::new(fs))
+            .with_source(UserProvidedSource::new(None))
+            .with_source(RegistrySource::new(Arc::new(registry)));
+        let candidates = resolver.resolve();
+
+        assert_eq!(
+            candidates[0].rejection.as_deref(),
+            Some("Not an EFT install: EscapeFromTarkov.exe not found")
+        );
+        let probes = log.probes();
+        assert!(probes.iter().any(|p| p.kind == ProbeKind::Registry
+            && p.found
+            && p.target == "HKLM\\SOFTWARE\\Battlestate Games\\EscapeFromTarkov\\InstallPath"));
+        assert!(probes
+            .iter()
+            .any(|p| p.kind == ProbeKind::Registry && !p.found));
+    }
+
+    #[test]
+    fn test_inspect_logs() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_dir("/eft/Logs/log_2024.01.15_10-30-45_0.14.0.2.28375")
+            .add_dir("/eft/Logs/log_2024.01.14_10-30-45_0.14.0.1.28000");
+
+        let logs = inspect_logs(&fs, Path::new("/eft/Logs"));
+        assert!(logs.exists && logs.readable);
+        assert_eq!(logs.entries, 2);
+        assert_eq!(
+            logs.newest_session.as_deref(),
+            Some("log_2024.01.15_10-30-45_0.14.0.2.28375")
+        );
+    }
+
+    #[test]
+    fn test_inspect_missing_logs() {
+        let logs = inspect_logs(&FakeFileSystem::new(), Path::new("/eft/Logs"));
+        assert!(!logs.exists && !logs.readable);
+        assert!(logs.error.is_some());
+        assert_eq!(logs.newest_session, None);
+    }
+}
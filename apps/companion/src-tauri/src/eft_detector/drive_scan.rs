@@ -0,0 +1,337 @@
+//! Drive Scan
+//!
+//! Looks for EFT on every mounted volume, including mapped network shares:
+//! 1. List volumes (drive letters on Windows, `/proc/mounts` elsewhere; the
+//!    Linux root file system is left out, its game folders are found by other sources)
+//! 2. On each volume, check folders whose name looks like an EFT install,
+//!    at the top level and one level below common parents like `Games`
+//! 3. Stop when the time budget runs out or the scan is cancelled
+//!
+//! Results are cached on disk so start-up does not pay for a scan.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use super::install_source::FileSystem;
+
+/// How long a full scan may take
+pub const SCAN_BUDGET: Duration = Duration::from_secs(10);
+
+/// How long cached scan results are trusted
+const CACHE_MAX_AGE_DAYS: i64 = 7;
+
+/// Current version of the cache format
+const CACHE_VERSION: u32 = 1;
+
+/// Folders that commonly hold game installs, matched case-insensitively
+const PARENT_FOLDERS: &[&str] = &[
+    "Battlestate Games",
+    "Games",
+    "Program Files",
+    "Program Files (x86)",
+];
+
+/// File systems on Linux that can hold an install besides block devices
+const NETWORK_FS_TYPES: &[&str] = &["nfs", "nfs4", "cifs", "smb3", "smbfs"];
+
+/// Outcome of a drive scan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DriveScanResult {
+    pub volumes: Vec<PathBuf>,
+    pub installs: Vec<PathBuf>,
+    /// False if the scan ran out of time or was cancelled
+    pub complete: bool,
+}
+
+/// Mounted volumes on this machine
+pub fn list_volumes(fs: &dyn FileSystem) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        drive_letters(fs)
+    } else {
+        fs.read_to_string(Path::new("/proc/mounts"))
+            .map(|content| parse_mounts(&content))
+            .unwrap_or_default()
+    }
+}
+
+/// Drive letters that are present, including mapped network drives.
+/// A: and B: are skipped; probing floppy drives can stall for seconds.
+fn drive_letters(fs: &dyn FileSystem) -> Vec<PathBuf> {
+    (b'C'..=b'Z')
+        .map(|letter| PathBuf::from(format!("{}:\\", letter as char)))
+        .filter(|root| fs.is_dir(root))
+        .collect()
+}
+
+/// Mount points of real and network file systems in `/proc/mounts`, except `/`
+pub fn parse_mounts(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            let mount_point = fields.next()?;
+            let fs_type = fields.next()?;
+
+            let block_device = device.starts_with("/dev/") && !device.starts_with("/dev/loop");
+            if mount_point == "/" || (!block_device && !NETWORK_FS_TYPES.contains(&fs_type)) {
+                return None;
+            }
+            Some(PathBuf::from(mount_point.replace("\\040", " ")))
+        })
+        .collect()
+}
+
+/// Whether a folder name looks like an EFT install
+fn looks_like_install(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.contains("tarkov")
+        || name == "eft"
+        || name.starts_with("eft ")
+        || name.starts_with("eft_")
+        || name.starts_with("eft-")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Search volumes for EFT installs within a time budget
+pub fn scan(
+    fs: &dyn FileSystem,
+    volumes: &[PathBuf],
+    budget: Duration,
+    cancel: &CancellationToken,
+) -> DriveScanResult {
+    let deadline = Instant::now() + budget;
+    let out_of_time = || cancel.is_cancelled() || Instant::now() >= deadline;
+
+    let mut installs = Vec::new();
+    let mut complete = true;
+
+    let check = |folder: PathBuf, installs: &mut Vec<PathBuf>| {
+        if fs.exists(&folder.join("EscapeFromTarkov.exe")) && !installs.contains(&folder) {
+            info!("Drive scan found EFT at: {:?}", folder);
+            installs.push(folder);
+        }
+    };
+
+    'volumes: for volume in volumes {
+        if out_of_time() {
+            complete = false;
+            break;
+        }
+
+        let Ok(entries) = fs.read_dir(volume) else {
+            debug!("Could not read volume {:?}", volume);
+            continue;
+        };
+
+        for entry in entries {
+            let name = file_name(&entry);
+            if looks_like_install(&name) {
+                check(entry.clone(), &mut installs);
+            }
+            if !PARENT_FOLDERS.iter().any(|p| p.eq_ignore_ascii_case(&name)) {
+                continue;
+            }
+
+            if out_of_time() {
+                complete = false;
+                break 'volumes;
+            }
+            for child in fs.read_dir(&entry).unwrap_or_default() {
+                if looks_like_install(&file_name(&child)) {
+                    check(child, &mut installs);
+                }
+            }
+        }
+    }
+
+    if !complete {
+        warn!("Drive scan stopped early; results may be incomplete");
+    }
+
+    DriveScanResult {
+        volumes: volumes.to_vec(),
+        installs,
+        complete,
+    }
+}
+
+/// Persisted scan results
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    #[serde(rename = "scannedAt")]
+    scanned_at: DateTime<Utc>,
+    result: DriveScanResult,
+}
+
+/// Cached results, if they are recent, complete and for the same volumes
+pub fn load_cache(cache_path: &Path, volumes: &[PathBuf]) -> Option<DriveScanResult> {
+    let content = fs::read_to_string(cache_path).ok()?;
+    let cache: CacheFile = match serde_json::from_str(&content) {
+        Ok(cache) => cache,
+        Err(e) => {
+            warn!(
+                "Ignoring unreadable drive scan cache {:?}: {}",
+                cache_path, e
+            );
+            return None;
+        }
+    };
+
+    let fresh = Utc::now() - cache.scanned_at < ChronoDuration::days(CACHE_MAX_AGE_DAYS);
+    if cache.version != CACHE_VERSION
+        || !fresh
+        || !cache.result.complete
+        || cache.result.volumes != volumes
+    {
+        return None;
+    }
+
+    Some(cache.result)
+}
+
+/// Write scan results atomically (temp file, then rename)
+pub fn save_cache(cache_path: &Path, result: &DriveScanResult) -> std::io::Result<()> {
+    let cache = CacheFile {
+        version: CACHE_VERSION,
+        scanned_at: Utc::now(),
+        result: result.clone(),
+    };
+
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = cache_path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_vec(&cache)?)?;
+    fs::rename(&tmp_path, cache_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::install_source::fake::FakeFileSystem;
+    use super::*;
+
+    fn volumes() -> Vec<PathBuf> {
+        vec![PathBuf::from("/mnt/c"), PathBuf::from("/mnt/f")]
+    }
+
+    #[test]
+    fn test_parse_mounts() {
+        let mounts = "\
+sysfs /sys sysfs rw,nosuid 0 0
+proc /proc proc rw,nosuid 0 0
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+/dev/loop3 /snap/core/123 squashfs ro 0 0
+/dev/sdb1 /media/user/Games\\040Drive ntfs3 rw 0 0
+//nas/games /mnt/nas cifs rw 0 0
+tmpfs /run tmpfs rw 0 0
+";
+        assert_eq!(
+            parse_mounts(mounts),
+            vec![
+                PathBuf::from("/media/user/Games Drive"),
+                PathBuf::from("/mnt/nas"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_looks_like_install() {
+        assert!(looks_like_install("Escape from Tarkov"));
+        assert!(looks_like_install("EFT"));
+        assert!(looks_like_install("EFT Live"));
+        assert!(looks_like_install("tarkov-backup"));
+        assert!(!looks_like_install("Effects"));
+        assert!(!looks_like_install("Steam"));
+    }
+
+    #[test]
+    fn test_scan_finds_installs_on_any_volume() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file("/mnt/c/Battlestate Games/EFT/EscapeFromTarkov.exe", "")
+            .add_file("/mnt/f/Tarkov Live/EscapeFromTarkov.exe", "")
+            .add_file("/mnt/f/games/EFT Test/EscapeFromTarkov.exe", "")
+            // Name matches but there is no game inside
+            .add_dir("/mnt/f/Tarkov Screenshots")
+            // Too deep to be searched
+            .add_file("/mnt/f/Backup/Old/EFT/EscapeFromTarkov.exe", "");
+
+        let result = scan(&fs, &volumes(), SCAN_BUDGET, &CancellationToken::new());
+
+        assert!(result.complete);
+        assert_eq!(
+            result.installs,
+            vec![
+                PathBuf::from("/mnt/c/Battlestate Games/EFT"),
+                PathBuf::from("/mnt/f/Tarkov Live"),
+                PathBuf::from("/mnt/f/games/EFT Test"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_stops_when_cancelled() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file("/mnt/c/EFT/EscapeFromTarkov.exe", "");
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let result = scan(&fs, &volumes(), SCAN_BUDGET, &cancel);
+
+        assert!(!result.complete);
+        assert!(result.installs.is_empty());
+    }
+
+    #[test]
+    fn test_scan_stops_when_out_of_time() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file("/mnt/c/EFT/EscapeFromTarkov.exe", "");
+
+        let result = scan(&fs, &volumes(), Duration::ZERO, &CancellationToken::new());
+        assert!(!result.complete);
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("drive_scan.json");
+        let result = DriveScanResult {
+            volumes: volumes(),
+            installs: vec![PathBuf::from("/mnt/f/Tarkov Live")],
+            complete: true,
+        };
+
+        save_cache(&cache_path, &result).unwrap();
+
+        assert_eq!(load_cache(&cache_path, &volumes()), Some(result));
+        // A new or removed volume invalidates the cache
+        assert_eq!(load_cache(&cache_path, &volumes()[..1]), None);
+    }
+
+    #[test]
+    fn test_incomplete_scan_is_not_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("drive_scan.json");
+        let result = DriveScanResult {
+            volumes: volumes(),
+            installs: Vec::new(),
+            complete: false,
+        };
+
+        save_cache(&cache_path, &result).unwrap();
+        assert_eq!(load_cache(&cache_path, &volumes()), None);
+    }
+}
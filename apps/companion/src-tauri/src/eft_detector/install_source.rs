@@ -0,0 +1,801 @@
+//! Install Sources
+//!
+//! Each place an EFT install can be found is an `InstallSource`. The
+//! `InstallResolver` asks every source in rank order and returns all
+//! candidates with their validation result, so the UI can let the user pick
+//! when more than one install is found.
+//!
+//! Sources read through the `FileSystem` trait so they can be tested against
+//! a fake filesystem.
+
+use log::{debug, info};
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use super::registry::{Hive, RegistryBackend};
+use super::wine::{self, WineRegistry};
+use super::{drive_scan, launcher_settings, steam};
+use crate::error::CompanionError;
+
+/// Default installation paths to check if no other source finds the game
+pub const DEFAULT_PATHS: &[&str] = &[
+    "C:\\Battlestate Games\\Escape from Tarkov",
+    "C:\\Battlestate Games\\EFT",
+    "D:\\Battlestate Games\\Escape from Tarkov",
+    "D:\\Battlestate Games\\EFT",
+    "C:\\Games\\Escape from Tarkov",
+    "C:\\Games\\EFT",
+    "D:\\Games\\Escape from Tarkov",
+    "D:\\Games\\EFT",
+    "E:\\Battlestate Games\\Escape from Tarkov",
+    "E:\\Battlestate Games\\EFT",
+];
+
+/// Registry values where the BSG Launcher stores the EFT install path
+const REGISTRY_PATHS: &[(&str, &str)] = &[
+    (
+        "SOFTWARE\\Battlestate Games\\EscapeFromTarkov",
+        "InstallPath",
+    ),
+    (
+        "SOFTWARE\\WOW6432Node\\Battlestate Games\\EscapeFromTarkov",
+        "InstallPath",
+    ),
+];
+
+/// Filesystem access used by install sources
+pub trait FileSystem: Send + Sync {
+    fn exists(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// Size of a file in bytes
+    fn file_size(&self, path: &Path) -> io::Result<u64>;
+    /// Entries of a directory, in no particular order
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+}
+
+/// The real filesystem
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        Ok(std::fs::metadata(path)?.len())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+}
+
+/// Where an install candidate came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InstallSourceKind {
+    UserProvided,
+    RunningProcess,
+    LauncherSettings,
+    Registry,
+    Steam,
+    WinePrefix,
+    DefaultLocation,
+    DriveScan,
+}
+
+/// A place that may know where EFT is installed
+pub trait InstallSource: Send + Sync {
+    fn kind(&self) -> InstallSourceKind;
+
+    /// Paths this source believes are EFT installs, best first
+    fn candidates(&self, fs: &dyn FileSystem) -> Vec<PathBuf>;
+}
+
+/// A possible EFT install and whether it passed validation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallCandidate {
+    pub path: String,
+    pub source: InstallSourceKind,
+    pub valid: bool,
+    /// Why the path was rejected, if it was
+    pub rejection: Option<String>,
+}
+
+/// Check that a folder contains an EFT install
+pub fn validate_install(fs: &dyn FileSystem, path: &Path) -> Result<(), CompanionError> {
+    let invalid = |reason: &str| Err(CompanionError::InvalidInstall(reason.to_string()));
+    if !fs.is_dir(path) {
+        return invalid("Folder does not exist");
+    }
+
+    if !fs.exists(&path.join("EscapeFromTarkov.exe")) {
+        return invalid("EscapeFromTarkov.exe not found");
+    }
+
+    // Logs folder might not exist if game hasn't been run yet, that's ok
+    // But the EFT_Data folder should always exist
+    if !fs.exists(&path.join("EscapeFromTarkov_Data")) {
+        return invalid("EscapeFromTarkov_Data folder not found");
+    }
+
+    Ok(())
+}
+
+/// Asks install sources in rank order and validates what they find
+pub struct InstallResolver {
+    fs: Arc<dyn FileSystem>,
+    sources: Vec<Box<dyn InstallSource>>,
+}
+
+impl InstallResolver {
+    pub fn new(fs: Arc<dyn FileSystem>) -> Self {
+        Self {
+            fs,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Add a source ranked below those already added
+    pub fn with_source(mut self, source: impl InstallSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Every candidate from every source, without duplicates.
+    /// Valid candidates come first; within each group the source rank is kept.
+    pub fn resolve(&self) -> Vec<InstallCandidate> {
+        let mut seen = Vec::new();
+        let mut candidates = Vec::new();
+
+        for source in &self.sources {
+            for path in source.candidates(self.fs.as_ref()) {
+                let key = path_key(&path);
+                if seen.contains(&key) {
+                    continue;
+                }
+                seen.push(key);
+
+                let result = validate_install(self.fs.as_ref(), &path);
+                if let Err(reason) = &result {
+                    debug!("Rejected {:?} from {:?}: {}", path, source.kind(), reason);
+                }
+                candidates.push(InstallCandidate {
+                    path: path.to_string_lossy().to_string(),
+                    source: source.kind(),
+                    valid: result.is_ok(),
+                    rejection: result.err().map(|e| e.to_string()),
+                });
+            }
+        }
+
+        candidates.sort_by_key(|c| !c.valid);
+        candidates
+    }
+
+    /// The highest ranked valid install
+    pub fn best(&self) -> Option<InstallCandidate> {
+        self.resolve().into_iter().find(|c| c.valid)
+    }
+}
+
+/// Comparison key for a path, ignoring a trailing separator. Only Windows paths are
+/// case-insensitive; Linux and Wine trees may hold installs differing only in case.
+fn path_key(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        path.trim_end_matches(['\\', '/'])
+            .replace('/', "\\")
+            .to_lowercase()
+    } else {
+        path.trim_end_matches('/').to_string()
+    }
+}
+
+/// A path the user chose in settings
+pub struct UserProvidedSource {
+    path: Option<String>,
+}
+
+impl UserProvidedSource {
+    pub fn new(path: Option<String>) -> Self {
+        Self { path }
+    }
+}
+
+impl InstallSource for UserProvidedSource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::UserProvided
+    }
+
+    fn candidates(&self, _fs: &dyn FileSystem) -> Vec<PathBuf> {
+        self.path.iter().map(PathBuf::from).collect()
+    }
+}
+
+/// Install folders from the BSG Launcher settings file
+pub struct LauncherSettingsSource {
+    settings_files: Vec<PathBuf>,
+}
+
+impl LauncherSettingsSource {
+    /// Look in the current user's roaming AppData folder
+    pub fn new() -> Self {
+        let settings_files = std::env::var_os("APPDATA")
+            .map(|appdata| launcher_settings::settings_files(Path::new(&appdata)))
+            .unwrap_or_default();
+        Self::with_files(settings_files)
+    }
+
+    pub fn with_files(settings_files: Vec<PathBuf>) -> Self {
+        Self { settings_files }
+    }
+}
+
+impl Default for LauncherSettingsSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstallSource for LauncherSettingsSource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::LauncherSettings
+    }
+
+    fn candidates(&self, fs: &dyn FileSystem) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for file in &self.settings_files {
+            let Ok(content) = fs.read_to_string(file) else {
+                continue;
+            };
+            match launcher_settings::parse_install_paths(&content) {
+                Ok(found) => paths.extend(found),
+                Err(e) => debug!("Could not parse {:?}: {}", file, e),
+            }
+        }
+        paths
+    }
+}
+
+/// Install path the BSG Launcher writes to the registry
+pub struct RegistrySource {
+    registry: Arc<dyn RegistryBackend>,
+}
+
+impl RegistrySource {
+    pub fn new(registry: Arc<dyn RegistryBackend>) -> Self {
+        Self { registry }
+    }
+}
+
+impl InstallSource for RegistrySource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::Registry
+    }
+
+    fn candidates(&self, _fs: &dyn FileSystem) -> Vec<PathBuf> {
+        registry_install_paths(self.registry.as_ref())
+            .into_iter()
+            .map(|path| {
+                info!("Found EFT path in registry: {}", path);
+                PathBuf::from(path)
+            })
+            .collect()
+    }
+}
+
+/// Install paths the BSG Launcher recorded in a registry, machine-wide first
+pub fn registry_install_paths(registry: &dyn RegistryBackend) -> Vec<String> {
+    [Hive::LocalMachine, Hive::CurrentUser]
+        .iter()
+        .flat_map(|&hive| {
+            REGISTRY_PATHS
+                .iter()
+                .filter_map(move |&(key, value)| registry.read_string(hive, key, value))
+        })
+        .collect()
+}
+
+/// Steam edition installs across all Steam libraries
+pub struct SteamSource {
+    roots: Vec<PathBuf>,
+}
+
+impl SteamSource {
+    /// Look in the given Steam installations
+    pub fn with_roots(roots: Vec<PathBuf>) -> Self {
+        Self { roots }
+    }
+}
+
+impl InstallSource for SteamSource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::Steam
+    }
+
+    fn candidates(&self, fs: &dyn FileSystem) -> Vec<PathBuf> {
+        self.roots
+            .iter()
+            .filter_map(|root| steam::find_eft_install(fs, root))
+            .collect()
+    }
+}
+
+/// Installs inside Wine, Lutris, Bottles and Proton prefixes
+pub struct WinePrefixSource {
+    home: Option<PathBuf>,
+    steam_roots: Vec<PathBuf>,
+}
+
+impl WinePrefixSource {
+    /// Look in the current user's home folder and the given Steam installations
+    pub fn new(steam_roots: Vec<PathBuf>) -> Self {
+        Self::with_home(std::env::var_os("HOME").map(PathBuf::from), steam_roots)
+    }
+
+    pub fn with_home(home: Option<PathBuf>, steam_roots: Vec<PathBuf>) -> Self {
+        Self { home, steam_roots }
+    }
+}
+
+impl InstallSource for WinePrefixSource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::WinePrefix
+    }
+
+    fn candidates(&self, fs: &dyn FileSystem) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        let prefixes = wine::discover_prefixes(fs, self.home.as_deref(), &self.steam_roots);
+        for prefix in &prefixes {
+            let registry = WineRegistry::load(fs, prefix);
+            let windows_paths = registry_install_paths(&registry)
+                .into_iter()
+                .chain(DEFAULT_PATHS.iter().map(|p| p.to_string()));
+
+            for windows_path in windows_paths {
+                let Some(path) = wine::to_host_path(prefix, &windows_path) else {
+                    continue;
+                };
+                if fs.is_dir(&path) && !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+
+        // Installs in other folders of a prefix's C: drive, searched like a volume
+        let drives: Vec<PathBuf> = prefixes.iter().map(|p| p.join("drive_c")).collect();
+        let scan = drive_scan::scan(
+            fs,
+            &drives,
+            drive_scan::SCAN_BUDGET,
+            &CancellationToken::new(),
+        );
+        for path in scan.installs {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+}
+
+/// Common installation folders
+pub struct DefaultLocationSource {
+    paths: Vec<PathBuf>,
+}
+
+impl DefaultLocationSource {
+    pub fn new() -> Self {
+        Self {
+            paths: DEFAULT_PATHS.iter().map(PathBuf::from).collect(),
+        }
+    }
+}
+
+impl Default for DefaultLocationSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstallSource for DefaultLocationSource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::DefaultLocation
+    }
+
+    fn candidates(&self, fs: &dyn FileSystem) -> Vec<PathBuf> {
+        // Only report folders that exist; the rest are just guesses
+        self.paths
+            .iter()
+            .filter(|p| fs.is_dir(p))
+            .cloned()
+            .collect()
+    }
+}
+
+/// The install the game is currently running from
+pub struct RunningProcessSource {
+    install: Option<PathBuf>,
+}
+
+impl RunningProcessSource {
+    pub fn new(install: Option<PathBuf>) -> Self {
+        Self { install }
+    }
+}
+
+impl InstallSource for RunningProcessSource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::RunningProcess
+    }
+
+    fn candidates(&self, _fs: &dyn FileSystem) -> Vec<PathBuf> {
+        self.install.clone().into_iter().collect()
+    }
+}
+
+/// Installs found by the last drive scan
+pub struct DriveScanSource {
+    installs: Vec<PathBuf>,
+}
+
+impl DriveScanSource {
+    pub fn new(installs: Vec<PathBuf>) -> Self {
+        Self { installs }
+    }
+}
+
+impl InstallSource for DriveScanSource {
+    fn kind(&self) -> InstallSourceKind {
+        InstallSourceKind::DriveScan
+    }
+
+    fn candidates(&self, _fs: &dyn FileSystem) -> Vec<PathBuf> {
+        self.installs.clone()
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::FileSystem;
+    use std::collections::{HashMap, HashSet};
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    /// In-memory filesystem for source tests
+    #[derive(Default)]
+    pub struct FakeFileSystem {
+        files: HashMap<PathBuf, Vec<u8>>,
+        dirs: HashSet<PathBuf>,
+    }
+
+    impl FakeFileSystem {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn add_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+            let path = path.into();
+            for ancestor in path.ancestors() {
+                if !ancestor.as_os_str().is_empty() {
+                    self.dirs.insert(ancestor.to_path_buf());
+                }
+            }
+            self
+        }
+
+        pub fn add_file(&mut self, path: impl Into<PathBuf>, content: &str) -> &mut Self {
+            self.add_bytes(path, content.as_bytes().to_vec())
+        }
+
+        pub fn add_bytes(&mut self, path: impl Into<PathBuf>, content: Vec<u8>) -> &mut Self {
+            let path = path.into();
+            if let Some(parent) = path.parent() {
+                self.add_dir(parent);
+            }
+            self.files.insert(path, content);
+            self
+        }
+
+        /// Add a folder that passes install validation
+        pub fn add_install(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+            let path = path.into();
+            self.add_file(path.join("EscapeFromTarkov.exe"), "")
+                .add_dir(path.join("EscapeFromTarkov_Data"))
+        }
+    }
+
+    impl FileSystem for FakeFileSystem {
+        fn exists(&self, path: &Path) -> bool {
+            self.files.contains_key(path) || self.dirs.contains(path)
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            self.dirs.contains(path)
+        }
+
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            String::from_utf8(self.read(path)?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            self.files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn file_size(&self, path: &Path) -> io::Result<u64> {
+            Ok(self.read(path)?.len() as u64)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            if !self.dirs.contains(path) {
+                return Err(io::Error::from(io::ErrorKind::NotFound));
+            }
+            let mut entries: Vec<PathBuf> = self
+                .dirs
+                .iter()
+                .chain(self.files.keys())
+                .filter(|entry| entry.parent() == Some(path))
+                .cloned()
+                .collect();
+            entries.sort();
+            Ok(entries)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::registry::fake::FakeRegistry;
+    use super::fake::FakeFileSystem;
+    use super::*;
+
+    struct FixedSource(InstallSourceKind, Vec<&'static str>);
+
+    impl InstallSource for FixedSource {
+        fn kind(&self) -> InstallSourceKind {
+            self.0
+        }
+
+        fn candidates(&self, _fs: &dyn FileSystem) -> Vec<PathBuf> {
+            self.1.iter().map(PathBuf::from).collect()
+        }
+    }
+
+    #[test]
+    fn test_validate_install_reasons() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_install("C:\\EFT")
+            .add_dir("C:\\Empty")
+            .add_file("C:\\NoData/EscapeFromTarkov.exe", "");
+
+        assert_eq!(validate_install(&fs, Path::new("C:\\EFT")), Ok(()));
+        assert_eq!(
+            validate_install(&fs, Path::new("C:\\Missing")),
+            Err(CompanionError::InvalidInstall(
+                "Folder does not exist".to_string()
+            ))
+        );
+        assert_eq!(
+            validate_install(&fs, Path::new("C:\\Empty")),
+            Err(CompanionError::InvalidInstall(
+                "EscapeFromTarkov.exe not found".to_string()
+            ))
+        );
+        assert_eq!(
+            validate_install(&fs, Path::new("C:\\NoData")),
+            Err(CompanionError::InvalidInstall(
+                "EscapeFromTarkov_Data folder not found".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_resolver_returns_every_candidate() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_install("C:\\EFT").add_install("D:\\Steam\\EFT");
+
+        let resolver = InstallResolver::new(Arc::new(fs))
+            .with_source(FixedSource(InstallSourceKind::Registry, vec!["C:\\Old"]))
+            .with_source(FixedSource(
+                InstallSourceKind::Steam,
+                vec!["D:\\Steam\\EFT"],
+            ))
+            .with_source(FixedSource(
+                InstallSourceKind::DefaultLocation,
+                vec!["C:\\EFT"],
+            ));
+
+        let candidates = resolver.resolve();
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].source, InstallSourceKind::Steam);
+        assert_eq!(candidates[1].source, InstallSourceKind::DefaultLocation);
+        assert!(candidates[0].valid && candidates[1].valid);
+        assert_eq!(candidates[2].path, "C:\\Old");
+        assert!(!candidates[2].valid);
+        assert_eq!(
+            candidates[2].rejection.as_deref(),
+            Some("Not an EFT install: Folder does not exist")
+        );
+    }
+
+    #[test]
+    fn test_resolver_keeps_first_source_for_duplicates() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_install("C:\\EFT");
+
+        let with_separator = if cfg!(windows) {
+            "C:\\EFT\\"
+        } else {
+            "C:\\EFT/"
+        };
+        let resolver = InstallResolver::new(Arc::new(fs))
+            .with_source(FixedSource(
+                InstallSourceKind::Registry,
+                vec![with_separator],
+            ))
+            .with_source(FixedSource(
+                InstallSourceKind::DefaultLocation,
+                vec!["C:\\EFT"],
+            ));
+
+        let candidates = resolver.resolve();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].source, InstallSourceKind::Registry);
+    }
+
+    #[test]
+    fn test_path_key_folds_case_only_on_windows() {
+        let upper = path_key(Path::new("/home/user/Games/EFT"));
+        let lower = path_key(Path::new("/home/user/Games/eft"));
+        assert_eq!(upper == lower, cfg!(windows));
+    }
+
+    #[test]
+    fn test_best_prefers_rank() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_install("C:\\Custom").add_install("C:\\EFT");
+
+        let resolver = InstallResolver::new(Arc::new(fs))
+            .with_source(UserProvidedSource::new(Some("C:\\Custom".to_string())))
+            .with_source(FixedSource(InstallSourceKind::Registry, vec!["C:\\EFT"]));
+
+        let best = resolver.best().unwrap();
+        assert_eq!(best.path, "C:\\Custom");
+        assert_eq!(best.source, InstallSourceKind::UserProvided);
+    }
+
+    #[test]
+    fn test_best_without_valid_install() {
+        let resolver = InstallResolver::new(Arc::new(FakeFileSystem::new()))
+            .with_source(UserProvidedSource::new(Some("C:\\Nope".to_string())));
+
+        assert_eq!(resolver.resolve().len(), 1);
+        assert!(resolver.best().is_none());
+    }
+
+    #[test]
+    fn test_default_locations_skip_missing_folders() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_install("D:\\Games\\EFT");
+
+        let source = DefaultLocationSource::new();
+        assert_eq!(
+            source.candidates(&fs),
+            vec![PathBuf::from("D:\\Games\\EFT")]
+        );
+    }
+
+    #[test]
+    fn test_launcher_settings_source() {
+        let install = PathBuf::from("D:\\Battlestate Games")
+            .join("EFT")
+            .to_string_lossy()
+            .to_string();
+        let mut fs = FakeFileSystem::new();
+        fs.add_file(
+            "/appdata/settings",
+            r#"{"gamesRootDir": "D:\\Battlestate Games"}"#,
+        )
+        .add_install(&install);
+
+        let resolver = InstallResolver::new(Arc::new(fs)).with_source(
+            LauncherSettingsSource::with_files(vec![
+                PathBuf::from("/appdata/missing"),
+                PathBuf::from("/appdata/settings"),
+            ]),
+        );
+
+        let best = resolver.best().unwrap();
+        assert_eq!(best.path, install);
+        assert_eq!(best.source, InstallSourceKind::LauncherSettings);
+        assert_eq!(resolver.resolve().len(), 2);
+    }
+
+    #[test]
+    fn test_registry_source() {
+        let mut registry = FakeRegistry::new();
+        registry.set(
+            Hive::CurrentUser,
+            "SOFTWARE\\Battlestate Games\\EscapeFromTarkov",
+            "InstallPath",
+            "C:\\Battlestate Games\\EFT",
+        );
+
+        let source = RegistrySource::new(Arc::new(registry));
+        assert_eq!(
+            source.candidates(&FakeFileSystem::new()),
+            vec![PathBuf::from("C:\\Battlestate Games\\EFT")]
+        );
+    }
+
+    #[test]
+    fn test_wine_prefix_source_follows_prefix_registry() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file(
+            "/home/user/.wine/system.reg",
+            r#"
+[Software\\Battlestate Games\\EscapeFromTarkov] 1700000000
+"InstallPath"="D:\\Tarkov"
+"#,
+        )
+        .add_install("/home/user/.wine/drive_c/Battlestate Games/EFT")
+        .add_install("/home/user/.wine/dosdevices/d:/Tarkov");
+
+        let source = WinePrefixSource::with_home(Some(PathBuf::from("/home/user")), Vec::new());
+        assert_eq!(
+            source.candidates(&fs),
+            vec![
+                PathBuf::from("/home/user/.wine/dosdevices/d:/Tarkov"),
+                PathBuf::from("/home/user/.wine/drive_c/Battlestate Games/EFT"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wine_prefix_source_searches_drive_c() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_dir("/home/user/.wine/drive_c")
+            .add_install("/home/user/.local/share/bottles/bottles/Tarkov/drive_c/Games/Tarkov Live")
+            .add_install("/home/user/.local/share/bottles/bottles/Tarkov/drive_c/EFT-Backup")
+            // Too deep to be searched
+            .add_install(
+                "/home/user/.local/share/bottles/bottles/Tarkov/drive_c/users/user/Desktop/EFT",
+            );
+
+        let source = WinePrefixSource::with_home(Some(PathBuf::from("/home/user")), Vec::new());
+        assert_eq!(
+            source.candidates(&fs),
+            vec![
+                PathBuf::from("/home/user/.local/share/bottles/bottles/Tarkov/drive_c/EFT-Backup"),
+                PathBuf::from(
+                    "/home/user/.local/share/bottles/bottles/Tarkov/drive_c/Games/Tarkov Live"
+                ),
+            ]
+        );
+    }
+}
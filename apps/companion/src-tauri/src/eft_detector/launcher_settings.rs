@@ -0,0 +1,140 @@
+//! BSG Launcher Settings
+//!
+//! The BSG Launcher keeps its games root and per-game install folders in a
+//! JSON settings file under `%APPDATA%\Battlestate Games\BsgLauncher`. Two
+//! formats are in the wild:
+//! 1. Older launchers only store `gamesRootDir`; EFT lives in a folder below it
+//! 2. Newer launchers also store a `games` list with an `installPath` per game
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Game ids the launcher uses for Escape from Tarkov
+const EFT_GAME_IDS: &[&str] = &["eft", "escapefromtarkov"];
+
+/// Folder names the launcher installs EFT to below the games root
+const EFT_FOLDER_NAMES: &[&str] = &["Escape from Tarkov", "EFT"];
+
+/// Settings file names the launcher has used
+const SETTINGS_FILE_NAMES: &[&str] = &["settings", "settings.json"];
+
+/// Possible settings files below a roaming AppData folder
+pub fn settings_files(appdata: &Path) -> Vec<PathBuf> {
+    let dir = appdata.join("Battlestate Games").join("BsgLauncher");
+    SETTINGS_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .collect()
+}
+
+/// Install folders named in a launcher settings file, most specific first
+pub fn parse_install_paths(content: &str) -> Result<Vec<PathBuf>, String> {
+    let settings: Value =
+        serde_json::from_str(content).map_err(|e| format!("Invalid launcher settings: {}", e))?;
+
+    let mut paths = Vec::new();
+
+    // Newer format: an explicit install path per game, as a list or keyed by id
+    match settings.get("games") {
+        Some(Value::Array(games)) => {
+            for game in games {
+                let id = game.get("id").and_then(|id| id.as_str()).unwrap_or("");
+                if is_eft(id) {
+                    paths.extend(install_path(game));
+                }
+            }
+        }
+        Some(Value::Object(games)) => {
+            for (id, game) in games {
+                if is_eft(id) {
+                    paths.extend(install_path(game));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // Older format: only the games root is known
+    let root = ["gamesRootDir", "gameRootDir"]
+        .iter()
+        .find_map(|key| settings.get(*key).and_then(|v| v.as_str()))
+        .filter(|root| !root.is_empty());
+    if let Some(root) = root {
+        let root = root.trim_end_matches(['\\', '/']);
+        for name in EFT_FOLDER_NAMES {
+            paths.push(PathBuf::from(root).join(name));
+        }
+    }
+
+    Ok(paths)
+}
+
+fn is_eft(id: &str) -> bool {
+    EFT_GAME_IDS.iter().any(|eft| id.eq_ignore_ascii_case(eft))
+}
+
+fn install_path(game: &Value) -> Option<PathBuf> {
+    ["installPath", "installDir"]
+        .iter()
+        .find_map(|key| game.get(*key).and_then(|v| v.as_str()))
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: &str = include_str!("../../tests/fixtures/bsg_launcher/settings.json");
+    const SETTINGS_LEGACY: &str =
+        include_str!("../../tests/fixtures/bsg_launcher/settings_legacy.json");
+
+    #[test]
+    fn test_parse_current_format() {
+        let paths = parse_install_paths(SETTINGS).unwrap();
+        assert_eq!(paths[0], PathBuf::from("E:\\Games\\EFT Live"));
+        assert_eq!(
+            paths[1],
+            PathBuf::from("D:\\Battlestate Games").join("Escape from Tarkov")
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_format() {
+        let paths = parse_install_paths(SETTINGS_LEGACY).unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("C:\\Battlestate Games").join("Escape from Tarkov"),
+                PathBuf::from("C:\\Battlestate Games").join("EFT"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_games_keyed_by_id() {
+        let paths =
+            parse_install_paths(r#"{"games": {"EFT": {"installDir": "F:\\Tarkov"}}}"#).unwrap();
+        assert_eq!(paths, vec![PathBuf::from("F:\\Tarkov")]);
+    }
+
+    #[test]
+    fn test_ignores_other_games() {
+        let paths =
+            parse_install_paths(r#"{"games": [{"id": "arena", "installPath": "C:\\Arena"}]}"#)
+                .unwrap();
+        assert!(paths.is_empty());
+    }
+
+    #[test]
+    fn test_settings_files() {
+        let files = settings_files(Path::new("/appdata"));
+        assert_eq!(files.len(), 2);
+        assert!(files[0].ends_with("Battlestate Games/BsgLauncher/settings"));
+    }
+
+    #[test]
+    fn test_parse_invalid_json() {
+        assert!(parse_install_paths("not json").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Registry Backends
+//!
+//! Detection reads the Windows Registry through `RegistryBackend`, so the
+//! crate builds and tests on every platform:
+//! 1. `WindowsRegistry` reads the real registry (Windows only)
+//! 2. `NoRegistry` finds nothing, for platforms without a registry
+
+use std::sync::Arc;
+
+/// Registry root a key lives under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hive {
+    LocalMachine,
+    CurrentUser,
+}
+
+/// Read access to registry string values
+pub trait RegistryBackend: Send + Sync {
+    /// Read a string value, or None if the key or value does not exist
+    fn read_string(&self, hive: Hive, key: &str, value: &str) -> Option<String>;
+}
+
+/// The registry of the machine the companion runs on
+#[cfg(windows)]
+pub fn system_registry() -> Arc<dyn RegistryBackend> {
+    Arc::new(WindowsRegistry)
+}
+
+/// The registry of the machine the companion runs on
+#[cfg(not(windows))]
+pub fn system_registry() -> Arc<dyn RegistryBackend> {
+    Arc::new(NoRegistry)
+}
+
+/// The Windows Registry
+#[cfg(windows)]
+pub struct WindowsRegistry;
+
+#[cfg(windows)]
+impl RegistryBackend for WindowsRegistry {
+    fn read_string(&self, hive: Hive, key: &str, value: &str) -> Option<String> {
+        use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+        use winreg::RegKey;
+
+        let root = match hive {
+            Hive::LocalMachine => RegKey::predef(HKEY_LOCAL_MACHINE),
+            Hive::CurrentUser => RegKey::predef(HKEY_CURRENT_USER),
+        };
+        root.open_subkey(key)
+            .ok()?
+            .get_value::<String, _>(value)
+            .ok()
+    }
+}
+
+/// A registry with no keys
+#[cfg_attr(windows, allow(dead_code))]
+pub struct NoRegistry;
+
+impl RegistryBackend for NoRegistry {
+    fn read_string(&self, _hive: Hive, _key: &str, _value: &str) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::{Hive, RegistryBackend};
+    use std::collections::HashMap;
+
+    /// In-memory registry for source tests
+    #[derive(Default)]
+    pub struct FakeRegistry {
+        values: HashMap<(Hive, String, String), String>,
+    }
+
+    impl FakeRegistry {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn set(&mut self, hive: Hive, key: &str, value: &str, data: &str) -> &mut Self {
+            self.values.insert(
+                (hive, key.to_lowercase(), value.to_lowercase()),
+                data.to_string(),
+            );
+            self
+        }
+    }
+
+    impl RegistryBackend for FakeRegistry {
+        fn read_string(&self, hive: Hive, key: &str, value: &str) -> Option<String> {
+            self.values
+                .get(&(hive, key.to_lowercase(), value.to_lowercase()))
+                .cloned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_registry_finds_nothing() {
+        assert_eq!(
+            NoRegistry.read_string(Hive::LocalMachine, "SOFTWARE\\Valve\\Steam", "InstallPath"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_system_registry_does_not_panic() {
+        // Keys may or may not exist depending on the machine
+        let _ =
+            system_registry().read_string(Hive::CurrentUser, "Software\\Valve\\Steam", "SteamPath");
+    }
+}
@@ -0,0 +1,95 @@
+//! Single Player Tarkov
+//!
+//! SPT runs the EFT client against a local server kept in the install folder:
+//! 1. The client logs to the install's `Logs` folder like the live game, including the
+//!    quest messages the local server sends it; BepInEx plugin output goes to
+//!    `BepInEx/LogOutput.log`
+//! 2. The server (`SPT.Server.exe`, `Aki.Server.exe` before 3.10) logs to `user/logs`
+//! 3. Each profile, with its quest states, is a JSON file in `user/profiles`
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::install_source::FileSystem;
+
+/// Server executables of current and older SPT releases
+const SERVER_EXECUTABLES: &[&str] = &["SPT.Server.exe", "Aki.Server.exe"];
+
+/// Which game an install plays
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GameMode {
+    /// The official game, online
+    #[default]
+    Live,
+    /// Single Player Tarkov, offline against a local server
+    Spt,
+}
+
+impl GameMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameMode::Live => "live",
+            GameMode::Spt => "spt",
+        }
+    }
+}
+
+/// Whether an install has the SPT server and BepInEx next to the client
+pub fn is_spt_install(fs: &dyn FileSystem, install: &Path) -> bool {
+    fs.is_dir(&install.join("BepInEx"))
+        && SERVER_EXECUTABLES
+            .iter()
+            .any(|exe| fs.exists(&install.join(exe)))
+}
+
+/// Game mode of an install
+pub fn game_mode(fs: &dyn FileSystem, install: &Path) -> GameMode {
+    if is_spt_install(fs, install) {
+        GameMode::Spt
+    } else {
+        GameMode::Live
+    }
+}
+
+/// Where an SPT install writes quest progress
+#[derive(Debug, Clone, PartialEq)]
+pub struct SptPaths {
+    /// The client's Logs folder, with one folder per game session
+    pub client_logs: PathBuf,
+    /// Folder holding one JSON file per profile
+    pub profiles: PathBuf,
+}
+
+impl SptPaths {
+    pub fn new(install: &Path) -> Self {
+        Self {
+            client_logs: install.join("Logs"),
+            profiles: install.join("user").join("profiles"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::install_source::fake::FakeFileSystem;
+    use super::*;
+
+    #[test]
+    fn test_detects_spt_installs() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_install("/games/SPT")
+            .add_file("/games/SPT/SPT.Server.exe", "")
+            .add_dir("/games/SPT/BepInEx/plugins")
+            .add_install("/games/Aki")
+            .add_file("/games/Aki/Aki.Server.exe", "")
+            .add_dir("/games/Aki/BepInEx")
+            .add_install("/games/EFT")
+            // Some live players use BepInEx mods without SPT
+            .add_dir("/games/EFT/BepInEx");
+
+        assert_eq!(game_mode(&fs, Path::new("/games/SPT")), GameMode::Spt);
+        assert_eq!(game_mode(&fs, Path::new("/games/Aki")), GameMode::Spt);
+        assert_eq!(game_mode(&fs, Path::new("/games/EFT")), GameMode::Live);
+    }
+}
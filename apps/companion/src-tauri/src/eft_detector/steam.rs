@@ -0,0 +1,313 @@
+//! Steam Library Detection
+//!
+//! Finds the Steam edition of EFT by reading Steam's `libraryfolders.vdf`
+//! and the game's app manifest in whichever library it was installed to.
+
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+
+use super::install_source::FileSystem;
+use super::registry::{Hive, RegistryBackend};
+use super::vdf::{self, VdfError};
+
+/// Steam app id of Escape from Tarkov
+pub const EFT_STEAM_APP_ID: &str = "3932890";
+
+/// A Steam library folder
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryFolder {
+    pub path: PathBuf,
+    /// App ids installed in this library (empty for the legacy format, which does not list them)
+    pub app_ids: Vec<String>,
+}
+
+/// Parse `libraryfolders.vdf`, in either the current or the legacy format
+pub fn parse_library_folders(content: &str) -> Result<Vec<LibraryFolder>, VdfError> {
+    let doc = vdf::parse(content)?;
+    let Some(root) = doc.get("libraryfolders") else {
+        return Ok(Vec::new());
+    };
+
+    let mut folders = Vec::new();
+    for (key, value) in root.entries() {
+        // Library entries use numeric keys; the rest are Steam bookkeeping
+        if !key.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+
+        // Legacy format: "1" "D:\\SteamLibrary"
+        if let Some(path) = value.as_str() {
+            folders.push(LibraryFolder {
+                path: PathBuf::from(path),
+                app_ids: Vec::new(),
+            });
+            continue;
+        }
+
+        // Current format: "0" { "path" "..." "apps" { "<appid>" "<size>" } }
+        if let Some(path) = value.get("path").and_then(|p| p.as_str()) {
+            let app_ids = value
+                .get("apps")
+                .map(|apps| apps.entries().iter().map(|(id, _)| id.clone()).collect())
+                .unwrap_or_default();
+            folders.push(LibraryFolder {
+                path: PathBuf::from(path),
+                app_ids,
+            });
+        }
+    }
+
+    Ok(folders)
+}
+
+/// Read the install folder name from an `appmanifest_<appid>.acf`
+pub fn parse_app_manifest_installdir(content: &str) -> Option<String> {
+    let doc = vdf::parse(content).ok()?;
+    doc.get("AppState")?
+        .get("installdir")?
+        .as_str()
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| dir.to_string())
+}
+
+/// Find the EFT install folder across the libraries of a Steam installation
+pub fn find_eft_install(fs: &dyn FileSystem, steam_root: &Path) -> Option<PathBuf> {
+    let mut libraries = libraries(fs, steam_root);
+
+    // Check libraries that list EFT first
+    libraries.sort_by_key(|l| !l.app_ids.iter().any(|id| id == EFT_STEAM_APP_ID));
+
+    for library in libraries {
+        let steamapps = library.path.join("steamapps");
+        let manifest_path = steamapps.join(format!("appmanifest_{}.acf", EFT_STEAM_APP_ID));
+        let Ok(manifest) = fs.read_to_string(&manifest_path) else {
+            continue;
+        };
+
+        let Some(install_dir) = parse_app_manifest_installdir(&manifest) else {
+            debug!("No installdir in {:?}", manifest_path);
+            continue;
+        };
+
+        let install_path = steamapps.join("common").join(install_dir);
+        if fs.is_dir(&install_path) {
+            info!("Found Steam EFT install at: {:?}", install_path);
+            return Some(install_path);
+        }
+    }
+
+    None
+}
+
+/// All libraries of a Steam installation, including the Steam folder itself
+pub fn libraries(fs: &dyn FileSystem, steam_root: &Path) -> Vec<LibraryFolder> {
+    let mut libraries = read_library_folders(fs, steam_root);
+
+    // The legacy format leaves out the Steam folder itself
+    if !libraries.iter().any(|l| l.path == steam_root) {
+        libraries.push(LibraryFolder {
+            path: steam_root.to_path_buf(),
+            app_ids: Vec::new(),
+        });
+    }
+
+    libraries
+}
+
+fn read_library_folders(fs: &dyn FileSystem, steam_root: &Path) -> Vec<LibraryFolder> {
+    let candidates = [
+        steam_root.join("steamapps").join("libraryfolders.vdf"),
+        steam_root.join("config").join("libraryfolders.vdf"),
+    ];
+
+    for path in candidates {
+        let Ok(content) = fs.read_to_string(&path) else {
+            continue;
+        };
+        match parse_library_folders(&content) {
+            Ok(folders) => return folders,
+            Err(e) => debug!("Could not parse {:?}: {}", path, e),
+        }
+    }
+
+    Vec::new()
+}
+
+/// Registry values where Steam records its installation folder
+const STEAM_REGISTRY_KEYS: &[(Hive, &str, &str)] = &[
+    (Hive::CurrentUser, "Software\\Valve\\Steam", "SteamPath"),
+    (
+        Hive::LocalMachine,
+        "SOFTWARE\\WOW6432Node\\Valve\\Steam",
+        "InstallPath",
+    ),
+    (Hive::LocalMachine, "SOFTWARE\\Valve\\Steam", "InstallPath"),
+];
+
+/// Candidate Steam installation folders on this machine
+pub fn steam_roots(registry: &dyn RegistryBackend) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = STEAM_REGISTRY_KEYS
+        .iter()
+        .filter_map(|&(hive, key, value)| registry.read_string(hive, key, value))
+        .map(PathBuf::from)
+        .collect();
+
+    #[cfg(windows)]
+    roots.push(PathBuf::from("C:\\Program Files (x86)\\Steam"));
+
+    #[cfg(not(windows))]
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        roots.push(home.join(".steam").join("steam"));
+        roots.push(home.join(".local").join("share").join("Steam"));
+        roots.push(
+            home.join(".var")
+                .join("app")
+                .join("com.valvesoftware.Steam")
+                .join(".local")
+                .join("share")
+                .join("Steam"),
+        );
+    }
+
+    roots.dedup();
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::install_source::fake::FakeFileSystem;
+    use super::super::install_source::RealFileSystem;
+    use super::super::registry::fake::FakeRegistry;
+    use super::*;
+    use std::fs;
+
+    const LIBRARY_FOLDERS: &str = include_str!("../../tests/fixtures/steam/libraryfolders.vdf");
+    const LIBRARY_FOLDERS_LEGACY: &str =
+        include_str!("../../tests/fixtures/steam/libraryfolders_legacy.vdf");
+    const APP_MANIFEST: &str = include_str!("../../tests/fixtures/steam/appmanifest_3932890.acf");
+
+    #[test]
+    fn test_parse_library_folders() {
+        let folders = parse_library_folders(LIBRARY_FOLDERS).unwrap();
+
+        assert_eq!(folders.len(), 2);
+        assert_eq!(
+            folders[0].path,
+            PathBuf::from("C:\\Program Files (x86)\\Steam")
+        );
+        assert_eq!(folders[1].path, PathBuf::from("D:\\SteamLibrary"));
+        assert_eq!(folders[1].app_ids, vec![EFT_STEAM_APP_ID.to_string()]);
+    }
+
+    #[test]
+    fn test_parse_legacy_library_folders() {
+        let folders = parse_library_folders(LIBRARY_FOLDERS_LEGACY).unwrap();
+
+        assert_eq!(folders.len(), 2);
+        assert_eq!(folders[0].path, PathBuf::from("D:\\SteamLibrary"));
+        assert_eq!(folders[1].path, PathBuf::from("E:\\Games\\Steam"));
+        assert!(folders[0].app_ids.is_empty());
+    }
+
+    #[test]
+    fn test_parse_app_manifest() {
+        assert_eq!(
+            parse_app_manifest_installdir(APP_MANIFEST),
+            Some("Escape from Tarkov".to_string())
+        );
+        assert_eq!(parse_app_manifest_installdir("\"AppState\" { }"), None);
+    }
+
+    /// Build a Steam folder whose libraryfolders.vdf points at a second library
+    fn fake_steam(root: &Path, library: &Path, manifest: bool) {
+        let steamapps = root.join("steamapps");
+        fs::create_dir_all(&steamapps).unwrap();
+        let vdf = LIBRARY_FOLDERS
+            .replace(
+                "C:\\\\Program Files (x86)\\\\Steam",
+                &root.to_string_lossy().replace('\\', "\\\\"),
+            )
+            .replace(
+                "D:\\\\SteamLibrary",
+                &library.to_string_lossy().replace('\\', "\\\\"),
+            );
+        fs::write(steamapps.join("libraryfolders.vdf"), vdf).unwrap();
+
+        let library_apps = library.join("steamapps");
+        fs::create_dir_all(library_apps.join("common").join("Escape from Tarkov")).unwrap();
+        if manifest {
+            fs::write(
+                library_apps.join(format!("appmanifest_{}.acf", EFT_STEAM_APP_ID)),
+                APP_MANIFEST,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_find_eft_install_in_secondary_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("Steam");
+        let library = dir.path().join("SteamLibrary");
+        fake_steam(&root, &library, true);
+
+        assert_eq!(
+            find_eft_install(&RealFileSystem, &root),
+            Some(
+                library
+                    .join("steamapps")
+                    .join("common")
+                    .join("Escape from Tarkov")
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_eft_install_requires_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("Steam");
+        let library = dir.path().join("SteamLibrary");
+        fake_steam(&root, &library, false);
+
+        assert_eq!(find_eft_install(&RealFileSystem, &root), None);
+    }
+
+    #[test]
+    fn test_find_eft_install_without_steam() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            find_eft_install(&RealFileSystem, &dir.path().join("Steam")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_eft_install_with_fake_filesystem() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file(
+            "/steam/config/libraryfolders.vdf",
+            &LIBRARY_FOLDERS_LEGACY.replace("D:\\\\SteamLibrary", "/games"),
+        )
+        .add_file("/games/steamapps/appmanifest_3932890.acf", APP_MANIFEST)
+        .add_dir("/games/steamapps/common/Escape from Tarkov");
+
+        assert_eq!(
+            find_eft_install(&fs, Path::new("/steam")),
+            Some(PathBuf::from("/games/steamapps/common/Escape from Tarkov"))
+        );
+    }
+
+    #[test]
+    fn test_steam_roots_from_registry() {
+        let mut registry = FakeRegistry::new();
+        registry.set(
+            Hive::CurrentUser,
+            "Software\\Valve\\Steam",
+            "SteamPath",
+            "c:/program files (x86)/steam",
+        );
+
+        let roots = steam_roots(&registry);
+        assert_eq!(roots[0], PathBuf::from("c:/program files (x86)/steam"));
+    }
+}
@@ -0,0 +1,262 @@
+//! Valve KeyValues (VDF) Parser
+//!
+//! Minimal reader for the text KeyValues format Steam uses for
+//! `libraryfolders.vdf` and `appmanifest_*.acf`: quoted or bare tokens,
+//! nested `{ }` blocks, `\\`-escapes and `//` comments.
+
+use std::fmt;
+
+/// A parsed VDF value: either a string or a block of key/value pairs
+#[derive(Debug, Clone, PartialEq)]
+pub enum VdfValue {
+    String(String),
+    Block(Vec<(String, VdfValue)>),
+}
+
+impl VdfValue {
+    /// Look up a child by key (case-insensitive, as Steam treats keys)
+    pub fn get(&self, key: &str) -> Option<&VdfValue> {
+        match self {
+            VdfValue::Block(entries) => entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            VdfValue::String(_) => None,
+        }
+    }
+
+    /// The string value, if this is a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            VdfValue::String(s) => Some(s),
+            VdfValue::Block(_) => None,
+        }
+    }
+
+    /// Child entries, if this is a block
+    pub fn entries(&self) -> &[(String, VdfValue)] {
+        match self {
+            VdfValue::Block(entries) => entries,
+            VdfValue::String(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VdfError {
+    pub message: String,
+    pub line: usize,
+}
+
+impl fmt::Display for VdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VDF parse error on line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for VdfError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Str(String),
+    Open,
+    Close,
+}
+
+struct Tokenizer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    fn error(&self, message: &str) -> VdfError {
+        VdfError {
+            message: message.to_string(),
+            line: self.line,
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, VdfError> {
+        loop {
+            match self.chars.peek() {
+                None => return Ok(None),
+                Some('\n') => {
+                    self.line += 1;
+                    self.chars.next();
+                }
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('/') => {
+                    self.chars.next();
+                    if self.chars.peek() != Some(&'/') {
+                        return Err(self.error("unexpected '/'"));
+                    }
+                    while let Some(&c) = self.chars.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.chars.next();
+                    }
+                }
+                Some('{') => {
+                    self.chars.next();
+                    return Ok(Some(Token::Open));
+                }
+                Some('}') => {
+                    self.chars.next();
+                    return Ok(Some(Token::Close));
+                }
+                Some('"') => {
+                    self.chars.next();
+                    return self.quoted().map(|s| Some(Token::Str(s)));
+                }
+                Some(_) => return Ok(Some(Token::Str(self.bare()))),
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, VdfError> {
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(value),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c) => value.push(c),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    value.push(c);
+                }
+            }
+        }
+    }
+
+    fn bare(&mut self) -> String {
+        let mut value = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                break;
+            }
+            value.push(c);
+            self.chars.next();
+        }
+        value
+    }
+}
+
+/// Parse a VDF document into its top-level block
+pub fn parse(input: &str) -> Result<VdfValue, VdfError> {
+    let mut tokenizer = Tokenizer::new(input);
+    let entries = parse_block(&mut tokenizer, false)?;
+    Ok(VdfValue::Block(entries))
+}
+
+fn parse_block(
+    tokenizer: &mut Tokenizer<'_>,
+    nested: bool,
+) -> Result<Vec<(String, VdfValue)>, VdfError> {
+    let mut entries = Vec::new();
+    loop {
+        let key = match tokenizer.next_token()? {
+            None if nested => return Err(tokenizer.error("missing '}'")),
+            None => return Ok(entries),
+            Some(Token::Close) if nested => return Ok(entries),
+            Some(Token::Close) => return Err(tokenizer.error("unexpected '}'")),
+            Some(Token::Open) => return Err(tokenizer.error("expected a key, found '{'")),
+            Some(Token::Str(key)) => key,
+        };
+
+        let value = match tokenizer.next_token()? {
+            Some(Token::Str(value)) => VdfValue::String(value),
+            Some(Token::Open) => VdfValue::Block(parse_block(tokenizer, true)?),
+            _ => return Err(tokenizer.error(&format!("missing value for key \"{}\"", key))),
+        };
+        entries.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_blocks() {
+        let doc = parse(
+            r#"
+            "AppState"
+            {
+                "appid"     "3932890"
+                "installdir"    "Escape from Tarkov"
+                "UserConfig"
+                {
+                    "language"  "english"
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let app = doc.get("appstate").unwrap();
+        assert_eq!(app.get("appid").and_then(|v| v.as_str()), Some("3932890"));
+        assert_eq!(
+            app.get("UserConfig")
+                .and_then(|c| c.get("language"))
+                .and_then(|v| v.as_str()),
+            Some("english")
+        );
+    }
+
+    #[test]
+    fn test_parse_escapes_and_comments() {
+        let doc = parse(
+            r#"
+            // Written by Steam
+            "path"  "D:\\SteamLibrary"
+            "quote" "say \"hi\""
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.get("path").and_then(|v| v.as_str()),
+            Some("D:\\SteamLibrary")
+        );
+        assert_eq!(
+            doc.get("quote").and_then(|v| v.as_str()),
+            Some("say \"hi\"")
+        );
+    }
+
+    #[test]
+    fn test_parse_bare_tokens() {
+        let doc = parse("root { key value }").unwrap();
+        assert_eq!(
+            doc.get("root")
+                .and_then(|r| r.get("key"))
+                .and_then(|v| v.as_str()),
+            Some("value")
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(r#""root" { "key" "value" "#).is_err());
+        assert!(parse(r#""root" "unterminated"#).is_err());
+        assert!(parse(r#""key""#).is_err());
+        assert!(parse("}").is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! Game Version Detection
+//!
+//! Reports the client version of an install, read from:
+//! 1. The `FileVersion` in the version resource of `EscapeFromTarkov.exe`
+//! 2. The version suffix of the newest log session folder
+//!    (`log_2024.01.15_10-30-45_0.14.0.2.28375`)
+
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::install_source::FileSystem;
+
+/// Executables larger than this are not searched for a version resource
+const MAX_EXE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Signature of the fixed-size part of a version resource
+const FIXED_FILE_INFO_SIGNATURE: [u8; 4] = [0xbd, 0x04, 0xef, 0xfe];
+
+/// Where a version was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionSource {
+    Executable,
+    LogSession,
+}
+
+/// Client version of an install
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameVersion {
+    /// Full version, e.g. `0.14.0.2.28375`
+    pub version: String,
+    /// Build number, the last part of the version
+    pub build: Option<u32>,
+    pub source: VersionSource,
+}
+
+impl GameVersion {
+    fn new(version: String, source: VersionSource) -> Self {
+        let build = version
+            .rsplit('.')
+            .next()
+            .filter(|_| version.matches('.').count() >= 3)
+            .and_then(|build| build.parse().ok());
+        Self {
+            version,
+            build,
+            source,
+        }
+    }
+}
+
+fn session_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"^log_(\d{4})\.(\d{2})\.(\d{2})_(\d{1,2})-(\d{2})-(\d{2})(?:_(\d+(?:\.\d+)+))?$",
+        )
+        .unwrap()
+    })
+}
+
+/// Version suffix of a log session folder name
+pub fn version_from_session_name(name: &str) -> Option<String> {
+    session_pattern()
+        .captures(name)?
+        .get(7)
+        .map(|version| version.as_str().to_string())
+}
+
+/// The newest log session folder in a Logs folder
+pub fn newest_session(fs: &dyn FileSystem, logs_dir: &Path) -> Option<PathBuf> {
+    fs.read_dir(logs_dir)
+        .ok()?
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            let captures = session_pattern().captures(&name)?;
+            // Hours are not zero-padded, so compare the parsed timestamp
+            let started: Vec<u32> = (1..=6)
+                .filter_map(|i| captures.get(i)?.as_str().parse().ok())
+                .collect();
+            Some((started, path))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Version of the newest log session in a Logs folder
+pub fn newest_session_version(fs: &dyn FileSystem, logs_dir: &Path) -> Option<String> {
+    let session = newest_session(fs, logs_dir)?;
+    version_from_session_name(&session.file_name()?.to_string_lossy())
+}
+
+/// Version from the version resource of a Windows executable
+pub fn version_from_exe(bytes: &[u8]) -> Option<String> {
+    string_file_version(bytes).or_else(|| fixed_file_version(bytes))
+}
+
+/// The `FileVersion` string, stored as UTF-16 after its key
+fn string_file_version(bytes: &[u8]) -> Option<String> {
+    let key: Vec<u8> = "FileVersion\0"
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    let start = bytes.windows(key.len()).position(|w| w == key.as_slice())? + key.len();
+
+    // Skip the padding between key and value, then read up to the terminator
+    let units: Vec<u16> = bytes[start..]
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .skip_while(|&unit| unit == 0)
+        .take_while(|&unit| unit != 0)
+        .take(64)
+        .collect();
+
+    let version = String::from_utf16(&units).ok()?;
+    let version = version.trim().replace(", ", ".");
+    version
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.')
+        .then_some(version)
+        .filter(|v| !v.is_empty())
+}
+
+/// The binary file version of `VS_FIXEDFILEINFO`
+fn fixed_file_version(bytes: &[u8]) -> Option<String> {
+    let start = bytes
+        .windows(FIXED_FILE_INFO_SIGNATURE.len())
+        .position(|w| w == FIXED_FILE_INFO_SIGNATURE)?;
+    // Signature, struct version, then the most and least significant halves
+    let read = |offset: usize| -> Option<u32> {
+        let field = bytes.get(start + offset..start + offset + 4)?;
+        Some(u32::from_le_bytes(field.try_into().ok()?))
+    };
+    let (ms, ls) = (read(8)?, read(12)?);
+    Some(format!(
+        "{}.{}.{}.{}",
+        ms >> 16,
+        ms & 0xffff,
+        ls >> 16,
+        ls & 0xffff
+    ))
+}
+
+/// Detect the client version of an install
+pub fn detect_version(fs: &dyn FileSystem, install: &Path) -> Option<GameVersion> {
+    // Check the size first so a huge or bogus file is never read into memory
+    let exe = install.join("EscapeFromTarkov.exe");
+    let exe_version = fs
+        .file_size(&exe)
+        .ok()
+        .filter(|&size| size <= MAX_EXE_SIZE)
+        .and_then(|_| fs.read(&exe).ok())
+        .and_then(|bytes| version_from_exe(&bytes));
+    if let Some(version) = exe_version {
+        return Some(GameVersion::new(version, VersionSource::Executable));
+    }
+
+    newest_session_version(fs, &install.join("Logs"))
+        .map(|version| GameVersion::new(version, VersionSource::LogSession))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::install_source::fake::FakeFileSystem;
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()
+    }
+
+    /// Minimal stand-in for an executable with a version resource
+    fn fake_exe(file_version: &str) -> Vec<u8> {
+        let mut bytes = b"MZ\x90\x00 not a real PE header".to_vec();
+        bytes.extend(FIXED_FILE_INFO_SIGNATURE);
+        bytes.extend(0x0001_0000u32.to_le_bytes());
+        bytes.extend(14u32.to_le_bytes());
+        bytes.extend(((2u32 << 16) | 28375).to_le_bytes());
+        bytes.extend(utf16("CompanyName\0"));
+        bytes.extend(utf16("Battlestate Games\0"));
+        if !file_version.is_empty() {
+            bytes.extend(utf16("FileVersion\0\0"));
+            bytes.extend(utf16(file_version));
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_version_from_session_name() {
+        assert_eq!(
+            version_from_session_name("log_2024.01.15_10-30-45_0.14.0.2.28375"),
+            Some("0.14.0.2.28375".to_string())
+        );
+        assert_eq!(version_from_session_name("log_2024.01.15_10-30-45"), None);
+        assert_eq!(version_from_session_name("screenshots"), None);
+    }
+
+    #[test]
+    fn test_newest_session_version() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_dir("/eft/Logs/log_2024.01.15_9-30-45_0.14.0.1.28000")
+            .add_dir("/eft/Logs/log_2024.01.15_10-30-45_0.14.0.2.28375")
+            .add_dir("/eft/Logs/log_2024.01.14_23-59-59_0.13.5.0.27000")
+            .add_file("/eft/Logs/traces.log", "");
+
+        assert_eq!(
+            newest_session_version(&fs, Path::new("/eft/Logs")),
+            Some("0.14.0.2.28375".to_string())
+        );
+    }
+
+    #[test]
+    fn test_version_from_exe_string_table() {
+        assert_eq!(
+            version_from_exe(&fake_exe("0.14.0.2.28375")),
+            Some("0.14.0.2.28375".to_string())
+        );
+    }
+
+    #[test]
+    fn test_version_from_exe_fixed_info() {
+        assert_eq!(
+            version_from_exe(&fake_exe("")),
+            Some("0.14.2.28375".to_string())
+        );
+        assert_eq!(version_from_exe(b"MZ no resources"), None);
+    }
+
+    #[test]
+    fn test_detect_version_prefers_exe() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file("/eft/Logs/log_2024.01.15_10-30-45_0.14.0.1.28000/a.log", "");
+        fs.add_bytes("/eft/EscapeFromTarkov.exe", fake_exe("0.14.0.2.28375"));
+
+        let version = detect_version(&fs, Path::new("/eft")).unwrap();
+        assert_eq!(version.version, "0.14.0.2.28375");
+        assert_eq!(version.build, Some(28375));
+        assert_eq!(version.source, VersionSource::Executable);
+    }
+
+    #[test]
+    fn test_detect_version_falls_back_to_logs() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file("/eft/EscapeFromTarkov.exe", "")
+            .add_dir("/eft/Logs/log_2024.01.15_10-30-45_0.14.0.1.28000");
+
+        let version = detect_version(&fs, Path::new("/eft")).unwrap();
+        assert_eq!(version.version, "0.14.0.1.28000");
+        assert_eq!(version.source, VersionSource::LogSession);
+    }
+
+    #[test]
+    fn test_detect_version_unknown() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_install("/eft");
+        assert_eq!(detect_version(&fs, Path::new("/eft")), None);
+    }
+}
@@ -0,0 +1,333 @@
+//! Wine / Proton Prefix Discovery
+//!
+//! Linux players run EFT inside a Wine prefix. Prefixes are found in:
+//! 1. The default `~/.wine` prefix
+//! 2. Lutris game configs (`prefix:` entries)
+//! 3. Bottles (native and Flatpak)
+//! 4. Steam Proton `compatdata/<appid>/pfx` folders of EFT and of non-Steam
+//!    shortcuts (where the BSG launcher is added) in every Steam library
+//!
+//! Inside a prefix, the registry hives (`system.reg`, `user.reg`) are plain
+//! text and are read through the same `RegistryBackend` as on Windows.
+
+use log::debug;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::install_source::FileSystem;
+use super::registry::{Hive, RegistryBackend};
+use super::steam;
+
+/// Non-Steam shortcuts get app ids with the top bit set
+const SHORTCUT_APP_ID_MIN: u64 = 0x8000_0000;
+
+/// Whether a Proton `compatdata` folder can hold EFT; other games' prefixes are skipped
+fn is_eft_compatdata(app: &Path) -> bool {
+    let Some(name) = app.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    name == steam::EFT_STEAM_APP_ID
+        || name
+            .parse::<u64>()
+            .is_ok_and(|id| id >= SHORTCUT_APP_ID_MIN)
+}
+
+/// Registry values of one hive, keyed by lowercased key path and value name
+type RegValues = HashMap<String, HashMap<String, String>>;
+
+/// Parse a Wine registry hive file into its string values
+pub fn parse_reg_file(content: &str) -> RegValues {
+    let mut values: RegValues = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in content.lines() {
+        let line = line.trim_start();
+
+        // Section header: [Software\\Battlestate Games\\EscapeFromTarkov] 1700000000
+        if let Some(rest) = line.strip_prefix('[') {
+            current = rest
+                .rfind(']')
+                .map(|end| unescape(&rest[..end]).to_lowercase());
+            continue;
+        }
+
+        let Some(key) = &current else {
+            continue;
+        };
+
+        // Value line: "InstallPath"="C:\\Battlestate Games\\EFT"
+        let Some(rest) = line.strip_prefix('"') else {
+            continue;
+        };
+        let Some((name, rest)) = read_quoted(rest) else {
+            continue;
+        };
+        let Some(data) = rest.strip_prefix('=') else {
+            continue;
+        };
+
+        // Plain and expandable strings; dwords and binary data are not needed
+        let data = data.strip_prefix("str(2):").unwrap_or(data);
+        if let Some((value, _)) = data.strip_prefix('"').and_then(read_quoted) {
+            values
+                .entry(key.clone())
+                .or_default()
+                .insert(name.to_lowercase(), value);
+        }
+    }
+
+    values
+}
+
+/// Read an escaped string up to its closing quote, returning it and the rest of the line
+fn read_quoted(input: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &input[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn unescape(input: &str) -> String {
+    input.replace("\\\\", "\\")
+}
+
+/// The registry of a Wine prefix
+pub struct WineRegistry {
+    machine: RegValues,
+    user: RegValues,
+}
+
+impl WineRegistry {
+    /// Load the hives of a prefix; missing hives are treated as empty
+    pub fn load(fs: &dyn FileSystem, prefix: &Path) -> Self {
+        let read = |name: &str| {
+            fs.read_to_string(&prefix.join(name))
+                .map(|content| parse_reg_file(&content))
+                .unwrap_or_default()
+        };
+        Self {
+            machine: read("system.reg"),
+            user: read("user.reg"),
+        }
+    }
+}
+
+impl RegistryBackend for WineRegistry {
+    fn read_string(&self, hive: Hive, key: &str, value: &str) -> Option<String> {
+        let values = match hive {
+            Hive::LocalMachine => &self.machine,
+            Hive::CurrentUser => &self.user,
+        };
+        values
+            .get(&key.to_lowercase())?
+            .get(&value.to_lowercase())
+            .cloned()
+    }
+}
+
+/// Map a Windows path inside a prefix (`C:\Games\EFT`) to the host path
+pub fn to_host_path(prefix: &Path, windows_path: &str) -> Option<PathBuf> {
+    let mut chars = windows_path.chars();
+    let drive = chars.next()?.to_ascii_lowercase();
+    if !drive.is_ascii_alphabetic() || chars.next()? != ':' {
+        return None;
+    }
+
+    // C: is always drive_c; other letters are symlinks in dosdevices
+    let mut path = if drive == 'c' {
+        prefix.join("drive_c")
+    } else {
+        prefix.join("dosdevices").join(format!("{}:", drive))
+    };
+    for part in chars.as_str().split(['\\', '/']).filter(|p| !p.is_empty()) {
+        path.push(part);
+    }
+    Some(path)
+}
+
+/// Find Wine prefixes for the user's home folder and Steam installations
+pub fn discover_prefixes(
+    fs: &dyn FileSystem,
+    home: Option<&Path>,
+    steam_roots: &[PathBuf],
+) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    if let Some(home) = home {
+        candidates.push(home.join(".wine"));
+
+        // Lutris keeps one YAML config per game
+        for dir in [
+            home.join(".config").join("lutris").join("games"),
+            home.join(".local")
+                .join("share")
+                .join("lutris")
+                .join("games"),
+        ] {
+            for config in fs.read_dir(&dir).unwrap_or_default() {
+                if let Ok(content) = fs.read_to_string(&config) {
+                    candidates.extend(parse_lutris_prefix(&content));
+                }
+            }
+        }
+
+        // Every bottle is a prefix
+        for dir in [
+            home.join(".local")
+                .join("share")
+                .join("bottles")
+                .join("bottles"),
+            home.join(".var")
+                .join("app")
+                .join("com.usebottles.bottles")
+                .join("data")
+                .join("bottles")
+                .join("bottles"),
+        ] {
+            candidates.extend(fs.read_dir(&dir).unwrap_or_default());
+        }
+    }
+
+    // Proton keeps a prefix per app id, including non-Steam shortcuts
+    for root in steam_roots {
+        for library in steam::libraries(fs, root) {
+            let compatdata = library.path.join("steamapps").join("compatdata");
+            for app in fs.read_dir(&compatdata).unwrap_or_default() {
+                if is_eft_compatdata(&app) {
+                    candidates.push(app.join("pfx"));
+                }
+            }
+        }
+    }
+
+    let mut prefixes = Vec::new();
+    for prefix in candidates {
+        if fs.is_dir(&prefix.join("drive_c")) && !prefixes.contains(&prefix) {
+            debug!("Found Wine prefix: {:?}", prefix);
+            prefixes.push(prefix);
+        }
+    }
+    prefixes
+}
+
+/// Read the `prefix:` entry of a Lutris game config
+pub fn parse_lutris_prefix(content: &str) -> Option<PathBuf> {
+    content.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("prefix:")?.trim();
+        let value = value.trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| PathBuf::from(value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::install_source::fake::FakeFileSystem;
+    use super::*;
+
+    const SYSTEM_REG: &str = include_str!("../../tests/fixtures/wine/system.reg");
+    const LUTRIS_CONFIG: &str = include_str!("../../tests/fixtures/wine/lutris-tarkov.yml");
+
+    #[test]
+    fn test_parse_reg_file() {
+        let values = parse_reg_file(SYSTEM_REG);
+        let eft = &values["software\\wow6432node\\battlestate games\\escapefromtarkov"];
+        assert_eq!(eft["installpath"], "C:\\Battlestate Games\\EFT");
+        assert_eq!(eft["displayname"], "Escape from Tarkov");
+        // Non-string values are skipped
+        assert!(!eft.contains_key("estimatedsize"));
+    }
+
+    #[test]
+    fn test_wine_registry_backend() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_file("/prefix/system.reg", SYSTEM_REG);
+
+        let registry = WineRegistry::load(&fs, Path::new("/prefix"));
+        assert_eq!(
+            registry.read_string(
+                Hive::LocalMachine,
+                "SOFTWARE\\WOW6432Node\\Battlestate Games\\EscapeFromTarkov",
+                "InstallPath"
+            ),
+            Some("C:\\Battlestate Games\\EFT".to_string())
+        );
+        assert_eq!(
+            registry.read_string(
+                Hive::CurrentUser,
+                "SOFTWARE\\WOW6432Node\\Battlestate Games\\EscapeFromTarkov",
+                "InstallPath"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_to_host_path() {
+        let prefix = Path::new("/home/user/.wine");
+        assert_eq!(
+            to_host_path(prefix, "C:\\Battlestate Games\\EFT"),
+            Some(PathBuf::from(
+                "/home/user/.wine/drive_c/Battlestate Games/EFT"
+            ))
+        );
+        assert_eq!(
+            to_host_path(prefix, "d:/Games/EFT\\"),
+            Some(PathBuf::from("/home/user/.wine/dosdevices/d:/Games/EFT"))
+        );
+        assert_eq!(to_host_path(prefix, "Games\\EFT"), None);
+    }
+
+    #[test]
+    fn test_parse_lutris_prefix() {
+        assert_eq!(
+            parse_lutris_prefix(LUTRIS_CONFIG),
+            Some(PathBuf::from("/home/user/Games/escape-from-tarkov"))
+        );
+        assert_eq!(parse_lutris_prefix("game:\n  exe: foo.exe\n"), None);
+    }
+
+    #[test]
+    fn test_discover_prefixes() {
+        let mut fs = FakeFileSystem::new();
+        fs.add_dir("/home/user/.wine/drive_c")
+            .add_file(
+                "/home/user/.config/lutris/games/escape-from-tarkov-1700000000.yml",
+                LUTRIS_CONFIG,
+            )
+            .add_dir("/home/user/Games/escape-from-tarkov/drive_c")
+            .add_dir("/home/user/.local/share/bottles/bottles/Tarkov/drive_c")
+            .add_dir("/home/user/.steam/steam/steamapps/compatdata/3932890/pfx/drive_c")
+            .add_dir("/home/user/.steam/steam/steamapps/compatdata/2873492811/pfx/drive_c")
+            // Another game's prefix is not read
+            .add_dir("/home/user/.steam/steam/steamapps/compatdata/1086940/pfx/drive_c")
+            // Not a prefix: no drive_c
+            .add_dir("/home/user/.steam/steam/steamapps/compatdata/228980");
+
+        let prefixes = discover_prefixes(
+            &fs,
+            Some(Path::new("/home/user")),
+            &[PathBuf::from("/home/user/.steam/steam")],
+        );
+
+        assert_eq!(
+            prefixes,
+            vec![
+                PathBuf::from("/home/user/.wine"),
+                PathBuf::from("/home/user/Games/escape-from-tarkov"),
+                PathBuf::from("/home/user/.local/share/bottles/bottles/Tarkov"),
+                PathBuf::from("/home/user/.steam/steam/steamapps/compatdata/2873492811/pfx"),
+                PathBuf::from("/home/user/.steam/steam/steamapps/compatdata/3932890/pfx"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Companion Errors
+//!
+//! One error type for everything the frontend can be told about:
+//! 1. Every variant has a stable, machine-readable code
+//! 2. Errors reach the frontend as `{ "code": "...", "message": "..." }`
+//! 3. reqwest, io, notify, keyring and serde_json errors convert with `?`
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompanionError {
+    /// The server could not be reached, or the connection dropped
+    Network(String),
+    /// The server rejected the companion token
+    Unauthorized,
+    /// The server asked for fewer requests
+    RateLimited { retry_after: Option<Duration> },
+    /// The server failed or refused the request
+    Server { status: u16, message: String },
+    /// A response could not be understood
+    Parse(String),
+    /// No companion token is configured
+    NoToken,
+    /// No EFT installation was found
+    InstallNotFound,
+    /// A folder is not an EFT installation, and why
+    InvalidInstall(String),
+    /// The installation has no Logs folder (profiles folder for SPT)
+    LogsMissing(PathBuf),
+    /// A file could not be read or written
+    Io(String),
+    /// The filesystem watcher failed
+    Watch(String),
+    /// Settings could not be read or saved
+    Settings(String),
+    /// The companion token could not be stored or read back
+    Vault(String),
+    /// The sync task is not running
+    SyncUnavailable,
+    /// A background task failed
+    Internal(String),
+}
+
+impl CompanionError {
+    /// Stable code the frontend can match on; never change an existing one
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompanionError::Network(_) => "network",
+            CompanionError::Unauthorized => "unauthorized",
+            CompanionError::RateLimited { .. } => "rate_limited",
+            CompanionError::Server { .. } => "server",
+            CompanionError::Parse(_) => "parse",
+            CompanionError::NoToken => "no_token",
+            CompanionError::InstallNotFound => "install_not_found",
+            CompanionError::InvalidInstall(_) => "invalid_install",
+            CompanionError::LogsMissing(_) => "logs_missing",
+            CompanionError::Io(_) => "io",
+            CompanionError::Watch(_) => "watch",
+            CompanionError::Settings(_) => "settings",
+            CompanionError::Vault(_) => "vault",
+            CompanionError::SyncUnavailable => "sync_unavailable",
+            CompanionError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl fmt::Display for CompanionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompanionError::Network(e) => write!(f, "Network error: {}", e),
+            CompanionError::Unauthorized => write!(f, "Invalid or expired companion token"),
+            CompanionError::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "Too many requests, try again in {}s",
+                retry_after.as_secs()
+            ),
+            CompanionError::RateLimited { retry_after: None } => {
+                write!(f, "Too many requests, try again later")
+            }
+            CompanionError::Server { status, message } => {
+                write!(f, "Server error {}: {}", status, message)
+            }
+            CompanionError::Parse(e) => write!(f, "Failed to parse response: {}", e),
+            CompanionError::NoToken => write!(f, "No companion token configured"),
+            CompanionError::InstallNotFound => write!(f, "No EFT installation found"),
+            CompanionError::InvalidInstall(reason) => write!(f, "Not an EFT install: {}", reason),
+            CompanionError::LogsMissing(path) => {
+                write!(f, "Logs folder not found: {}", path.display())
+            }
+            CompanionError::Io(e) => write!(f, "File error: {}", e),
+            CompanionError::Watch(e) => write!(f, "Log watcher error: {}", e),
+            CompanionError::Settings(e) => write!(f, "Settings error: {}", e),
+            CompanionError::Vault(e) => write!(f, "Token storage error: {}", e),
+            CompanionError::SyncUnavailable => write!(f, "Sync is not running"),
+            CompanionError::Internal(e) => write!(f, "Internal error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CompanionError {}
+
+impl Serialize for CompanionError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("CompanionError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+impl From<reqwest::Error> for CompanionError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            CompanionError::Parse(e.to_string())
+        } else {
+            CompanionError::Network(e.to_string())
+        }
+    }
+}
+
+impl From<std::io::Error> for CompanionError {
+    fn from(e: std::io::Error) -> Self {
+        CompanionError::Io(e.to_string())
+    }
+}
+
+impl From<notify::Error> for CompanionError {
+    fn from(e: notify::Error) -> Self {
+        CompanionError::Watch(e.to_string())
+    }
+}
+
+impl From<keyring::Error> for CompanionError {
+    fn from(e: keyring::Error) -> Self {
+        CompanionError::Vault(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for CompanionError {
+    fn from(e: serde_json::Error) -> Self {
+        CompanionError::Parse(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_code_and_message() {
+        let json = serde_json::to_value(CompanionError::Unauthorized).unwrap();
+        assert_eq!(json["code"], "unauthorized");
+        assert_eq!(json["message"], "Invalid or expired companion token");
+
+        let rate_limited = CompanionError::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+        };
+        let json = serde_json::to_value(&rate_limited).unwrap();
+        assert_eq!(json["code"], "rate_limited");
+        assert_eq!(json["message"], "Too many requests, try again in 30s");
+        assert_eq!(json.as_object().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_converts_library_errors() {
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        assert_eq!(CompanionError::from(io).code(), "io");
+
+        let json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert_eq!(CompanionError::from(json).code(), "parse");
+
+        let notify = notify::Error::path_not_found();
+        assert_eq!(CompanionError::from(notify).code(), "watch");
+    }
+}
@@ -0,0 +1,428 @@
+//! Event Deduplication
+//!
+//! Drops quest events that have already been queued:
+//! 1. The same log line read again after a restart or backfill, recognised by its
+//!    log session, file and offset
+//! 2. The same notification emitted twice by the game, recognised by a hash of its
+//!    body. The body carries the game's message id, so a quest that really repeats
+//!    (failed, then started again) arrives in a new message and is kept
+//!
+//! Seen keys are appended to disk and compacted with bounded retention.
+
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::log_watcher::QuestEvent;
+
+/// Maximum number of seen keys kept on disk
+const MAX_SEEN_ENTRIES: usize = 5000;
+
+/// How long a seen key is remembered
+const SEEN_RETENTION_DAYS: i64 = 14;
+
+/// Lines the seen-key file may grow to before it is compacted
+const MAX_STORE_LINES: usize = 2 * MAX_SEEN_ENTRIES;
+
+/// One line of the seen-key file
+#[derive(Debug, Serialize, Deserialize)]
+struct SeenEntry {
+    key: String,
+    #[serde(rename = "seenAt")]
+    seen_at: DateTime<Utc>,
+}
+
+pub struct EventDeduplicator {
+    seen: HashMap<String, DateTime<Utc>>,
+    store_path: Option<PathBuf>,
+    /// Lines in the seen-key file, including ones pruned since it was compacted
+    store_lines: usize,
+    duplicates_dropped: usize,
+}
+
+impl EventDeduplicator {
+    /// Create an in-memory deduplicator that is not persisted
+    pub fn new() -> Self {
+        Self {
+            seen: HashMap::new(),
+            store_path: None,
+            store_lines: 0,
+            duplicates_dropped: 0,
+        }
+    }
+
+    /// Load the seen-set from disk, starting empty if it is missing or unreadable.
+    /// The file is compacted, dropping expired keys.
+    pub fn load(store_path: PathBuf) -> Self {
+        let mut dedup = Self {
+            store_path: Some(store_path.clone()),
+            ..Self::new()
+        };
+
+        if let Ok(content) = fs::read_to_string(&store_path) {
+            // A torn last line is skipped; the key is only lost, never misread
+            dedup.seen = content
+                .lines()
+                .filter_map(|line| serde_json::from_str::<SeenEntry>(line).ok())
+                .map(|entry| (entry.key, entry.seen_at))
+                .collect();
+        }
+
+        dedup.prune(Utc::now());
+        if let Err(e) = dedup.compact() {
+            warn!("Failed to compact seen-event store {:?}: {}", store_path, e);
+        }
+        dedup
+    }
+
+    /// Whether an event has not been seen before; a duplicate is counted as dropped.
+    /// Call `record` once the event has been queued.
+    pub fn check(&mut self, event: &QuestEvent) -> bool {
+        // Events not read from a log have nothing to compare against
+        if Self::keys(event)
+            .iter()
+            .any(|key| self.seen.contains_key(key))
+        {
+            self.duplicates_dropped += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Remember an event as seen
+    pub fn record(&mut self, event: &QuestEvent) {
+        let keys = Self::keys(event);
+        if keys.is_empty() {
+            return;
+        }
+
+        let now = Utc::now();
+        for key in &keys {
+            self.seen.insert(key.clone(), now);
+        }
+        self.prune(now);
+
+        let saved = if self.store_lines >= MAX_STORE_LINES {
+            self.compact()
+        } else {
+            keys.into_iter()
+                .try_for_each(|key| self.append(&SeenEntry { key, seen_at: now }))
+        };
+        if let Err(e) = saved {
+            warn!("Failed to persist seen-event store: {}", e);
+        }
+    }
+
+    /// Number of duplicates dropped since start-up
+    pub fn duplicates_dropped(&self) -> usize {
+        self.duplicates_dropped
+    }
+
+    /// Number of keys currently remembered
+    pub fn seen_count(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Keys identifying an event by its position in the logs and by its content;
+    /// none for events that were not read from a log
+    fn keys(event: &QuestEvent) -> Vec<String> {
+        let origin = &event.origin;
+        if !origin.from_log {
+            return Vec::new();
+        }
+
+        vec![
+            format!(
+                "pos:{}/{}@{}:{}:{}",
+                origin.session,
+                event.log_file,
+                origin.offset,
+                event.quest_id,
+                event.status.as_str()
+            ),
+            format!(
+                "hash:{}:{}:{}",
+                origin.content_hash,
+                event.quest_id,
+                event.status.as_str()
+            ),
+        ]
+    }
+
+    /// Drop expired keys, then the oldest ones if the set is still over capacity
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - Duration::days(SEEN_RETENTION_DAYS);
+        self.seen.retain(|_, seen_at| *seen_at >= cutoff);
+
+        if self.seen.len() > MAX_SEEN_ENTRIES {
+            let mut entries: Vec<_> = self.seen.drain().collect();
+            entries.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+            entries.truncate(MAX_SEEN_ENTRIES);
+            self.seen = entries.into_iter().collect();
+        }
+    }
+
+    /// Add one key to the end of the seen-key file
+    fn append(&mut self, entry: &SeenEntry) -> std::io::Result<()> {
+        let Some(store_path) = &self.store_path else {
+            return Ok(());
+        };
+
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(store_path)?
+            .write_all(&line)?;
+        self.store_lines += 1;
+        Ok(())
+    }
+
+    /// Rewrite the seen-key file with only the remembered keys, atomically
+    /// (temp file, then rename)
+    fn compact(&mut self) -> std::io::Result<()> {
+        let Some(store_path) = &self.store_path else {
+            return Ok(());
+        };
+
+        let mut content = Vec::new();
+        for (key, seen_at) in &self.seen {
+            serde_json::to_writer(
+                &mut content,
+                &SeenEntry {
+                    key: key.clone(),
+                    seen_at: *seen_at,
+                },
+            )?;
+            content.push(b'\n');
+        }
+
+        if let Some(parent) = store_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp_path = store_path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, store_path)?;
+        self.store_lines = self.seen.len();
+        Ok(())
+    }
+}
+
+impl Default for EventDeduplicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stable FNV-1a hash of notification content, ignoring carriage returns
+pub fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in content.bytes().filter(|&b| b != b'\r') {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_watcher::{EventOrigin, QuestEventStatus};
+
+    /// Check an event and record it if it is new, as queueing does
+    fn offer(dedup: &mut EventDeduplicator, event: &QuestEvent) -> bool {
+        let new = dedup.check(event);
+        if new {
+            dedup.record(event);
+        }
+        new
+    }
+
+    fn event(quest_id: &str, offset: u64, body: &str) -> QuestEvent {
+        QuestEvent {
+            quest_id: quest_id.to_string(),
+            status: QuestEventStatus::Finished,
+            timestamp: Utc::now(),
+            log_file: "application.log".to_string(),
+            origin: EventOrigin {
+                from_log: true,
+                session: "log_2024.01.15_10-30-45".to_string(),
+                offset,
+                content_hash: content_hash(body),
+            },
+        }
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash("{\"a\": 1}"), content_hash("{\"a\": 1}"));
+        assert_eq!(content_hash("{\r\n}\r\n"), content_hash("{\n}\n"));
+        assert_ne!(content_hash("{\"a\": 1}"), content_hash("{\"a\": 2}"));
+    }
+
+    #[test]
+    fn test_drops_reread_of_same_line() {
+        let mut dedup = EventDeduplicator::new();
+        assert!(offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 120, "{1}")
+        ));
+        assert!(!offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 120, "{1}")
+        ));
+        assert_eq!(dedup.duplicates_dropped(), 1);
+    }
+
+    #[test]
+    fn test_drops_notification_emitted_twice() {
+        // The game logs the same message again further down the file
+        let mut dedup = EventDeduplicator::new();
+        let message = r#"{"message": {"_id": "65a4f1c2e0b8a7d3", "templateId": "59ca2eb686f77445a80ed049 successMessageText"}}"#;
+        assert!(offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 120, message)
+        ));
+        assert!(!offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 480, message)
+        ));
+        assert_eq!(dedup.duplicates_dropped(), 1);
+    }
+
+    #[test]
+    fn test_keeps_real_repeat_in_new_message() {
+        // e.g. a quest that was failed and then started again later in the session;
+        // each time the game sends a new message with its own id
+        let mut dedup = EventDeduplicator::new();
+        let first = r#"{"message": {"_id": "65a4f1c2e0b8a7d3", "templateId": "59ca2eb686f77445a80ed049 startedMessageText"}}"#;
+        let again = r#"{"message": {"_id": "65a4f9d0e0b8a7e1", "templateId": "59ca2eb686f77445a80ed049 startedMessageText"}}"#;
+        assert!(offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 120, first)
+        ));
+        assert!(offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 480, again)
+        ));
+
+        let mut next_session = event("59ca2eb686f77445a80ed049", 120, "{3}");
+        next_session.origin.session = "log_2024.01.16_09-00-00".to_string();
+        assert!(offer(&mut dedup, &next_session));
+        assert_eq!(dedup.duplicates_dropped(), 0);
+    }
+
+    #[test]
+    fn test_keeps_distinct_events() {
+        let mut dedup = EventDeduplicator::new();
+        assert!(offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 120, "{1}")
+        ));
+        assert!(offer(
+            &mut dedup,
+            &event("5d4bec3486f7743cac246665", 480, "{2}")
+        ));
+        assert_eq!(dedup.duplicates_dropped(), 0);
+    }
+
+    #[test]
+    fn test_events_without_origin_are_never_dropped() {
+        let mut dedup = EventDeduplicator::new();
+        let mut manual = event("59ca2eb686f77445a80ed049", 0, "");
+        manual.origin = EventOrigin::default();
+
+        assert!(offer(&mut dedup, &manual));
+        assert!(offer(&mut dedup, &manual));
+    }
+
+    #[test]
+    fn test_seen_set_persists_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("seen_events.json");
+
+        let mut dedup = EventDeduplicator::load(store_path.clone());
+        assert!(offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 120, "{1}")
+        ));
+
+        let mut restored = EventDeduplicator::load(store_path);
+        assert_eq!(restored.seen_count(), 2);
+        assert!(!offer(
+            &mut restored,
+            &event("59ca2eb686f77445a80ed049", 120, "{1}")
+        ));
+    }
+
+    #[test]
+    fn test_store_is_appended_then_compacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("seen_events.json");
+        let line_count = || fs::read_to_string(&store_path).unwrap().lines().count();
+
+        let mut dedup = EventDeduplicator::load(store_path.clone());
+        // Each event is remembered by its position and by its content
+        for offset in 0..3 {
+            let body = format!("{{{}}}", offset);
+            assert!(offer(
+                &mut dedup,
+                &event("59ca2eb686f77445a80ed049", offset, &body)
+            ));
+        }
+        assert_eq!(line_count(), 6);
+
+        // Expired keys are dropped from the file the next time it is compacted
+        dedup.seen.insert(
+            "expired".to_string(),
+            Utc::now() - Duration::days(SEEN_RETENTION_DAYS + 1),
+        );
+        dedup.store_lines = MAX_STORE_LINES;
+        assert!(offer(
+            &mut dedup,
+            &event("59ca2eb686f77445a80ed049", 3, "{3}")
+        ));
+        assert_eq!(line_count(), 8);
+        assert!(!fs::read_to_string(&store_path).unwrap().contains("expired"));
+    }
+
+    #[test]
+    fn test_retention_is_bounded() {
+        let mut dedup = EventDeduplicator::new();
+        let now = Utc::now();
+        dedup.seen.insert(
+            "expired".to_string(),
+            now - Duration::days(SEEN_RETENTION_DAYS + 1),
+        );
+        for i in 0..MAX_SEEN_ENTRIES + 10 {
+            dedup
+                .seen
+                .insert(format!("key{}", i), now - Duration::seconds(i as i64));
+        }
+
+        dedup.prune(now);
+
+        assert_eq!(dedup.seen_count(), MAX_SEEN_ENTRIES);
+        assert!(!dedup.seen.contains_key("expired"));
+        assert!(dedup.seen.contains_key("key0"));
+        assert!(!dedup
+            .seen
+            .contains_key(&format!("key{}", MAX_SEEN_ENTRIES + 5)));
+    }
+
+    #[test]
+    fn test_unreadable_store_starts_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("seen_events.json");
+        fs::write(&store_path, "not json").unwrap();
+
+        let dedup = EventDeduplicator::load(store_path);
+        assert_eq!(dedup.seen_count(), 0);
+    }
+}
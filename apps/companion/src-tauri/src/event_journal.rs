@@ -0,0 +1,355 @@
+//! Event Journal
+//!
+//! Keeps queued sync events on disk so they survive a crash or power loss:
+//! 1. Every queued event is appended as one checksummed line and fsync'd
+//! 2. Events the server acknowledged are recorded by id, then the journal is
+//!    compacted down to the events still pending
+//! 3. On start-up the journal is replayed up to the first torn or corrupt line,
+//!    and anything after it is cut off so new appends start on a clean line
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::event_dedup::content_hash;
+use crate::sync_manager::SyncEvent;
+
+/// One line of the journal
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum JournalRecord {
+    /// An event was queued
+    Enqueue { event: SyncEvent },
+    /// The server acknowledged these events
+    Ack {
+        #[serde(rename = "eventIds")]
+        event_ids: Vec<String>,
+    },
+}
+
+/// Append-only, fsync'd log of the sync queue
+pub struct EventJournal {
+    path: PathBuf,
+    file: File,
+}
+
+impl EventJournal {
+    /// Open the journal, returning it with the events still pending.
+    /// A torn or corrupt tail is dropped and truncated away.
+    pub fn open(path: PathBuf) -> io::Result<(Self, Vec<SyncEvent>)> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let (events, valid_len) = replay(&content);
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if valid_len < content.len() {
+            warn!(
+                "Dropping {} bytes of torn or corrupt records from {:?}",
+                content.len() - valid_len,
+                path
+            );
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+        if !events.is_empty() {
+            info!("Recovered {} pending events from {:?}", events.len(), path);
+        }
+
+        Ok((Self { path, file }, events.into()))
+    }
+
+    /// Record a queued event, returning once it is on disk
+    pub fn append(&mut self, event: &SyncEvent) -> io::Result<()> {
+        self.write_record(&JournalRecord::Enqueue {
+            event: event.clone(),
+        })
+    }
+
+    /// Record that the server acknowledged `event_ids`, then compact the
+    /// journal down to the `pending` events that remain
+    pub fn acknowledge<'a>(
+        &mut self,
+        event_ids: &[String],
+        pending: impl IntoIterator<Item = &'a SyncEvent>,
+    ) -> io::Result<()> {
+        if !event_ids.is_empty() {
+            self.write_record(&JournalRecord::Ack {
+                event_ids: event_ids.to_vec(),
+            })?;
+        }
+        self.compact(pending)
+    }
+
+    /// Rewrite the journal atomically (temp file, then rename) with only `pending`
+    pub fn compact<'a>(
+        &mut self,
+        pending: impl IntoIterator<Item = &'a SyncEvent>,
+    ) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("journal.tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        for event in pending {
+            tmp.write_all(&encode(&JournalRecord::Enqueue {
+                event: event.clone(),
+            })?)?;
+        }
+        tmp.sync_all()?;
+        drop(tmp);
+
+        fs::rename(&tmp_path, &self.path)?;
+        sync_dir(&self.path);
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+
+    /// Append a record; a failed write is cut off so the next one starts on a clean line
+    fn write_record(&mut self, record: &JournalRecord) -> io::Result<()> {
+        let line = encode(record)?;
+        let len = self.file.metadata()?.len();
+        let written = self
+            .file
+            .write_all(&line)
+            .and_then(|()| self.file.sync_data());
+        if written.is_err() {
+            let _ = self.file.set_len(len);
+        }
+        written
+    }
+
+    /// A journal whose writes all fail, for testing error handling
+    #[cfg(test)]
+    pub(crate) fn failing(path: PathBuf) -> io::Result<Self> {
+        fs::write(&path, "")?;
+        let file = File::open(&path)?;
+        Ok(Self { path, file })
+    }
+}
+
+/// A record as a line: checksum of the JSON, a space, then the JSON
+fn encode(record: &JournalRecord) -> io::Result<Vec<u8>> {
+    let json = serde_json::to_string(record)?;
+    Ok(format!("{} {}\n", content_hash(&json), json).into_bytes())
+}
+
+/// Decode one line, without its newline; None if it is torn or corrupt
+fn decode(line: &[u8]) -> Option<JournalRecord> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (checksum, json) = line.split_once(' ')?;
+    if checksum != content_hash(json) {
+        return None;
+    }
+    serde_json::from_str(json).ok()
+}
+
+/// Pending events in the journal and the length of its valid prefix
+fn replay(content: &[u8]) -> (VecDeque<SyncEvent>, usize) {
+    let mut events = VecDeque::new();
+    let mut valid_len = 0;
+
+    // A line without its newline was cut off mid-write
+    while let Some(end) = content[valid_len..].iter().position(|&b| b == b'\n') {
+        let Some(record) = decode(&content[valid_len..valid_len + end]) else {
+            break;
+        };
+        match record {
+            JournalRecord::Enqueue { event } => events.push_back(event),
+            JournalRecord::Ack { event_ids } => {
+                let acked: HashSet<String> = event_ids.into_iter().collect();
+                events.retain(|event| !acked.contains(&event.event_id));
+            }
+        }
+        valid_len += end + 1;
+    }
+
+    (events, valid_len)
+}
+
+/// Make a rename durable; directories cannot be opened for syncing on Windows
+pub(crate) fn sync_dir(path: &Path) {
+    if let Some(dir) = path.parent().and_then(|parent| File::open(parent).ok()) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    /// Set in the child process that is killed while appending
+    const WRITER_JOURNAL_ENV: &str = "EVENT_JOURNAL_WRITER_PATH";
+
+    fn event(i: usize) -> SyncEvent {
+        SyncEvent {
+            event_id: format!("event{}", i),
+            quest_id: format!("quest{}", i),
+            status: "FINISHED".to_string(),
+            timestamp: "2024-01-15T10:30:00Z".to_string(),
+            game_version: None,
+        }
+    }
+
+    fn quest_ids(events: &[SyncEvent]) -> Vec<String> {
+        events.iter().map(|e| e.quest_id.clone()).collect()
+    }
+
+    #[test]
+    fn test_pending_events_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("event_queue.journal");
+
+        let (mut journal, recovered) = EventJournal::open(path.clone()).unwrap();
+        assert!(recovered.is_empty());
+        for i in 0..3 {
+            journal.append(&event(i)).unwrap();
+        }
+        drop(journal);
+
+        let (_, recovered) = EventJournal::open(path).unwrap();
+        assert_eq!(quest_ids(&recovered), ["quest0", "quest1", "quest2"]);
+    }
+
+    #[test]
+    fn test_acknowledged_events_are_compacted_away() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("event_queue.journal");
+
+        let (mut journal, _) = EventJournal::open(path.clone()).unwrap();
+        let events: Vec<SyncEvent> = (0..3).map(event).collect();
+        for e in &events {
+            journal.append(e).unwrap();
+        }
+        let acked = ["event0".to_string(), "event2".to_string()];
+        journal.acknowledge(&acked, &events[1..2]).unwrap();
+        journal.append(&event(3)).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(!content.contains("quest0"));
+
+        let (_, recovered) = EventJournal::open(path).unwrap();
+        assert_eq!(quest_ids(&recovered), ["quest1", "quest3"]);
+    }
+
+    #[test]
+    fn test_ack_without_compaction_is_replayed() {
+        let mut content = Vec::new();
+        for i in 0..3 {
+            content.extend(encode(&JournalRecord::Enqueue { event: event(i) }).unwrap());
+        }
+        let ack = JournalRecord::Ack {
+            event_ids: vec!["event1".to_string()],
+        };
+        content.extend(encode(&ack).unwrap());
+
+        let (events, valid_len) = replay(&content);
+        assert_eq!(quest_ids(&Vec::from(events)), ["quest0", "quest2"]);
+        assert_eq!(valid_len, content.len());
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated_at_every_cut() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("event_queue.journal");
+
+        let intact = [
+            encode(&JournalRecord::Enqueue { event: event(0) }).unwrap(),
+            encode(&JournalRecord::Enqueue { event: event(1) }).unwrap(),
+        ]
+        .concat();
+        let last = encode(&JournalRecord::Enqueue { event: event(2) }).unwrap();
+
+        for cut in 0..last.len() {
+            fs::write(&path, [&intact[..], &last[..cut]].concat()).unwrap();
+
+            let (mut journal, recovered) = EventJournal::open(path.clone()).unwrap();
+            assert_eq!(
+                quest_ids(&recovered),
+                ["quest0", "quest1"],
+                "cut at {}",
+                cut
+            );
+            assert_eq!(fs::metadata(&path).unwrap().len(), intact.len() as u64);
+
+            // New records start on a clean line
+            journal.append(&event(3)).unwrap();
+            let (_, recovered) = EventJournal::open(path.clone()).unwrap();
+            assert_eq!(quest_ids(&recovered), ["quest0", "quest1", "quest3"]);
+        }
+    }
+
+    #[test]
+    fn test_corrupt_record_ends_replay() {
+        let first = encode(&JournalRecord::Enqueue { event: event(0) }).unwrap();
+        let mut second = encode(&JournalRecord::Enqueue { event: event(1) }).unwrap();
+        let at = second.len() - 5;
+        second[at] ^= 0x20;
+        // Zeroes left behind by a power loss while the file was being extended
+        let content = [&first[..], &second[..], &[0u8; 64][..]].concat();
+
+        let (events, valid_len) = replay(&content);
+        assert_eq!(quest_ids(&Vec::from(events)), ["quest0"]);
+        assert_eq!(valid_len, first.len());
+    }
+
+    /// Appends until killed; run as a child process by the test below
+    #[test]
+    #[ignore = "run as a child process by test_recovers_after_writer_is_killed"]
+    fn journal_writer_process() {
+        let Some(path) = std::env::var_os(WRITER_JOURNAL_ENV) else {
+            return;
+        };
+        let (mut journal, _) = EventJournal::open(PathBuf::from(path)).unwrap();
+        for i in 0.. {
+            journal.append(&event(i)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_recovers_after_writer_is_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("event_queue.journal");
+
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "event_journal::tests::journal_writer_process",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+            ])
+            .env(WRITER_JOURNAL_ENV, &path)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while fs::metadata(&path).map(|m| m.len()).unwrap_or(0) < 8 * 1024 {
+            assert!(Instant::now() < deadline, "writer did not start");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        // Whatever was written is recovered in order, with nothing skipped
+        let (mut journal, recovered) = EventJournal::open(path.clone()).unwrap();
+        assert!(!recovered.is_empty());
+        let expected: Vec<String> = (0..recovered.len())
+            .map(|i| format!("quest{}", i))
+            .collect();
+        assert_eq!(quest_ids(&recovered), expected);
+
+        journal.append(&event(recovered.len())).unwrap();
+        let (_, reopened) = EventJournal::open(path).unwrap();
+        assert_eq!(reopened.len(), recovered.len() + 1);
+    }
+}
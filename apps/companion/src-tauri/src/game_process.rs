@@ -0,0 +1,326 @@
+//! Game Process Detection
+//!
+//! Follows play sessions so watching does not depend on the tray menu:
+//! 1. Running processes are listed every few seconds through a `ProcessBackend`
+//! 2. The game and the BSG Launcher are matched by executable name, which also
+//!    finds them when run under Wine or Proton
+//! 3. Starts and exits are reported as `GameEvent`s, with the install folder
+//!    taken from the game's executable path
+
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use tokio::sync::mpsc;
+
+/// How often running processes are listed
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+const GAME_EXECUTABLE: &str = "EscapeFromTarkov.exe";
+
+const LAUNCHER_EXECUTABLES: &[&str] = &["BsgLauncher.exe"];
+
+/// Linux truncates process names to this many bytes
+const LINUX_NAME_LEN: usize = 15;
+
+/// A running process
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessInfo {
+    pub name: String,
+    pub exe: Option<PathBuf>,
+    /// First command-line argument; under Wine this is the program's Windows path
+    pub command: Option<String>,
+}
+
+/// Lists running processes
+pub trait ProcessBackend: Send {
+    fn processes(&mut self) -> Vec<ProcessInfo>;
+}
+
+/// Processes of this machine
+#[derive(Default)]
+pub struct SystemProcesses {
+    system: System,
+}
+
+impl ProcessBackend for SystemProcesses {
+    fn processes(&mut self) -> Vec<ProcessInfo> {
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing()
+                .with_exe(UpdateKind::OnlyIfNotSet)
+                .with_cmd(UpdateKind::OnlyIfNotSet),
+        );
+
+        self.system
+            .processes()
+            .values()
+            .map(|process| ProcessInfo {
+                name: process.name().to_string_lossy().to_string(),
+                exe: process.exe().map(Path::to_path_buf),
+                command: process
+                    .cmd()
+                    .first()
+                    .map(|arg| arg.to_string_lossy().to_string()),
+            })
+            .collect()
+    }
+}
+
+/// Last component of a Windows or Unix path
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Whether a process runs the given executable
+fn runs(process: &ProcessInfo, executable: &str) -> bool {
+    let name = process.name.as_str();
+    let truncated = executable.get(..LINUX_NAME_LEN).unwrap_or(executable);
+    let by_name = name.eq_ignore_ascii_case(executable)
+        || (name.len() == LINUX_NAME_LEN && name.eq_ignore_ascii_case(truncated));
+
+    by_name
+        || process
+            .exe
+            .as_ref()
+            .is_some_and(|exe| file_name(&exe.to_string_lossy()).eq_ignore_ascii_case(executable))
+        || process
+            .command
+            .as_deref()
+            .is_some_and(|command| file_name(command).eq_ignore_ascii_case(executable))
+}
+
+/// Folder the game was started from, if it is a path on this machine
+fn install_folder(process: &ProcessInfo) -> Option<PathBuf> {
+    let is_game = |path: &Path| {
+        path.is_absolute()
+            && file_name(&path.to_string_lossy()).eq_ignore_ascii_case(GAME_EXECUTABLE)
+    };
+
+    // Under Wine the executable is the Wine loader, but the command is the game
+    process
+        .exe
+        .clone()
+        .filter(|exe| is_game(exe))
+        .or_else(|| {
+            process
+                .command
+                .as_ref()
+                .map(PathBuf::from)
+                .filter(|command| is_game(command))
+        })
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+/// A change in what is running
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// The game started, from `install` if it could be resolved
+    GameStarted {
+        install: Option<PathBuf>,
+    },
+    /// The game or launcher started while neither was running
+    SessionStarted,
+    GameExited,
+    /// Neither the game nor the launcher is running any more
+    SessionEnded,
+}
+
+/// Turns process listings into start and exit events
+pub struct GameProcessMonitor {
+    backend: Box<dyn ProcessBackend>,
+    game_running: bool,
+    launcher_running: bool,
+}
+
+impl GameProcessMonitor {
+    pub fn new(backend: Box<dyn ProcessBackend>) -> Self {
+        Self {
+            backend,
+            game_running: false,
+            launcher_running: false,
+        }
+    }
+
+    /// List processes and report what started or exited since the last poll
+    pub fn poll(&mut self) -> Vec<GameEvent> {
+        let processes = self.backend.processes();
+        let game = processes.iter().find(|p| runs(p, GAME_EXECUTABLE));
+        let launcher_running = processes
+            .iter()
+            .any(|p| LAUNCHER_EXECUTABLES.iter().any(|exe| runs(p, exe)));
+
+        let was_active = self.game_running || self.launcher_running;
+        let mut events = Vec::new();
+
+        match (self.game_running, game) {
+            (false, Some(process)) => {
+                let install = install_folder(process);
+                info!("EFT started from {:?}", install);
+                events.push(GameEvent::GameStarted { install });
+            }
+            (true, None) => {
+                info!("EFT exited");
+                events.push(GameEvent::GameExited);
+            }
+            _ => {}
+        }
+        if launcher_running != self.launcher_running {
+            debug!("BSG Launcher running: {}", launcher_running);
+        }
+
+        self.game_running = game.is_some();
+        self.launcher_running = launcher_running;
+
+        let active = self.game_running || self.launcher_running;
+        if active && !was_active {
+            events.push(GameEvent::SessionStarted);
+        } else if !active && was_active {
+            events.push(GameEvent::SessionEnded);
+        }
+
+        events
+    }
+}
+
+/// Poll for the game on a background thread until the receiver is dropped
+pub fn spawn(mut monitor: GameProcessMonitor) -> mpsc::UnboundedReceiver<GameEvent> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    thread::spawn(move || loop {
+        for event in monitor.poll() {
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Returns one scripted listing per poll
+    struct ScriptedProcesses(VecDeque<Vec<ProcessInfo>>);
+
+    impl ProcessBackend for ScriptedProcesses {
+        fn processes(&mut self) -> Vec<ProcessInfo> {
+            self.0.pop_front().unwrap_or_default()
+        }
+    }
+
+    fn process(name: &str, exe: Option<&str>, command: Option<&str>) -> ProcessInfo {
+        ProcessInfo {
+            name: name.to_string(),
+            exe: exe.map(PathBuf::from),
+            command: command.map(str::to_string),
+        }
+    }
+
+    fn monitor(listings: Vec<Vec<ProcessInfo>>) -> GameProcessMonitor {
+        GameProcessMonitor::new(Box::new(ScriptedProcesses(listings.into())))
+    }
+
+    #[test]
+    fn test_matches_game_processes() {
+        let windows = process(
+            "EscapeFromTarkov.exe",
+            Some("C:\\Battlestate Games\\EFT\\EscapeFromTarkov.exe"),
+            None,
+        );
+        let linux = process("EscapeFromTarko", None, None);
+        let wine = process(
+            "wine64-preloader",
+            Some("/usr/bin/wine64-preloader"),
+            Some("C:\\Battlestate Games\\EFT\\EscapeFromTarkov.exe"),
+        );
+
+        assert!(runs(&windows, GAME_EXECUTABLE));
+        assert!(runs(&linux, GAME_EXECUTABLE));
+        assert!(runs(&wine, GAME_EXECUTABLE));
+        assert!(!runs(
+            &process("EscapeFromTarkov_BE.exe", None, None),
+            GAME_EXECUTABLE
+        ));
+        assert!(!runs(&process("EscapeFrom", None, None), GAME_EXECUTABLE));
+    }
+
+    #[test]
+    fn test_install_folder_from_host_paths_only() {
+        let native = if cfg!(windows) {
+            process(
+                "EscapeFromTarkov.exe",
+                Some("C:\\EFT\\EscapeFromTarkov.exe"),
+                None,
+            )
+        } else {
+            process(
+                "EscapeFromTarko",
+                Some("/games/EFT/EscapeFromTarkov.exe"),
+                None,
+            )
+        };
+        let expected = if cfg!(windows) {
+            "C:\\EFT"
+        } else {
+            "/games/EFT"
+        };
+        assert_eq!(install_folder(&native), Some(PathBuf::from(expected)));
+
+        // A Windows path inside a Wine prefix is not a path on a Linux host
+        let wine = process(
+            "wine64-preloader",
+            Some("/usr/bin/wine64-preloader"),
+            Some("C:\\EFT\\EscapeFromTarkov.exe"),
+        );
+        if !cfg!(windows) {
+            assert_eq!(install_folder(&wine), None);
+        }
+    }
+
+    #[test]
+    fn test_game_session_events() {
+        let game = process("EscapeFromTarkov.exe", None, None);
+        let launcher = process("BsgLauncher.exe", None, None);
+        let mut monitor = monitor(vec![
+            vec![launcher.clone()],
+            vec![launcher.clone(), game.clone()],
+            vec![launcher.clone(), game],
+            vec![launcher],
+            vec![],
+        ]);
+
+        assert_eq!(monitor.poll(), vec![GameEvent::SessionStarted]);
+        assert_eq!(
+            monitor.poll(),
+            vec![GameEvent::GameStarted { install: None }]
+        );
+        assert_eq!(monitor.poll(), vec![]);
+        assert_eq!(monitor.poll(), vec![GameEvent::GameExited]);
+        assert_eq!(monitor.poll(), vec![GameEvent::SessionEnded]);
+    }
+
+    #[test]
+    fn test_game_without_launcher() {
+        let game = process("EscapeFromTarkov.exe", None, None);
+        let mut monitor = monitor(vec![vec![game], vec![]]);
+
+        assert_eq!(
+            monitor.poll(),
+            vec![
+                GameEvent::GameStarted { install: None },
+                GameEvent::SessionStarted
+            ]
+        );
+        assert_eq!(
+            monitor.poll(),
+            vec![GameEvent::GameExited, GameEvent::SessionEnded]
+        );
+    }
+}
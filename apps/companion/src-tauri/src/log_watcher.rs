@@ -1,7 +1,13 @@
 //! EFT Log File Watcher
 //!
 //! Watches the EFT Logs directory for changes and parses quest events.
-//! Uses a polling approach (5-second intervals) since EFT may have the files locked.
+//! Filesystem events and polling of the active session (EFT may hold the files locked
+//! without emitting events) are coordinated by the `ScanScheduler`.
+//! The watch loop runs as a tokio task that is cancelled and joined on `stop()`.
+//!
+//! SPT installs are watched through their client's Logs folder, whose quest messages
+//! come from the local server and name their status only in the `templateId`, and
+//! through their profile files, whose quest states are compared between reads.
 
 use chrono::{DateTime, Utc};
 use log::{debug, info, warn};
@@ -9,13 +15,21 @@ use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::channel;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::eft_detector::SptPaths;
+use crate::error::CompanionError;
+use crate::event_dedup::content_hash;
+use crate::scan_scheduler::{discover_session, ScanScheduler};
+use crate::spt_profile::ProfileTracker;
 
 /// Quest event status as reported in EFT logs
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -42,8 +56,33 @@ pub struct QuestEvent {
     pub status: QuestEventStatus,
     pub timestamp: DateTime<Utc>,
     pub log_file: String,
+    #[serde(default)]
+    pub origin: EventOrigin,
+}
+
+/// Where in the logs a quest event was read from, used to recognise re-reads and repeats
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EventOrigin {
+    /// Read from a game log or profile; events made up elsewhere are never deduplicated
+    #[serde(default)]
+    pub from_log: bool,
+    /// Name of the log session folder containing the file
+    pub session: String,
+    /// Byte offset of the notification header within the log file
+    pub offset: u64,
+    /// Hash of the notification body. The body carries the game's message id, so it is
+    /// identical when the game emits a message twice and differs for a real repeat
+    pub content_hash: String,
 }
 
+/// Maximum size of a single notification before it is abandoned
+const MAX_NOTIFICATION_SIZE: usize = 10000;
+
+/// Quest message from an SPT server, which leaves the text empty and sends the quest's
+/// message key, e.g. `"templateId": "5936d90786f7742b1420ba5b successMessageText"`
+const SPT_QUEST_PATTERN: &str =
+    r#""templateId"\s*:\s*"([a-f0-9]{24}) (started|success|fail)MessageText""#;
+
 /// Log file state for tracking read position
 struct LogFileState {
     #[allow(dead_code)]
@@ -52,26 +91,172 @@ struct LogFileState {
     last_modified: std::time::SystemTime,
 }
 
+/// A `ChatMessageReceived` notification whose JSON body is still being collected
+struct PendingNotification {
+    start: usize,
+    header_len: usize,
+    context: String,
+    depth: i32,
+    opened: bool,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl PendingNotification {
+    fn new(header: &str, start: usize) -> Self {
+        let mut notification = Self {
+            start,
+            header_len: header.len(),
+            context: String::new(),
+            depth: 0,
+            opened: false,
+            in_string: false,
+            escaped: false,
+        };
+        notification.push_line(header);
+        notification
+    }
+
+    /// Append a line and track brace depth, ignoring braces inside JSON strings
+    fn push_line(&mut self, line: &str) {
+        for c in line.chars() {
+            if self.in_string {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match c {
+                '"' if self.opened => self.in_string = true,
+                '{' => {
+                    self.depth += 1;
+                    self.opened = true;
+                }
+                '}' if self.opened => self.depth -= 1,
+                _ => {}
+            }
+        }
+        self.context.push_str(line);
+    }
+
+    /// The notification body without its timestamped header line
+    fn body(&self) -> &str {
+        &self.context[self.header_len..]
+    }
+
+    /// The notification is complete once its JSON body has been closed
+    fn is_complete(&self) -> bool {
+        self.opened && self.depth <= 0
+    }
+}
+
+/// What a watcher reads quest events from
+#[derive(Debug, Clone)]
+enum WatchTarget {
+    /// A live install's Logs folder, with one folder per game session
+    Logs(PathBuf),
+    /// An SPT install's client logs and profiles
+    Spt { install: PathBuf, paths: SptPaths },
+}
+
+impl WatchTarget {
+    /// Folder the scheduler treats as the root of everything watched
+    fn root(&self) -> &Path {
+        match self {
+            WatchTarget::Logs(logs_dir) => logs_dir,
+            WatchTarget::Spt { install, .. } => install,
+        }
+    }
+
+    /// Folders to receive filesystem events for
+    fn watch_folders(&self) -> Vec<(PathBuf, RecursiveMode)> {
+        match self {
+            WatchTarget::Logs(logs_dir) => vec![(logs_dir.clone(), RecursiveMode::Recursive)],
+            WatchTarget::Spt { paths, .. } => {
+                let mut folders = vec![(paths.profiles.clone(), RecursiveMode::NonRecursive)];
+                // The client creates its Logs folder on first launch
+                if paths.client_logs.is_dir() {
+                    folders.push((paths.client_logs.clone(), RecursiveMode::Recursive));
+                }
+                folders
+            }
+        }
+    }
+
+    /// The active session folder and the files to read
+    async fn discover(&self) -> std::io::Result<(Option<PathBuf>, Vec<PathBuf>)> {
+        match self {
+            WatchTarget::Logs(logs_dir) => discover_session(logs_dir).await,
+            WatchTarget::Spt { paths, .. } => {
+                let (session, mut files) = if fs::metadata(&paths.client_logs).await.is_ok() {
+                    discover_session(&paths.client_logs).await?
+                } else {
+                    (None, Vec::new())
+                };
+                let mut profiles = Vec::new();
+                let mut entries = fs::read_dir(&paths.profiles).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|e| e == "json") {
+                        profiles.push(path);
+                    }
+                }
+                profiles.sort();
+                files.extend(profiles);
+                Ok((session.or_else(|| Some(paths.profiles.clone())), files))
+            }
+        }
+    }
+
+    /// Whether a file is an SPT profile rather than a log
+    fn is_profile(&self, path: &Path) -> bool {
+        match self {
+            WatchTarget::Logs(_) => false,
+            WatchTarget::Spt { paths, .. } => path.parent() == Some(paths.profiles.as_path()),
+        }
+    }
+}
+
 pub struct LogWatcher {
     logs_path: String,
-    _watcher: RecommendedWatcher,
-    stop_flag: Arc<Mutex<bool>>,
-    file_states: Arc<Mutex<HashMap<PathBuf, LogFileState>>>,
+    cancel: CancellationToken,
+    task: Option<JoinHandle<()>>,
 }
 
 impl LogWatcher {
-    /// Create a new log watcher for the given logs directory
-    pub fn new<F>(logs_path: String, callback: F) -> Result<Self, Box<dyn std::error::Error>>
+    /// Create a new log watcher for the given logs directory.
+    /// Must be called from within a tokio runtime; the watch loop runs as a tokio task.
+    pub async fn new<F>(logs_path: String, callback: F) -> Result<Self, CompanionError>
+    where
+        F: Fn(QuestEvent) + Send + Sync + 'static,
+    {
+        Self::start(WatchTarget::Logs(PathBuf::from(logs_path)), callback).await
+    }
+
+    /// Create a watcher for an SPT install folder.
+    /// Must be called from within a tokio runtime; the watch loop runs as a tokio task.
+    pub async fn new_spt<F>(install_path: String, callback: F) -> Result<Self, CompanionError>
     where
         F: Fn(QuestEvent) + Send + Sync + 'static,
     {
-        let (tx, rx) = channel();
-        let stop_flag = Arc::new(Mutex::new(false));
-        let file_states: Arc<Mutex<HashMap<PathBuf, LogFileState>>> =
-            Arc::new(Mutex::new(HashMap::new()));
+        let install = PathBuf::from(install_path);
+        let paths = SptPaths::new(&install);
+        Self::start(WatchTarget::Spt { install, paths }, callback).await
+    }
+
+    async fn start<F>(target: WatchTarget, callback: F) -> Result<Self, CompanionError>
+    where
+        F: Fn(QuestEvent) + Send + Sync + 'static,
+    {
+        let logs_path = target.root().to_string_lossy().to_string();
+        let (tx, rx) = unbounded_channel();
 
         // Create file watcher
-        let watcher = RecommendedWatcher::new(
+        let mut watcher = RecommendedWatcher::new(
             move |res| {
                 if let Ok(event) = res {
                     let _ = tx.send(event);
@@ -80,96 +265,96 @@ impl LogWatcher {
             Config::default().with_poll_interval(Duration::from_secs(5)),
         )?;
 
-        let logs_path_clone = logs_path.clone();
-        let stop_flag_clone = stop_flag.clone();
-        let file_states_clone = file_states.clone();
-        let callback = Arc::new(callback);
-
-        // Start the watcher thread
-        thread::spawn(move || {
-            Self::watch_loop(
-                logs_path_clone,
-                rx,
-                stop_flag_clone,
-                file_states_clone,
-                callback,
-            );
-        });
-
-        let mut watcher_instance = Self {
-            logs_path: logs_path.clone(),
-            _watcher: watcher,
-            stop_flag,
-            file_states,
-        };
-
-        // Start watching the logs directory
-        watcher_instance
-            ._watcher
-            .watch(Path::new(&logs_path), RecursiveMode::NonRecursive)?;
+        // Start watching the logs directory, including session folders
+        for (folder, mode) in target.watch_folders() {
+            watcher.watch(&folder, mode)?;
+        }
 
         // Initial scan of existing log files
-        watcher_instance.initial_scan()?;
+        let mut scheduler = ScanScheduler::new(target.root().to_path_buf(), Instant::now());
+        let mut profiles = ProfileTracker::new();
+        let file_states = Self::initial_scan(&target, &mut scheduler, &mut profiles).await?;
+
+        let cancel = CancellationToken::new();
+        let task = tokio::spawn(Self::watch_loop(
+            target,
+            watcher,
+            rx,
+            cancel.clone(),
+            scheduler,
+            file_states,
+            profiles,
+            Arc::new(callback),
+        ));
 
         info!("Started watching logs directory: {}", logs_path);
-        Ok(watcher_instance)
+        Ok(Self {
+            logs_path,
+            cancel,
+            task: Some(task),
+        })
     }
 
-    /// Initial scan of existing log files to find the latest one
-    fn initial_scan(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let logs_dir = Path::new(&self.logs_path);
-        if !logs_dir.exists() {
-            return Ok(());
+    /// Stop the watch loop and wait for it to finish
+    pub async fn stop(mut self) {
+        self.cancel.cancel();
+        if let Some(task) = self.task.take() {
+            if let Err(e) = task.await {
+                warn!("Log watcher task ended abnormally: {}", e);
+            }
         }
+        info!("Stopped watching logs directory: {}", self.logs_path);
+    }
 
-        // Find the most recent log file
-        let mut latest_file: Option<(PathBuf, std::time::SystemTime)> = None;
-
-        for entry in fs::read_dir(logs_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+    /// Whether the watch loop is still running
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| !task.is_finished())
+    }
 
-            if path.extension().map(|e| e == "log").unwrap_or(false) {
-                if let Ok(metadata) = entry.metadata() {
-                    if let Ok(modified) = metadata.modified() {
-                        match &latest_file {
-                            None => latest_file = Some((path, modified)),
-                            Some((_, latest_time)) if modified > *latest_time => {
-                                latest_file = Some((path, modified));
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
+    /// Initial scan: find the active session and start its files at their current end.
+    /// SPT profiles are read once so only later changes are reported.
+    async fn initial_scan(
+        target: &WatchTarget,
+        scheduler: &mut ScanScheduler,
+        profiles: &mut ProfileTracker,
+    ) -> std::io::Result<HashMap<PathBuf, LogFileState>> {
+        let mut states = HashMap::new();
+        if !target.root().exists() {
+            return Ok(states);
         }
 
-        // Set up state for the latest file (seek to end to only catch new events)
-        if let Some((path, modified)) = latest_file {
-            if let Ok(file) = File::open(&path) {
-                let size = file.metadata().map(|m| m.len()).unwrap_or(0);
-
-                let mut states = self.file_states.lock().unwrap();
+        let (session, files) = target.discover().await?;
+        for path in &files {
+            if target.is_profile(path) {
+                Self::process_profile(path, &mut states, profiles, &|_| {}).await;
+                continue;
+            }
+            if let Ok(metadata) = fs::metadata(path).await {
                 states.insert(
                     path.clone(),
                     LogFileState {
-                        path,
-                        position: size, // Start at end to only catch new events
-                        last_modified: modified,
+                        path: path.clone(),
+                        position: metadata.len(), // Start at end to only catch new events
+                        last_modified: metadata.modified().unwrap_or(std::time::UNIX_EPOCH),
                     },
                 );
             }
         }
+        scheduler.set_session(session, files);
 
-        Ok(())
+        Ok(states)
     }
 
-    /// Main watch loop that processes file change events
-    fn watch_loop<F>(
-        logs_path: String,
-        rx: std::sync::mpsc::Receiver<notify::Event>,
-        stop_flag: Arc<Mutex<bool>>,
-        file_states: Arc<Mutex<HashMap<PathBuf, LogFileState>>>,
+    /// Main watch loop that processes file change events until cancelled
+    #[allow(clippy::too_many_arguments)]
+    async fn watch_loop<F>(
+        target: WatchTarget,
+        _watcher: RecommendedWatcher,
+        mut rx: UnboundedReceiver<notify::Event>,
+        cancel: CancellationToken,
+        mut scheduler: ScanScheduler,
+        mut file_states: HashMap<PathBuf, LogFileState>,
+        mut profiles: ProfileTracker,
         callback: Arc<F>,
     ) where
         F: Fn(QuestEvent) + Send + Sync + 'static,
@@ -189,94 +374,110 @@ impl LogWatcher {
         )
         .unwrap();
 
-        // Alternative pattern if templateId comes before text
-        let quest_pattern_alt = Regex::new(
-            r#""templateId"\s*:\s*"([a-f0-9]{24})[\s\S]*?"text"\s*:\s*"quest (started|finished|failed)""#,
-        )
+        // Alternative pattern if templateId comes before text; SPT's quest messages
+        // have no text, so their status is read from the message key instead
+        let quest_pattern_alt = match target {
+            WatchTarget::Logs(_) => Regex::new(
+                r#""templateId"\s*:\s*"([a-f0-9]{24})[\s\S]*?"text"\s*:\s*"quest (started|finished|failed)""#,
+            ),
+            WatchTarget::Spt { .. } => Regex::new(SPT_QUEST_PATTERN),
+        }
         .unwrap();
 
         loop {
-            // Check stop flag
-            if *stop_flag.lock().unwrap() {
-                info!("Log watcher stopping");
-                break;
-            }
+            let deadline = tokio::time::Instant::from_std(scheduler.next_deadline());
 
-            // Process events with timeout
-            match rx.recv_timeout(Duration::from_secs(1)) {
-                Ok(event) => {
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    info!("Log watcher stopping");
+                    break;
+                }
+                event = rx.recv() => {
+                    let Some(event) = event else {
+                        warn!("File watcher channel disconnected");
+                        break;
+                    };
+                    let now = Instant::now();
                     for path in event.paths {
-                        if path.extension().map(|e| e == "log").unwrap_or(false) {
-                            Self::process_log_file(
-                                &path,
-                                &file_states,
-                                &notification_pattern,
-                                &quest_pattern,
-                                &quest_pattern_alt,
-                                &callback,
-                            );
-                        }
+                        scheduler.record_event(path, now);
                     }
                 }
-                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
-                    // Periodic check - scan for new files
-                    let logs_dir = Path::new(&logs_path);
-                    if logs_dir.exists() {
-                        if let Ok(entries) = fs::read_dir(logs_dir) {
-                            for entry in entries.flatten() {
-                                let path = entry.path();
-                                if path.extension().map(|e| e == "log").unwrap_or(false) {
-                                    Self::process_log_file(
-                                        &path,
-                                        &file_states,
-                                        &notification_pattern,
-                                        &quest_pattern,
-                                        &quest_pattern_alt,
-                                        &callback,
-                                    );
+                _ = tokio::time::sleep_until(deadline) => {
+                    let now = Instant::now();
+                    let mut batch = scheduler.take_due(now);
+
+                    if batch.refresh_session {
+                        match target.discover().await {
+                            Ok((session, files)) => {
+                                if scheduler.active_session() != session.as_deref() {
+                                    info!("Active log session: {:?}", session);
                                 }
+                                batch.files.extend(scheduler.set_session(session, files));
                             }
+                            Err(e) => debug!("Could not scan logs directory {:?}: {}", target.root(), e),
                         }
                     }
-                }
-                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                    warn!("File watcher channel disconnected");
-                    break;
+
+                    let mut grew = false;
+                    for path in &batch.files {
+                        if target.is_profile(path) {
+                            grew |= Self::process_profile(
+                                path,
+                                &mut file_states,
+                                &mut profiles,
+                                callback.as_ref(),
+                            )
+                            .await;
+                            continue;
+                        }
+                        grew |= Self::process_log_file(
+                            path,
+                            &mut file_states,
+                            &notification_pattern,
+                            &quest_pattern,
+                            &quest_pattern_alt,
+                            &mut profiles,
+                            &callback,
+                        )
+                        .await;
+                    }
+
+                    if batch.polled {
+                        scheduler.record_poll(grew, now);
+                    }
                 }
             }
         }
     }
 
-    /// Process a single log file for quest events
-    fn process_log_file<F>(
+    /// Process a single log file for quest events.
+    /// Returns true if new content was read.
+    async fn process_log_file<F>(
         path: &Path,
-        file_states: &Arc<Mutex<HashMap<PathBuf, LogFileState>>>,
+        file_states: &mut HashMap<PathBuf, LogFileState>,
         notification_pattern: &Regex,
         quest_pattern: &Regex,
         quest_pattern_alt: &Regex,
+        profiles: &mut ProfileTracker,
         callback: &Arc<F>,
-    ) where
+    ) -> bool
+    where
         F: Fn(QuestEvent) + Send + Sync + 'static,
     {
-        let file = match File::open(path) {
-            Ok(f) => f,
+        // Stat first so unchanged files are never opened
+        let metadata = match fs::metadata(path).await {
+            Ok(m) => m,
             Err(e) => {
-                debug!("Could not open log file {:?}: {}", path, e);
-                return;
+                debug!("Could not stat log file {:?}: {}", path, e);
+                return false;
             }
         };
 
-        let metadata = match file.metadata() {
-            Ok(m) => m,
-            Err(_) => return,
-        };
-
         let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
         let file_size = metadata.len();
 
         // Get or create file state
-        let mut states = file_states.lock().unwrap();
-        let state = states
+        let state = file_states
             .entry(path.to_path_buf())
             .or_insert_with(|| LogFileState {
                 path: path.to_path_buf(),
@@ -284,60 +485,173 @@ impl LogWatcher {
                 last_modified: modified,
             });
 
+        // A file shorter than what was read has been rewritten
+        if file_size < state.position {
+            debug!("Log file {:?} was rewritten, reading from the start", path);
+            state.position = 0;
+        }
+
         // Skip if file hasn't changed
         if state.position >= file_size {
-            return;
+            return false;
         }
 
-        // Read new content
-        let mut reader = BufReader::new(file);
-        if reader.seek(SeekFrom::Start(state.position)).is_err() {
-            return;
+        let mut file = match File::open(path).await {
+            Ok(f) => f,
+            Err(e) => {
+                debug!("Could not open log file {:?}: {}", path, e);
+                return false;
+            }
+        };
+
+        // Read everything past the last committed record
+        if file.seek(SeekFrom::Start(state.position)).await.is_err() {
+            return false;
+        }
+
+        let mut buffer = Vec::new();
+        if file.read_to_end(&mut buffer).await.is_err() {
+            return false;
+        }
+
+        let (events, consumed) = Self::extract_events(
+            &buffer,
+            notification_pattern,
+            quest_pattern,
+            quest_pattern_alt,
+            path,
+        );
+
+        for mut event in events {
+            event.origin.offset += state.position;
+            // An SPT profile may already have shown the change
+            if !profiles.first_report(&event) {
+                debug!("Quest event already read from an SPT profile: {:?}", event);
+                continue;
+            }
+            info!("Detected quest event: {:?}", event);
+            callback(event);
         }
 
-        let mut line_buffer = String::new();
-        let mut notification_context = String::new();
-        let mut in_notification = false;
+        // Only advance past complete records; anything still being written is re-read next time
+        state.position += consumed as u64;
+        state.last_modified = modified;
+        true
+    }
+
+    /// Re-read an SPT profile if it changed and report quests whose status changed.
+    /// Returns true if the profile was read.
+    async fn process_profile<F>(
+        path: &Path,
+        file_states: &mut HashMap<PathBuf, LogFileState>,
+        profiles: &mut ProfileTracker,
+        callback: &F,
+    ) -> bool
+    where
+        F: Fn(QuestEvent) + ?Sized,
+    {
+        let Ok(metadata) = fs::metadata(path).await else {
+            return false;
+        };
+        let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+        if let Some(state) = file_states.get(path) {
+            if state.last_modified == modified && state.position == metadata.len() {
+                return false;
+            }
+        }
 
-        while let Ok(bytes_read) = reader.read_line(&mut line_buffer) {
-            if bytes_read == 0 {
-                break;
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(e) => {
+                debug!("Could not read SPT profile {:?}: {}", path, e);
+                return false;
             }
+        };
 
-            // Check for notification start
-            if notification_pattern.is_match(&line_buffer) {
-                in_notification = true;
-                notification_context = line_buffer.clone();
-            } else if in_notification {
-                // Continue collecting context for multi-line notifications
-                notification_context.push_str(&line_buffer);
-
-                // Try to parse quest event from accumulated context
-                if let Some(event) = Self::parse_quest_event(
-                    &notification_context,
-                    quest_pattern,
-                    quest_pattern_alt,
-                    path,
-                ) {
-                    info!("Detected quest event: {:?}", event);
+        // A profile caught mid-write fails to parse and is read again on the next poll
+        match profiles.update(path, &content) {
+            Ok(events) => {
+                for event in events {
+                    info!("Detected SPT quest event: {:?}", event);
                     callback(event);
-                    in_notification = false;
-                    notification_context.clear();
                 }
+            }
+            Err(e) => {
+                debug!("Skipping SPT profile {:?}: {}", path, e);
+                return false;
+            }
+        }
+
+        file_states.insert(
+            path.to_path_buf(),
+            LogFileState {
+                path: path.to_path_buf(),
+                position: metadata.len(),
+                last_modified: modified,
+            },
+        );
+        true
+    }
+
+    /// Extract quest events from newly appended log bytes.
+    ///
+    /// Returns the events found, with offsets relative to the start of `buffer`, and the
+    /// number of bytes that belong to complete records.
+    /// An unterminated trailing line, or a notification whose JSON body has not been
+    /// closed yet, is left unconsumed so it can be parsed whole once the game flushes it.
+    fn extract_events(
+        buffer: &[u8],
+        notification_pattern: &Regex,
+        quest_pattern: &Regex,
+        quest_pattern_alt: &Regex,
+        log_path: &Path,
+    ) -> (Vec<QuestEvent>, usize) {
+        let mut events = Vec::new();
+        let mut consumed = 0;
+        let mut pending: Option<PendingNotification> = None;
+        let mut line_start = 0;
+
+        while let Some(newline) = buffer[line_start..].iter().position(|&b| b == b'\n') {
+            let line_end = line_start + newline + 1;
+            let line = String::from_utf8_lossy(&buffer[line_start..line_end]);
+
+            if notification_pattern.is_match(&line) {
+                // A new notification header closes whatever was pending before it
+                pending = Some(PendingNotification::new(&line, line_start));
+                consumed = line_start;
+            } else if let Some(notification) = pending.as_mut() {
+                notification.push_line(&line);
+            } else {
+                consumed = line_end;
+            }
 
-                // Limit context size to prevent memory issues
-                if notification_context.len() > 10000 {
-                    in_notification = false;
-                    notification_context.clear();
+            if let Some(notification) = pending.as_ref() {
+                if notification.is_complete() {
+                    if let Some(mut event) = Self::parse_quest_event(
+                        &notification.context,
+                        quest_pattern,
+                        quest_pattern_alt,
+                        log_path,
+                    ) {
+                        event.origin.from_log = true;
+                        event.origin.offset = notification.start as u64;
+                        event.origin.content_hash = content_hash(notification.body());
+                        events.push(event);
+                    }
+                    pending = None;
+                    consumed = line_end;
+                } else if notification.context.len() > MAX_NOTIFICATION_SIZE {
+                    // Limit context size to prevent memory issues
+                    debug!("Dropping oversized notification in {:?}", log_path);
+                    pending = None;
+                    consumed = line_end;
                 }
             }
 
-            line_buffer.clear();
+            line_start = line_end;
         }
 
-        // Update position
-        state.position = reader.stream_position().unwrap_or(file_size);
-        state.last_modified = modified;
+        (events, consumed)
     }
 
     /// Parse a quest event from log content
@@ -358,6 +672,10 @@ impl LogWatcher {
                 status,
                 timestamp: Utc::now(),
                 log_file: log_path.file_name()?.to_string_lossy().to_string(),
+                origin: EventOrigin {
+                    session: Self::session_name(log_path),
+                    ..EventOrigin::default()
+                },
             });
         }
 
@@ -372,18 +690,31 @@ impl LogWatcher {
                 status,
                 timestamp: Utc::now(),
                 log_file: log_path.file_name()?.to_string_lossy().to_string(),
+                origin: EventOrigin {
+                    session: Self::session_name(log_path),
+                    ..EventOrigin::default()
+                },
             });
         }
 
         None
     }
 
-    /// Parse status string to enum
+    /// Name of the session folder a log file lives in, empty for files directly in Logs
+    fn session_name(log_path: &Path) -> String {
+        log_path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Parse status string to enum, as logged in the text or the SPT message key
     fn parse_status(status: &str) -> Option<QuestEventStatus> {
         match status {
             "started" => Some(QuestEventStatus::Started),
-            "finished" => Some(QuestEventStatus::Finished),
-            "failed" => Some(QuestEventStatus::Failed),
+            "finished" | "success" => Some(QuestEventStatus::Finished),
+            "failed" | "fail" => Some(QuestEventStatus::Failed),
             _ => None,
         }
     }
@@ -391,7 +722,8 @@ impl LogWatcher {
 
 impl Drop for LogWatcher {
     fn drop(&mut self) {
-        *self.stop_flag.lock().unwrap() = true;
+        // The task cannot be awaited here; callers that need to wait use `stop()`
+        self.cancel.cancel();
     }
 }
 
@@ -566,6 +898,312 @@ mod tests {
         assert!(event.is_none());
     }
 
+    fn test_patterns() -> (Regex, Regex, Regex) {
+        (
+            Regex::new(r"Got notification \| ChatMessageReceived").unwrap(),
+            Regex::new(
+                r#""text"\s*:\s*"quest (started|finished|failed)"[\s\S]*?"templateId"\s*:\s*"([a-f0-9]{24})"#,
+            )
+            .unwrap(),
+            Regex::new(
+                r#""templateId"\s*:\s*"([a-f0-9]{24})[\s\S]*?"text"\s*:\s*"quest (started|finished|failed)""#,
+            )
+            .unwrap(),
+        )
+    }
+
+    const SAMPLE_NOTIFICATION: &str = "2024-01-15 10:30:45.123|Info|application|Got notification | ChatMessageReceived\n{\n  \"type\": \"new_message\",\n  \"message\": {\n    \"text\": \"quest finished\",\n    \"templateId\": \"59ca2eb686f77445a80ed049 successMessageText\"\n  }\n}\n";
+
+    const SPT_NOTIFICATION: &str = "2024-01-15 10:31:02.000|Info|application|Got notification | ChatMessageReceived\n{\n  \"type\": \"new_message\",\n  \"message\": {\n    \"type\": 12,\n    \"text\": \"\",\n    \"templateId\": \"59674cd986f7744ab26e32f2 successMessageText\"\n  }\n}\n";
+
+    #[test]
+    fn test_parse_quest_event_spt_message_key() {
+        let (_, primary, _) = test_patterns();
+        let spt = Regex::new(SPT_QUEST_PATTERN).unwrap();
+        let log_path = PathBuf::from("log_2024.01.15_10-30-45/notifications.log");
+
+        let event = LogWatcher::parse_quest_event(SPT_NOTIFICATION, &primary, &spt, &log_path)
+            .expect("Should parse SPT quest message");
+        assert_eq!(event.quest_id, "59674cd986f7744ab26e32f2");
+        assert_eq!(event.status, QuestEventStatus::Finished);
+
+        let failed = SPT_NOTIFICATION.replace("successMessageText", "failMessageText");
+        let event = LogWatcher::parse_quest_event(&failed, &primary, &spt, &log_path).unwrap();
+        assert_eq!(event.status, QuestEventStatus::Failed);
+
+        // Other messages from traders carry no quest key
+        let trade = SPT_NOTIFICATION.replace(" successMessageText", "");
+        assert!(LogWatcher::parse_quest_event(&trade, &primary, &spt, &log_path).is_none());
+    }
+
+    #[test]
+    fn test_extract_events_complete_notification() {
+        let (notification, primary, alt) = test_patterns();
+        let buffer = format!(
+            "2024-01-15 10:30:44.000|Info|application|Session start\n{}",
+            SAMPLE_NOTIFICATION
+        );
+
+        let (events, consumed) = LogWatcher::extract_events(
+            buffer.as_bytes(),
+            &notification,
+            &primary,
+            &alt,
+            Path::new("test.log"),
+        );
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].quest_id, "59ca2eb686f77445a80ed049");
+        assert_eq!(consumed, buffer.len());
+    }
+
+    #[test]
+    fn test_extract_events_keeps_unterminated_line_pending() {
+        let (notification, primary, alt) = test_patterns();
+        let buffer = "2024-01-15 10:30:44.000|Info|application|Session start\n2024-01-15 10:30:45.123|Info|app";
+
+        let (events, consumed) = LogWatcher::extract_events(
+            buffer.as_bytes(),
+            &notification,
+            &primary,
+            &alt,
+            Path::new("test.log"),
+        );
+
+        assert!(events.is_empty());
+        assert_eq!(consumed, buffer.find('\n').unwrap() + 1);
+    }
+
+    #[test]
+    fn test_extract_events_keeps_unclosed_notification_pending() {
+        let (notification, primary, alt) = test_patterns();
+        // Body cut off mid-flush, after the templateId but before the JSON is closed
+        let cut = SAMPLE_NOTIFICATION.find("  }").unwrap();
+        let buffer = &SAMPLE_NOTIFICATION[..cut];
+
+        let (events, consumed) = LogWatcher::extract_events(
+            buffer.as_bytes(),
+            &notification,
+            &primary,
+            &alt,
+            Path::new("test.log"),
+        );
+
+        assert!(events.is_empty());
+        assert_eq!(consumed, 0);
+    }
+
+    #[test]
+    fn test_extract_events_ignores_braces_in_strings() {
+        let (notification, primary, alt) = test_patterns();
+        let buffer = "Got notification | ChatMessageReceived\n{\n  \"text\": \"quest started\",\n  \"note\": \"}} \\\" {\",\n";
+
+        let (events, consumed) = LogWatcher::extract_events(
+            buffer.as_bytes(),
+            &notification,
+            &primary,
+            &alt,
+            Path::new("test.log"),
+        );
+
+        assert!(events.is_empty());
+        assert_eq!(consumed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_process_log_file_byte_by_byte_appends() {
+        use std::io::Write;
+        use std::sync::Mutex;
+
+        let (notification, primary, alt) = test_patterns();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("application.log");
+        let mut file_states = HashMap::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        let callback = Arc::new(move |event: QuestEvent| events_clone.lock().unwrap().push(event));
+
+        let content = format!(
+            "{}{}",
+            SAMPLE_NOTIFICATION,
+            SAMPLE_NOTIFICATION.replace("finished", "started")
+        );
+        let mut file = std::fs::File::create(&path).unwrap();
+
+        for byte in content.as_bytes() {
+            file.write_all(&[*byte]).unwrap();
+            file.flush().unwrap();
+            LogWatcher::process_log_file(
+                &path,
+                &mut file_states,
+                &notification,
+                &primary,
+                &alt,
+                &mut ProfileTracker::new(),
+                &callback,
+            )
+            .await;
+        }
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].quest_id, "59ca2eb686f77445a80ed049");
+        assert_eq!(events[0].status, QuestEventStatus::Finished);
+        assert_eq!(events[1].status, QuestEventStatus::Started);
+
+        // Offsets are absolute within the file so re-reads can be recognised
+        assert_eq!(events[0].origin.offset, 0);
+        assert_eq!(events[1].origin.offset, SAMPLE_NOTIFICATION.len() as u64);
+        assert_ne!(events[0].origin.content_hash, events[1].origin.content_hash);
+
+        assert_eq!(file_states[&path].position, content.len() as u64);
+    }
+
+    /// Start a watcher on `dir` that forwards events to a channel
+    async fn start_test_watcher(
+        dir: &Path,
+    ) -> (LogWatcher, tokio::sync::mpsc::UnboundedReceiver<QuestEvent>) {
+        let (tx, rx) = unbounded_channel();
+        let watcher = LogWatcher::new(dir.to_string_lossy().to_string(), move |event| {
+            let _ = tx.send(event);
+        })
+        .await
+        .unwrap();
+        (watcher, rx)
+    }
+
+    fn append(path: &Path, content: &str) {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watcher_emits_appended_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("application.log");
+        append(
+            &path,
+            "2024-01-15 10:30:44.000|Info|application|Session start\n",
+        );
+
+        let (watcher, mut rx) = start_test_watcher(dir.path()).await;
+        assert!(watcher.is_running());
+
+        append(&path, SAMPLE_NOTIFICATION);
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for quest event")
+            .unwrap();
+        assert_eq!(event.quest_id, "59ca2eb686f77445a80ed049");
+
+        watcher.stop().await;
+    }
+
+    async fn next_event(rx: &mut UnboundedReceiver<QuestEvent>) -> QuestEvent {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for quest event")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_spt_watcher_reports_each_change_once() {
+        let profile = include_str!("../tests/fixtures/spt/profile.json");
+        let dir = tempfile::tempdir().unwrap();
+        let paths = SptPaths::new(dir.path());
+        std::fs::create_dir_all(&paths.profiles).unwrap();
+        let session = paths.client_logs.join("log_2024.01.15_10-30-45");
+        std::fs::create_dir_all(&session).unwrap();
+        let client_log = session.join("log_2024.01.15_10-30-45 notifications.log");
+        append(
+            &client_log,
+            "2024-01-15 10:30:45.000|Info|notifications|Connected\n",
+        );
+        let profile_path = paths.profiles.join("6650a1b2c3d4e5f601234567.json");
+        std::fs::write(&profile_path, profile).unwrap();
+
+        let (tx, mut rx) = unbounded_channel();
+        let watcher = LogWatcher::new_spt(dir.path().to_string_lossy().to_string(), move |e| {
+            let _ = tx.send(e);
+        })
+        .await
+        .unwrap();
+
+        // The server's quest message reaches the client log first
+        append(&client_log, SPT_NOTIFICATION);
+        let event = next_event(&mut rx).await;
+        assert_eq!(event.quest_id, "59674cd986f7744ab26e32f2");
+        assert_eq!(event.status, QuestEventStatus::Finished);
+
+        // The profile saved later repeats that change; only the one it adds is reported
+        std::fs::write(
+            &profile_path,
+            profile
+                .replace(r#""status": 2,"#, r#""status": 4,"#)
+                .replace(r#""status": "AvailableForStart""#, r#""status": "Started""#),
+        )
+        .unwrap();
+        let event = next_event(&mut rx).await;
+        assert_eq!(event.quest_id, "5967530a86f77462ba22226b");
+        assert_eq!(event.status, QuestEventStatus::Started);
+
+        watcher.stop().await;
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stop_joins_watch_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("application.log");
+        append(
+            &path,
+            "2024-01-15 10:30:44.000|Info|application|Session start\n",
+        );
+
+        let (watcher, mut rx) = start_test_watcher(dir.path()).await;
+        watcher.stop().await;
+
+        // The loop has exited and dropped its callback, closing the channel
+        append(&path, SAMPLE_NOTIFICATION);
+        let closed = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("channel should close once the loop has been joined");
+        assert!(closed.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_restart_runs_a_single_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("application.log");
+        append(
+            &path,
+            "2024-01-15 10:30:44.000|Info|application|Session start\n",
+        );
+
+        let (first, _first_rx) = start_test_watcher(dir.path()).await;
+        first.stop().await;
+        let (second, mut rx) = start_test_watcher(dir.path()).await;
+
+        append(&path, SAMPLE_NOTIFICATION);
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for quest event")
+            .unwrap();
+        assert_eq!(event.status, QuestEventStatus::Finished);
+
+        // Give a stray loop time to deliver a second copy
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(rx.try_recv().is_err());
+
+        second.stop().await;
+    }
+
     #[test]
     fn test_notification_pattern() {
         let notification_pattern = Regex::new(r"Got notification \| ChatMessageReceived").unwrap();
@@ -604,6 +1242,7 @@ mod tests {
             status: QuestEventStatus::Finished,
             timestamp: chrono::Utc::now(),
             log_file: "test.log".to_string(),
+            origin: EventOrigin::default(),
         };
 
         let json = serde_json::to_string(&event).unwrap();
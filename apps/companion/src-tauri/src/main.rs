@@ -2,10 +2,24 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod eft_detector;
+mod error;
+mod event_dedup;
+mod event_journal;
+mod game_process;
 mod log_watcher;
+mod scan_scheduler;
+mod settings;
+mod spt_profile;
 mod sync_manager;
+mod sync_retry;
+mod sync_scheduler;
+#[cfg(test)]
+mod test_server;
+mod token_vault;
+mod watcher_supervisor;
 
 use log::{error, info};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::{
     menu::{Menu, MenuItem},
@@ -14,117 +28,203 @@ use tauri::{
 };
 use tokio::sync::Mutex;
 
-use crate::eft_detector::EftDetector;
-use crate::log_watcher::LogWatcher;
-use crate::sync_manager::SyncManager;
+use crate::eft_detector::{DiagnosticsReport, EftDetector, InstallCandidate, InstallInfo};
+use crate::error::CompanionError;
+use crate::event_dedup::EventDeduplicator;
+use crate::event_journal::EventJournal;
+use crate::game_process::{GameEvent, GameProcessMonitor, SystemProcesses};
+use crate::settings::{Settings, SettingsStore};
+use crate::sync_manager::{QueueOutcome, SyncManager, PROBE_INTERVAL};
+use crate::sync_scheduler::{SyncError, SyncReport, SyncScheduler, SyncSchedulerConfig};
+use crate::token_vault::{migrate_plaintext_token, TokenVault};
+use crate::watcher_supervisor::{SupervisorConfig, WatcherStatus, WatcherSupervisor};
 
 /// Application state shared across commands
 pub struct AppState {
     pub eft_detector: Arc<EftDetector>,
-    pub log_watcher: Arc<Mutex<Option<LogWatcher>>>,
+    pub watcher: Arc<Mutex<WatcherSupervisor>>,
     pub sync_manager: Arc<Mutex<SyncManager>>,
-    pub is_watching: Arc<Mutex<bool>>,
+    pub sync_scheduler: SyncScheduler,
+    pub settings: Arc<Mutex<SettingsStore>>,
+}
+
+/// Server used when the settings do not name one
+fn default_api_base() -> String {
+    // Use localhost for development, production URL for release builds
+    if cfg!(debug_assertions) {
+        "http://localhost:3000".to_string()
+    } else {
+        "https://eft-tracker.vercel.app".to_string()
+    }
+}
+
+/// Push settings into the detector and sync manager
+async fn apply_settings(state: &AppState, settings: &Settings) {
+    state
+        .eft_detector
+        .set_custom_path(settings.eft_path.clone());
+
+    let mut sync_manager = state.sync_manager.lock().await;
+    sync_manager.set_api_base(settings.api_base.clone().unwrap_or_else(default_api_base));
+}
+
+/// Get all companion settings
+#[tauri::command]
+async fn get_settings(state: State<'_, AppState>) -> Result<Settings, CompanionError> {
+    Ok(state.settings.lock().await.get().clone())
+}
+
+/// Save the settings the frontend changed, apply them and return all settings.
+/// Fields left out keep their saved value.
+#[tauri::command]
+async fn update_settings(
+    state: State<'_, AppState>,
+    changes: serde_json::Map<String, serde_json::Value>,
+) -> Result<Settings, CompanionError> {
+    let settings = {
+        let mut store = state.settings.lock().await;
+        store.merge(changes)?;
+        store.get().clone()
+    };
+    apply_settings(&state, &settings).await;
+    Ok(settings)
 }
 
 /// Get detected EFT installation path
 #[tauri::command]
-async fn get_eft_path(state: State<'_, AppState>) -> Result<Option<String>, String> {
+async fn get_eft_path(state: State<'_, AppState>) -> Result<Option<String>, CompanionError> {
     Ok(state.eft_detector.get_eft_path())
 }
 
 /// Set custom EFT path
 #[tauri::command]
-async fn set_eft_path(state: State<'_, AppState>, path: String) -> Result<bool, String> {
+async fn set_eft_path(state: State<'_, AppState>, path: String) -> Result<bool, CompanionError> {
     state.eft_detector.set_custom_path(Some(path.clone()));
-    Ok(state.eft_detector.validate_eft_path(&path))
+    let valid = state.eft_detector.validate_eft_path(&path);
+    if valid {
+        state
+            .settings
+            .lock()
+            .await
+            .update(|settings| settings.eft_path = Some(path))?;
+        // The user has told us where the game is; no need to keep searching
+        state.eft_detector.cancel_drive_scan();
+    }
+    Ok(valid)
+}
+
+/// Get the resolved install, its Logs folder and client version
+#[tauri::command]
+async fn get_install_info(
+    state: State<'_, AppState>,
+) -> Result<Option<InstallInfo>, CompanionError> {
+    Ok(state.eft_detector.get_install_info())
+}
+
+/// List every EFT install found, valid ones first, so the user can pick one
+#[tauri::command]
+async fn list_eft_installs(
+    state: State<'_, AppState>,
+) -> Result<Vec<InstallCandidate>, CompanionError> {
+    Ok(state.eft_detector.find_installs())
+}
+
+/// Report everything install detection probed, for attaching to bug reports
+#[tauri::command]
+async fn diagnose_install(state: State<'_, AppState>) -> Result<DiagnosticsReport, CompanionError> {
+    let eft_detector = state.eft_detector.clone();
+    tauri::async_runtime::spawn_blocking(move || eft_detector.diagnose())
+        .await
+        .map_err(|e| CompanionError::Internal(e.to_string()))
 }
 
 /// Start watching EFT logs
+///
+/// Fails with `InstallNotFound` when no install is known. Otherwise starts the watcher
+/// supervisor, which waits for the Logs folder if needed and restarts the watcher if
+/// it dies. Progress is reported through `watcher-state` events.
 #[tauri::command]
-async fn start_watching(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
-    let mut is_watching = state.is_watching.lock().await;
-    if *is_watching {
+async fn start_watching(app: AppHandle, state: State<'_, AppState>) -> Result<(), CompanionError> {
+    // Reading the install touches the disk; do it before holding the watcher lock
+    let eft_detector = state.eft_detector.clone();
+    let install_info =
+        tauri::async_runtime::spawn_blocking(move || eft_detector.get_install_info())
+            .await
+            .map_err(|e| CompanionError::Internal(e.to_string()))?;
+
+    let mut watcher = state.watcher.lock().await;
+    if watcher.is_active() {
         return Ok(());
     }
 
-    let eft_path = state
-        .eft_detector
-        .get_eft_path()
-        .ok_or("EFT installation not found")?;
+    // The supervisor waits for a missing Logs folder, but without an install the
+    // frontend must not think watching started
+    let Some(install_info) = install_info else {
+        return Err(CompanionError::InstallNotFound);
+    };
 
-    let logs_path = std::path::Path::new(&eft_path).join("Logs");
-    if !logs_path.exists() {
-        return Err("EFT Logs directory not found".to_string());
+    // Report the installed client version until events name their session's version,
+    // and whether the install is live or SPT
+    let game_version = install_info.version.map(|version| version.version);
+    {
+        let mut sync_manager = state.sync_manager.lock().await;
+        sync_manager.set_game_version(game_version);
+        sync_manager.set_client_mode(install_info.mode);
     }
 
+    let eft_detector = state.eft_detector.clone();
     let sync_manager = state.sync_manager.clone();
+    let sync_scheduler = state.sync_scheduler.clone();
     let app_handle = app.clone();
 
-    let watcher = LogWatcher::new(logs_path.to_string_lossy().to_string(), move |event| {
-        let sync_manager = sync_manager.clone();
-        let app_handle = app_handle.clone();
-
-        // Use tauri's async runtime to spawn the task
-        // This avoids the "no reactor running" panic when called from std::thread
-        tauri::async_runtime::spawn(async move {
-            let should_auto_sync = {
-                let mut manager = sync_manager.lock().await;
-                match manager.queue_event(event.clone()).await {
-                    Ok(should_sync) => should_sync,
-                    Err(e) => {
-                        error!("Failed to queue event: {}", e);
-                        false
-                    }
-                }
-            };
-
-            // Emit event to frontend
-            let _ = app_handle.emit("quest-event", &event);
-
-            // Auto-sync after a short delay to batch rapid events
-            if should_auto_sync {
-                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    watcher.start(
+        move || eft_detector.get_eft_path(),
+        move |event| {
+            let sync_manager = sync_manager.clone();
+            let sync_scheduler = sync_scheduler.clone();
+            let app_handle = app_handle.clone();
 
-                let mut manager = sync_manager.lock().await;
-                if manager.pending_count() > 0 {
-                    info!("Auto-syncing {} pending events", manager.pending_count());
-                    match manager.sync_pending().await {
-                        Ok(result) => {
-                            let _ = app_handle.emit("sync-complete", &result);
-                            info!("Auto-sync complete: {:?}", result);
-                        }
+            tauri::async_runtime::spawn(async move {
+                let (should_auto_sync, pending, online) = {
+                    let mut manager = sync_manager.lock().await;
+                    let should_auto_sync = match manager.queue_event(event.clone()) {
+                        Ok(QueueOutcome::Queued { auto_sync }) => auto_sync,
+                        Ok(QueueOutcome::Duplicate) => return,
                         Err(e) => {
-                            error!("Auto-sync failed: {}", e);
-                            let _ = app_handle.emit("sync-error", &e);
+                            error!("Failed to queue event: {}", e);
+                            false
                         }
-                    }
+                    };
+                    (
+                        should_auto_sync,
+                        manager.pending_count(),
+                        manager.is_online(),
+                    )
+                };
+
+                // Emit event to frontend
+                let _ = app_handle.emit("quest-event", &event);
+                if !online {
+                    refresh_tray(&app_handle).await;
                 }
-            }
-        });
-    })
-    .map_err(|e| e.to_string())?;
 
-    let mut log_watcher = state.log_watcher.lock().await;
-    *log_watcher = Some(watcher);
-    *is_watching = true;
-
-    // Update tray icon to show watching state
-    update_tray_status(&app, true);
+                // The scheduler batches rapid events into one sync
+                if should_auto_sync {
+                    sync_scheduler.event_queued(pending);
+                }
+            });
+        },
+    );
 
-    info!("Started watching EFT logs at {:?}", logs_path);
+    info!("Started watching EFT logs");
     Ok(())
 }
 
 /// Stop watching EFT logs
 #[tauri::command]
-async fn stop_watching(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
-    let mut is_watching = state.is_watching.lock().await;
-    let mut log_watcher = state.log_watcher.lock().await;
-
-    *log_watcher = None;
-    *is_watching = false;
-
-    update_tray_status(&app, false);
+async fn stop_watching(state: State<'_, AppState>) -> Result<(), CompanionError> {
+    // Waits for the watch loop to exit so a quick restart never runs two loops
+    state.watcher.lock().await.stop().await;
 
     info!("Stopped watching EFT logs");
     Ok(())
@@ -132,22 +232,34 @@ async fn stop_watching(app: AppHandle, state: State<'_, AppState>) -> Result<(),
 
 /// Get current watching status
 #[tauri::command]
-async fn is_watching(state: State<'_, AppState>) -> Result<bool, String> {
-    let is_watching = state.is_watching.lock().await;
-    Ok(*is_watching)
+async fn is_watching(state: State<'_, AppState>) -> Result<bool, CompanionError> {
+    Ok(state.watcher.lock().await.is_active())
 }
 
-/// Set companion token for syncing
+/// Get the watcher supervisor state
 #[tauri::command]
-async fn set_companion_token(state: State<'_, AppState>, token: String) -> Result<(), String> {
-    let mut sync_manager = state.sync_manager.lock().await;
-    sync_manager.set_token(token);
-    Ok(())
+async fn get_watcher_state(state: State<'_, AppState>) -> Result<WatcherStatus, CompanionError> {
+    Ok(state.watcher.lock().await.status())
+}
+
+/// Set companion token for syncing, keeping it in the token vault
+#[tauri::command]
+async fn set_companion_token(
+    state: State<'_, AppState>,
+    token: String,
+) -> Result<(), CompanionError> {
+    state.sync_manager.lock().await.set_token(token)
+}
+
+/// Forget the companion token when the account is unlinked
+#[tauri::command]
+async fn clear_companion_token(state: State<'_, AppState>) -> Result<(), CompanionError> {
+    state.sync_manager.lock().await.clear_token()
 }
 
 /// Get sync status
 #[tauri::command]
-async fn get_sync_status(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
+async fn get_sync_status(state: State<'_, AppState>) -> Result<serde_json::Value, CompanionError> {
     let sync_manager = state.sync_manager.lock().await;
     Ok(sync_manager.get_status())
 }
@@ -157,29 +269,204 @@ async fn get_sync_status(state: State<'_, AppState>) -> Result<serde_json::Value
 async fn validate_token(
     state: State<'_, AppState>,
     token: String,
-) -> Result<serde_json::Value, String> {
-    let sync_manager = state.sync_manager.lock().await;
-    sync_manager.validate_token(&token).await
+) -> Result<serde_json::Value, CompanionError> {
+    // Validate without holding the manager, so status reads are not blocked
+    let validator = state.sync_manager.lock().await.token_validator();
+    validator.validate(&token).await
 }
 
-/// Manually trigger sync of pending events
+/// Validate the stored token, so it never has to be handed to the frontend.
+/// Returns `None` when no account is linked, and an error if the stored token cannot be read.
 #[tauri::command]
-async fn sync_now(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
-    let mut sync_manager = state.sync_manager.lock().await;
-    sync_manager.sync_pending().await
+async fn validate_stored_token(
+    state: State<'_, AppState>,
+) -> Result<Option<serde_json::Value>, CompanionError> {
+    let (validator, token) = {
+        let sync_manager = state.sync_manager.lock().await;
+        (
+            sync_manager.token_validator(),
+            sync_manager.require_token().cloned(),
+        )
+    };
+    match token {
+        Ok(token) => validator.validate(&token).await.map(Some),
+        Err(CompanionError::NoToken) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Manually trigger sync of pending events; a failure carries what was synced before it
+#[tauri::command]
+async fn sync_now(state: State<'_, AppState>) -> Result<serde_json::Value, SyncError> {
+    state.sync_scheduler.sync_now().await
+}
+
+/// Stop the sync in progress, leaving the rest of the queue for later
+#[tauri::command]
+async fn cancel_sync(state: State<'_, AppState>) -> Result<(), CompanionError> {
+    state.sync_scheduler.cancel();
+    Ok(())
 }
 
-fn update_tray_status(app: &AppHandle, watching: bool) {
+/// Show sync progress and the outcome of background syncs in the frontend and the tray
+fn report_sync(app: &AppHandle, report: SyncReport) {
+    match report {
+        SyncReport::Progress(progress) => {
+            let _ = app.emit("sync-progress", &progress);
+        }
+        SyncReport::Completed {
+            result: Ok(result), ..
+        } => {
+            let _ = app.emit("sync-complete", &result);
+        }
+        SyncReport::Completed { result: Err(e), .. } => {
+            let _ = app.emit("sync-error", &e);
+        }
+        SyncReport::ConnectivityChanged => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                report_connectivity(&app).await;
+            });
+        }
+    }
+}
+
+/// Probe the server while offline and flush the queue once it can be reached again
+fn monitor_connectivity(app: &AppHandle) {
+    let app_handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let state: State<AppState> = app_handle.state();
+        loop {
+            tokio::time::sleep(PROBE_INTERVAL).await;
+
+            // Probe without holding the manager, so status reads are not blocked
+            let probe = {
+                let manager = state.sync_manager.lock().await;
+                if manager.is_online() {
+                    continue;
+                }
+                manager.health_probe()
+            };
+            let reachable = probe.run().await;
+
+            if state.sync_manager.lock().await.record_probe(reachable) {
+                report_connectivity(&app_handle).await;
+                state.sync_scheduler.reconnected();
+            }
+        }
+    });
+}
+
+/// Tell the tray and the frontend that the server became reachable or unreachable
+async fn report_connectivity(app: &AppHandle) {
+    refresh_tray(app).await;
+    let state: State<AppState> = app.state();
+    let status = state.sync_manager.lock().await.get_status();
+    let _ = app.emit("sync-status", &status);
+}
+
+/// Start and stop watching as the game and launcher start and exit
+///
+/// Only a watcher started here is stopped again, so watching the user started
+/// by hand keeps running after the game closes.
+fn follow_game_process(app: &AppHandle) {
+    let mut events = game_process::spawn(GameProcessMonitor::new(Box::new(
+        SystemProcesses::default(),
+    )));
+    let app_handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let mut started_by_game = false;
+
+        while let Some(event) = events.recv().await {
+            let state: State<AppState> = app_handle.state();
+            match event {
+                GameEvent::GameStarted { install } => {
+                    if install.is_some() {
+                        set_running_install(&state, install).await;
+                    }
+                }
+                GameEvent::SessionStarted => {
+                    let auto_watch = state.settings.lock().await.get().auto_watch;
+                    if auto_watch && !state.watcher.lock().await.is_active() {
+                        match start_watching(app_handle.clone(), state).await {
+                            Ok(()) => started_by_game = true,
+                            Err(e) => error!("Failed to start watching: {}", e),
+                        }
+                    }
+                }
+                GameEvent::GameExited => {
+                    set_running_install(&state, None).await;
+
+                    // Give the watcher time to read the last lines the game wrote
+                    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+
+                    state.sync_scheduler.request("Sync after game exit");
+                }
+                GameEvent::SessionEnded => {
+                    if std::mem::take(&mut started_by_game) {
+                        if let Err(e) = stop_watching(state).await {
+                            error!("Failed to stop watching: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Tell the detector which install is running; resolving touches the disk and
+/// registry, so it runs on a blocking thread
+async fn set_running_install(state: &AppState, install: Option<PathBuf>) {
+    let detector = state.eft_detector.clone();
+    if let Err(e) =
+        tauri::async_runtime::spawn_blocking(move || detector.set_running_install(install)).await
+    {
+        error!("Failed to update the running install: {}", e);
+    }
+}
+
+/// Show the watcher state in the tray tooltip, and the queue while offline
+fn update_tray_status(app: &AppHandle, status: &WatcherStatus, offline_queued: Option<usize>) {
     if let Some(tray) = app.tray_by_id("main-tray") {
-        let tooltip = if watching {
-            "EFT Tracker Companion - Watching"
-        } else {
-            "EFT Tracker Companion - Idle"
-        };
+        let mut tooltip = format!("EFT Tracker Companion - {}", status.state.label());
+        if let Some(queued) = offline_queued {
+            tooltip.push_str(&format!(" · Offline — {} queued", queued));
+        }
         let _ = tray.set_tooltip(Some(tooltip));
     }
 }
 
+/// Update the tray tooltip from the current watcher and sync state
+async fn refresh_tray(app: &AppHandle) {
+    let state: State<AppState> = app.state();
+    let status = state.watcher.lock().await.status();
+    update_tray_status(app, &status, offline_queued(&state).await);
+}
+
+/// Number of queued events if the server cannot be reached
+async fn offline_queued(state: &AppState) -> Option<usize> {
+    let manager = state.sync_manager.lock().await;
+    (!manager.is_online()).then(|| manager.pending_count())
+}
+
+/// Forward watcher state changes to the tray tooltip and the frontend
+fn forward_watcher_state(app: &AppHandle) {
+    let state: State<AppState> = app.state();
+    let mut rx = state.watcher.blocking_lock().subscribe();
+    let app_handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        while rx.changed().await.is_ok() {
+            let status = rx.borrow_and_update().clone();
+            let state: State<AppState> = app_handle.state();
+            update_tray_status(&app_handle, &status, offline_queued(&state).await);
+            let _ = app_handle.emit("watcher-state", &status);
+        }
+    });
+}
+
 fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     let show_item = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
     let start_item = MenuItem::with_id(app, "start", "Start Watching", true, None::<&str>)?;
@@ -226,7 +513,7 @@ fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
                 let app_handle = app.clone();
                 tauri::async_runtime::spawn(async move {
                     let state: State<AppState> = app_handle.state();
-                    if let Err(e) = stop_watching(app_handle.clone(), state).await {
+                    if let Err(e) = stop_watching(state).await {
                         error!("Failed to stop watching: {}", e);
                     }
                 });
@@ -245,19 +532,17 @@ fn main() {
     env_logger::init();
 
     let eft_detector = Arc::new(EftDetector::new());
-    // Use localhost for development, production URL for release builds
-    let api_base = if cfg!(debug_assertions) {
-        "http://localhost:3000".to_string()
-    } else {
-        "https://eft-tracker.vercel.app".to_string()
-    };
-    let sync_manager = Arc::new(Mutex::new(SyncManager::new(api_base)));
+    let sync_manager = Arc::new(Mutex::new(SyncManager::new(default_api_base())));
+    let (sync_scheduler, sync_actor) = SyncScheduler::new(SyncSchedulerConfig::default());
 
     let app_state = AppState {
         eft_detector,
-        log_watcher: Arc::new(Mutex::new(None)),
+        watcher: Arc::new(Mutex::new(WatcherSupervisor::new(
+            SupervisorConfig::default(),
+        ))),
         sync_manager,
-        is_watching: Arc::new(Mutex::new(false)),
+        sync_scheduler,
+        settings: Arc::new(Mutex::new(SettingsStore::in_memory())),
     };
 
     tauri::Builder::default()
@@ -271,8 +556,82 @@ fn main() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
         .manage(app_state)
-        .setup(|app| {
+        .setup(move |app| {
             setup_tray(app.handle())?;
+            forward_watcher_state(app.handle());
+
+            let app_data_dir = app.path().app_data_dir()?;
+            let state: State<AppState> = app.state();
+
+            // Load settings, importing the old frontend store on first run
+            let settings_store = SettingsStore::load(
+                app_data_dir.join("companion_settings.json"),
+                &app_data_dir.join("settings.json"),
+            );
+
+            let settings = settings_store.get().clone();
+            *state.settings.blocking_lock() = settings_store;
+            tauri::async_runtime::block_on(apply_settings(&state, &settings));
+
+            // Restore the seen-event set so re-read log lines are not queued again
+            let seen_events_path = app_data_dir.join("seen_events.json");
+            state
+                .sync_manager
+                .blocking_lock()
+                .set_deduplicator(EventDeduplicator::load(seen_events_path));
+
+            // Restore events that were queued but not synced before the last exit
+            match EventJournal::open(app_data_dir.join("event_queue.journal")) {
+                Ok((journal, recovered)) => state
+                    .sync_manager
+                    .blocking_lock()
+                    .set_journal(journal, recovered),
+                Err(e) => error!(
+                    "Failed to open event journal, queue will not persist: {}",
+                    e
+                ),
+            }
+
+            // Keep the token in the OS credential store, moving it out of old settings.
+            // The store can take seconds to answer at login, so it is opened off the main
+            // thread; the sync manager stays locked until then, so nothing reads the
+            // token or syncs without it
+            let mut sync_manager = state.sync_manager.clone().blocking_lock_owned();
+            let settings_store = state.settings.clone();
+            let vault_dir = app_data_dir.clone();
+            tauri::async_runtime::spawn(async move {
+                let opened =
+                    tauri::async_runtime::spawn_blocking(move || TokenVault::open(&vault_dir))
+                        .await;
+                match opened {
+                    Ok(mut token_vault) => {
+                        migrate_plaintext_token(
+                            &mut *settings_store.lock().await,
+                            &mut token_vault,
+                        );
+                        sync_manager.set_vault(token_vault);
+                    }
+                    Err(e) => error!("Failed to open the token vault: {}", e),
+                }
+            });
+
+            // Run every sync from one task
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(
+                sync_actor.run(state.sync_manager.clone(), move |report| {
+                    report_sync(&app_handle, report)
+                }),
+            );
+
+            // Look for installs on every volume, reusing recent results when possible
+            let drive_scan_cache = app_data_dir.join("drive_scan.json");
+            state.eft_detector.start_drive_scan(drive_scan_cache);
+
+            // Watch while the game or launcher is running
+            follow_game_process(app.handle());
+
+            // Flush the queue when the server can be reached again
+            monitor_connectivity(app.handle());
 
             // Hide window on close instead of exiting
             let window = app.get_webview_window("main").unwrap();
@@ -303,15 +662,24 @@ fn main() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            get_settings,
+            update_settings,
             get_eft_path,
             set_eft_path,
+            list_eft_installs,
+            get_install_info,
+            diagnose_install,
             start_watching,
             stop_watching,
             is_watching,
+            get_watcher_state,
             set_companion_token,
+            clear_companion_token,
             get_sync_status,
             validate_token,
+            validate_stored_token,
             sync_now,
+            cancel_sync,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
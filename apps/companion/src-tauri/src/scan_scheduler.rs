@@ -0,0 +1,515 @@
+//! Log Scan Scheduler
+//!
+//! Decides when, and which, log files the watcher reads:
+//! 1. Filesystem events are coalesced per path and debounced
+//! 2. Only the active session's log files are polled
+//! 3. Polling backs off while the logs are idle (game not running)
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs;
+
+/// Quiet period after the last event for a path before it is read
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Longest a path is held back while events keep arriving for it
+const MAX_EVENT_DELAY: Duration = Duration::from_secs(1);
+
+/// Poll interval while the game is writing logs
+const ACTIVE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Poll interval ceiling once the logs have gone idle
+const IDLE_POLL_INTERVAL_MAX: Duration = Duration::from_secs(30);
+
+/// How long the logs must be idle before polling starts backing off
+const IDLE_GRACE: Duration = Duration::from_secs(30);
+
+/// How often to look for a newer session folder
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Paths with pending filesystem events
+struct PendingPath {
+    first_seen: Instant,
+    last_seen: Instant,
+}
+
+/// Work that is due now
+#[derive(Debug, Default, PartialEq)]
+pub struct ScanBatch {
+    /// Log files to read
+    pub files: Vec<PathBuf>,
+    /// Whether this batch includes the periodic poll of the active session
+    pub polled: bool,
+    /// Whether the active session should be re-discovered first
+    pub refresh_session: bool,
+}
+
+pub struct ScanScheduler {
+    logs_dir: PathBuf,
+    active_session: Option<PathBuf>,
+    session_files: Vec<PathBuf>,
+    pending: HashMap<PathBuf, PendingPath>,
+    session_refresh_requested: bool,
+    poll_interval: Duration,
+    next_poll: Instant,
+    next_session_check: Instant,
+    last_activity: Instant,
+}
+
+impl ScanScheduler {
+    pub fn new(logs_dir: PathBuf, now: Instant) -> Self {
+        Self {
+            logs_dir,
+            active_session: None,
+            session_files: Vec::new(),
+            pending: HashMap::new(),
+            session_refresh_requested: false,
+            poll_interval: ACTIVE_POLL_INTERVAL,
+            next_poll: now + ACTIVE_POLL_INTERVAL,
+            next_session_check: now + SESSION_CHECK_INTERVAL,
+            last_activity: now,
+        }
+    }
+
+    /// The folder whose log files are being polled
+    pub fn active_session(&self) -> Option<&Path> {
+        self.active_session.as_deref()
+    }
+
+    /// Current poll interval for the active session
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// Replace the active session after discovery.
+    /// Returns files that were not known before, so they can be read right away.
+    pub fn set_session(&mut self, session: Option<PathBuf>, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        let new_files = files
+            .iter()
+            .filter(|f| !self.session_files.contains(f))
+            .cloned()
+            .collect();
+        self.active_session = session;
+        self.session_files = files;
+        new_files
+    }
+
+    /// Record a filesystem event for a path
+    pub fn record_event(&mut self, path: PathBuf, now: Instant) {
+        let is_log = path.extension().map(|e| e == "log").unwrap_or(false);
+        let in_active_session = self.active_session.as_deref() == path.parent();
+
+        if is_log && in_active_session {
+            let pending = self.pending.entry(path).or_insert(PendingPath {
+                first_seen: now,
+                last_seen: now,
+            });
+            pending.last_seen = now;
+        } else if path.starts_with(&self.logs_dir) {
+            // A new session folder or log file outside the active session
+            self.session_refresh_requested = true;
+            self.pending
+                .entry(self.logs_dir.clone())
+                .or_insert(PendingPath {
+                    first_seen: now,
+                    last_seen: now,
+                });
+        }
+
+        self.mark_active(now);
+    }
+
+    /// Record the outcome of a poll; polls that find nothing new let the interval back off
+    pub fn record_poll(&mut self, grew: bool, now: Instant) {
+        if grew {
+            self.mark_active(now);
+        } else if now.duration_since(self.last_activity) >= IDLE_GRACE {
+            self.poll_interval = (self.poll_interval * 2).min(IDLE_POLL_INTERVAL_MAX);
+            self.next_poll = now + self.poll_interval;
+        }
+    }
+
+    /// When the next batch is due
+    pub fn next_deadline(&self) -> Instant {
+        let mut deadline = self.next_poll.min(self.next_session_check);
+        for pending in self.pending.values() {
+            deadline = deadline.min(Self::pending_deadline(pending));
+        }
+        deadline
+    }
+
+    /// Take everything that is due at `now`
+    pub fn take_due(&mut self, now: Instant) -> ScanBatch {
+        let mut batch = ScanBatch::default();
+
+        let due: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now >= Self::pending_deadline(pending))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in due {
+            self.pending.remove(&path);
+            if path != self.logs_dir {
+                batch.files.push(path);
+            } else if self.session_refresh_requested {
+                batch.refresh_session = true;
+                self.session_refresh_requested = false;
+            }
+        }
+
+        if now >= self.next_poll {
+            for file in &self.session_files {
+                if !batch.files.contains(file) {
+                    batch.files.push(file.clone());
+                }
+            }
+            batch.polled = true;
+            self.next_poll = now + self.poll_interval;
+        }
+
+        if now >= self.next_session_check {
+            batch.refresh_session = true;
+            self.next_session_check = now + SESSION_CHECK_INTERVAL.max(self.poll_interval);
+        }
+
+        batch
+    }
+
+    fn pending_deadline(pending: &PendingPath) -> Instant {
+        (pending.last_seen + DEBOUNCE).min(pending.first_seen + MAX_EVENT_DELAY)
+    }
+
+    fn mark_active(&mut self, now: Instant) {
+        self.last_activity = now;
+        if self.poll_interval != ACTIVE_POLL_INTERVAL {
+            self.poll_interval = ACTIVE_POLL_INTERVAL;
+            self.next_poll = self.next_poll.min(now + ACTIVE_POLL_INTERVAL);
+        }
+    }
+}
+
+/// Find the active session folder and its log files.
+///
+/// EFT writes each game session to its own `log_*` folder inside Logs; the newest one is
+/// active. Log files placed directly in Logs are used when there are no session folders.
+pub async fn discover_session(logs_dir: &Path) -> std::io::Result<(Option<PathBuf>, Vec<PathBuf>)> {
+    let mut newest: Option<(PathBuf, std::time::SystemTime)> = None;
+    let mut entries = fs::read_dir(logs_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_dir() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+        match &newest {
+            Some((_, newest_time)) if modified <= *newest_time => {}
+            _ => newest = Some((entry.path(), modified)),
+        }
+    }
+
+    let session = newest
+        .map(|(path, _)| path)
+        .unwrap_or_else(|| logs_dir.to_path_buf());
+    let files = list_log_files(&session).await?;
+    Ok((Some(session), files))
+}
+
+/// List `*.log` files directly inside a folder
+async fn list_log_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().map(|e| e == "log").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler_with_session(now: Instant) -> ScanScheduler {
+        let mut scheduler = ScanScheduler::new(PathBuf::from("/logs"), now);
+        scheduler.set_session(
+            Some(PathBuf::from("/logs/log_1")),
+            vec![
+                PathBuf::from("/logs/log_1/application.log"),
+                PathBuf::from("/logs/log_1/backend.log"),
+            ],
+        );
+        scheduler
+    }
+
+    #[test]
+    fn test_burst_of_events_is_coalesced() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with_session(start);
+        let path = PathBuf::from("/logs/log_1/application.log");
+
+        for i in 0..10 {
+            scheduler.record_event(path.clone(), start + Duration::from_millis(i * 10));
+        }
+
+        // Still inside the debounce window
+        let batch = scheduler.take_due(start + Duration::from_millis(200));
+        assert!(batch.files.is_empty());
+
+        let batch = scheduler.take_due(start + Duration::from_millis(400));
+        assert_eq!(batch.files, vec![path]);
+        assert!(!batch.polled);
+    }
+
+    #[test]
+    fn test_continuous_events_are_not_held_forever() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with_session(start);
+        let path = PathBuf::from("/logs/log_1/application.log");
+
+        for i in 0..12 {
+            scheduler.record_event(path.clone(), start + Duration::from_millis(i * 100));
+        }
+
+        assert_eq!(scheduler.next_deadline(), start + MAX_EVENT_DELAY);
+    }
+
+    #[test]
+    fn test_poll_only_reads_active_session_files() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with_session(start);
+
+        let batch = scheduler.take_due(start + ACTIVE_POLL_INTERVAL);
+        assert!(batch.polled);
+        assert_eq!(batch.files.len(), 2);
+        assert!(batch.files.iter().all(|f| f.starts_with("/logs/log_1")));
+    }
+
+    #[test]
+    fn test_events_outside_session_request_refresh() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with_session(start);
+
+        scheduler.record_event(PathBuf::from("/logs/log_2"), start);
+        let batch = scheduler.take_due(start + DEBOUNCE);
+
+        assert!(batch.refresh_session);
+        assert!(batch.files.is_empty());
+    }
+
+    #[test]
+    fn test_idle_polling_backs_off_and_recovers() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with_session(start);
+
+        let mut now = start + IDLE_GRACE;
+        for _ in 0..10 {
+            scheduler.record_poll(false, now);
+            now += scheduler.poll_interval();
+        }
+        assert_eq!(scheduler.poll_interval(), IDLE_POLL_INTERVAL_MAX);
+
+        scheduler.record_event(PathBuf::from("/logs/log_1/application.log"), now);
+        assert_eq!(scheduler.poll_interval(), ACTIVE_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_no_back_off_during_grace_period() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with_session(start);
+
+        scheduler.record_poll(false, start + Duration::from_secs(5));
+        assert_eq!(scheduler.poll_interval(), ACTIVE_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_set_session_returns_new_files() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_with_session(start);
+
+        let new_files = scheduler.set_session(
+            Some(PathBuf::from("/logs/log_1")),
+            vec![
+                PathBuf::from("/logs/log_1/application.log"),
+                PathBuf::from("/logs/log_1/backend.log"),
+                PathBuf::from("/logs/log_1/notifications.log"),
+            ],
+        );
+
+        assert_eq!(
+            new_files,
+            vec![PathBuf::from("/logs/log_1/notifications.log")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discover_session_picks_newest_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_session = dir.path().join("log_2024.01.14_09-00-00_0.14.0.0.12345");
+        std::fs::create_dir(&old_session).unwrap();
+        std::fs::write(old_session.join("application.log"), "").unwrap();
+
+        std::thread::sleep(Duration::from_millis(20));
+        let new_session = dir.path().join("log_2024.01.15_10-30-45_0.14.0.0.12345");
+        std::fs::create_dir(&new_session).unwrap();
+        std::fs::write(new_session.join("application.log"), "").unwrap();
+        std::fs::write(new_session.join("notes.txt"), "").unwrap();
+
+        let (session, files) = discover_session(dir.path()).await.unwrap();
+
+        assert_eq!(session, Some(new_session.clone()));
+        assert_eq!(files, vec![new_session.join("application.log")]);
+    }
+
+    #[tokio::test]
+    async fn test_discover_session_falls_back_to_logs_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("application.log"), "").unwrap();
+
+        let (session, files) = discover_session(dir.path()).await.unwrap();
+
+        assert_eq!(session, Some(dir.path().to_path_buf()));
+        assert_eq!(files, vec![dir.path().join("application.log")]);
+    }
+
+    /// Filesystem calls made by one scan strategy, counted where they are made
+    #[derive(Debug, Default)]
+    struct FsCalls {
+        folder_listings: usize,
+        session_discoveries: usize,
+        opens: usize,
+        stats: usize,
+        /// CPU time of the process, user and system, spent on the strategy
+        cpu: Duration,
+        elapsed: Duration,
+    }
+
+    impl FsCalls {
+        fn syscalls(&self) -> usize {
+            self.folder_listings + self.opens + self.stats
+        }
+
+        /// Count the calls of one `discover_session`: it lists the Logs folder,
+        /// stats each entry and lists the session it picks
+        fn discovered_session(&mut self, logs_entries: usize) {
+            self.session_discoveries += 1;
+            self.folder_listings += 2;
+            self.stats += logs_entries;
+        }
+    }
+
+    /// User plus system CPU time used by this process so far
+    #[cfg(unix)]
+    fn process_cpu_time() -> Duration {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) }, 0);
+        let time = |t: libc::timeval| {
+            Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+        };
+        time(usage.ru_utime) + time(usage.ru_stime)
+    }
+
+    /// CPU time is only measured on Unix; elsewhere the wall time is all there is
+    #[cfg(not(unix))]
+    fn process_cpu_time() -> Duration {
+        Duration::ZERO
+    }
+
+    /// Compares filesystem work for ten idle minutes against the previous strategy of
+    /// listing the Logs folder and opening every `*.log` file once per second. Both
+    /// strategies run against a real folder; the calls and the CPU and wall time
+    /// it prints are what this run did, not estimates.
+    ///
+    /// Run with `cargo test bench_idle_scan_overhead -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_idle_scan_overhead() {
+        const SESSIONS: usize = 40;
+        const FILES_PER_SESSION: usize = 6;
+        const SIMULATED: Duration = Duration::from_secs(600);
+
+        let dir = tempfile::tempdir().unwrap();
+        for s in 0..SESSIONS {
+            let session = dir.path().join(format!("log_{:02}", s));
+            std::fs::create_dir(&session).unwrap();
+            for f in 0..FILES_PER_SESSION {
+                std::fs::write(session.join(format!("{}.log", f)), "line\n").unwrap();
+            }
+            // Legacy layout: files directly in Logs were all rescanned every second
+            std::fs::write(dir.path().join(format!("{:02}.log", s)), "line\n").unwrap();
+        }
+        let logs_entries = std::fs::read_dir(dir.path()).unwrap().count();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        // Before: list the Logs folder, then open and fstat every log file, every second
+        let mut before = FsCalls::default();
+        let started = Instant::now();
+        let cpu_started = process_cpu_time();
+        for _ in 0..SIMULATED.as_secs() {
+            before.folder_listings += 1;
+            for entry in std::fs::read_dir(dir.path()).unwrap().flatten() {
+                let path = entry.path();
+                if path.extension().map(|e| e == "log").unwrap_or(false) {
+                    let file = std::fs::File::open(&path).unwrap();
+                    before.opens += 1;
+                    let _ = file.metadata().unwrap();
+                    before.stats += 1;
+                }
+            }
+        }
+        before.cpu = process_cpu_time() - cpu_started;
+        before.elapsed = started.elapsed();
+
+        // After: discover the session the way the watcher does, then stat only its
+        // files on the backed-off schedule
+        let mut after = FsCalls::default();
+        let started = Instant::now();
+        let cpu_started = process_cpu_time();
+        let (session, files) = runtime.block_on(discover_session(dir.path())).unwrap();
+        after.discovered_session(logs_entries);
+        let start = Instant::now();
+        let mut scheduler = ScanScheduler::new(dir.path().to_path_buf(), start);
+        scheduler.set_session(session, files);
+        let mut now = start;
+        while now < start + SIMULATED {
+            now = scheduler.next_deadline();
+            let batch = scheduler.take_due(now);
+            if batch.refresh_session {
+                let (session, files) = runtime.block_on(discover_session(dir.path())).unwrap();
+                after.discovered_session(logs_entries);
+                scheduler.set_session(session, files);
+            }
+            for path in &batch.files {
+                let _ = std::fs::metadata(path).unwrap();
+                after.stats += 1;
+            }
+            if batch.polled {
+                scheduler.record_poll(false, now);
+            }
+        }
+        after.cpu = process_cpu_time() - cpu_started;
+        after.elapsed = started.elapsed();
+
+        println!("idle scan over {}s:", SIMULATED.as_secs());
+        for (name, calls) in [("before", &before), ("after", &after)] {
+            println!(
+                "  {:<6}  {} syscalls  {:?} CPU  {:?} wall  {:?}",
+                name,
+                calls.syscalls(),
+                calls.cpu,
+                calls.elapsed,
+                calls
+            );
+        }
+        assert!(after.syscalls() < before.syscalls() / 10);
+    }
+}
@@ -0,0 +1,471 @@
+//! Companion Settings
+//!
+//! Typed settings owned by the backend and persisted as versioned JSON:
+//! 1. Loaded once at start-up and applied to the detector and sync manager
+//! 2. Saved atomically and durably (synced temp file, then rename) on every change
+//! 3. The frontend sends only the fields it changed, so a stale copy cannot undo
+//!    a change the backend made, such as a new install folder
+//! 4. Older files are upgraded step by step through `MIGRATIONS`, including
+//!    the plugin-store file the frontend used to write
+//! 5. The companion token is not a setting; a plaintext copy in an older file is
+//!    held until it has been moved into the token vault. An unreadable file kept
+//!    aside for inspection has anything that looks like a token removed
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::CompanionError;
+use crate::event_journal::sync_dir;
+
+/// Current version of the settings file format
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Upgrades from each version to the next; entry `n` turns version `n` into `n + 1`
+const MIGRATIONS: &[fn(Value) -> Value] = &[migrate_v0_plugin_store];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    /// Install folder chosen by the user, overriding detection
+    pub eft_path: Option<String>,
+    pub auto_start: bool,
+    pub auto_watch: bool,
+    pub notifications: bool,
+    pub notification_sound: bool,
+    /// Server to sync with; the build default is used when unset
+    pub api_base: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            eft_path: None,
+            auto_start: false,
+            auto_watch: true,
+            notifications: true,
+            notification_sound: true,
+            api_base: None,
+        }
+    }
+}
+
+/// On-disk layout: the settings plus their format version
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsFile {
+    version: u32,
+    settings: Settings,
+}
+
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    settings: Settings,
+    /// Token found in the loaded file, which is no longer written back
+    plaintext_token: Option<String>,
+    /// Files still holding a plaintext token, including the imported plugin-store file
+    plaintext_files: Vec<PathBuf>,
+}
+
+impl SettingsStore {
+    /// Settings that are never written to disk
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            settings: Settings::default(),
+            plaintext_token: None,
+            plaintext_files: Vec::new(),
+        }
+    }
+
+    /// Load settings from `path`, importing `legacy_path` if there is no settings file yet.
+    /// Unreadable files are moved aside and defaults are used.
+    pub fn load(path: PathBuf, legacy_path: &Path) -> Self {
+        let mut store = Self {
+            path: Some(path.clone()),
+            settings: Settings::default(),
+            plaintext_token: None,
+            plaintext_files: Vec::new(),
+        };
+
+        if let Ok(content) = fs::read_to_string(&path) {
+            match parse(&content) {
+                Ok(settings) => {
+                    store.settings = settings;
+                    store.plaintext_token = plaintext_token(&content);
+                }
+                Err(e) => {
+                    warn!("Ignoring unreadable settings {:?}: {}", path, e);
+                    let _ = fs::rename(&path, path.with_extension("json.corrupt"));
+                }
+            }
+            scrub_tokens(&path.with_extension("json.corrupt"));
+        } else if let Ok(content) = fs::read_to_string(legacy_path) {
+            match serde_json::from_str::<Value>(&content)
+                .map_err(CompanionError::from)
+                .and_then(|value| migrate(value, 0))
+            {
+                Ok(settings) => {
+                    info!("Imported settings from {:?}", legacy_path);
+                    store.settings = settings;
+                    store.plaintext_token = plaintext_token(&content);
+                    if let Err(e) = store.save() {
+                        warn!("Failed to save imported settings: {}", e);
+                    }
+                }
+                Err(e) => warn!("Could not import settings {:?}: {}", legacy_path, e),
+            }
+        }
+
+        store.plaintext_files = [path.as_path(), legacy_path]
+            .into_iter()
+            .filter(|path| {
+                fs::read_to_string(path)
+                    .ok()
+                    .and_then(|content| plaintext_token(&content))
+                    .is_some()
+            })
+            .map(Path::to_path_buf)
+            .collect();
+        store
+    }
+
+    pub fn get(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Token left in plaintext by an older version, until it is forgotten
+    pub fn plaintext_token(&self) -> Option<&str> {
+        self.plaintext_token.as_deref()
+    }
+
+    /// Whether any settings file on disk still holds a plaintext token
+    pub fn has_plaintext_files(&self) -> bool {
+        !self.plaintext_files.is_empty()
+    }
+
+    /// Drop the plaintext token and remove it from every file that holds it
+    pub fn forget_plaintext_token(&mut self) -> Result<(), CompanionError> {
+        self.plaintext_token = None;
+        for path in std::mem::take(&mut self.plaintext_files) {
+            let scrubbed = if Some(&path) == self.path.as_ref() {
+                self.save()
+            } else {
+                remove_plaintext_token(&path)
+            };
+            scrubbed.map_err(|e| CompanionError::Settings(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Apply the fields present in `changes` and save; fields left out keep
+    /// their current value
+    pub fn merge(&mut self, changes: Map<String, Value>) -> Result<(), CompanionError> {
+        let mut value = serde_json::to_value(&self.settings)
+            .map_err(|e| CompanionError::Settings(e.to_string()))?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.extend(changes);
+        }
+        let settings =
+            serde_json::from_value(value).map_err(|e| CompanionError::Settings(e.to_string()))?;
+        self.replace(settings)
+    }
+
+    /// Replace all settings and save them
+    fn replace(&mut self, settings: Settings) -> Result<(), CompanionError> {
+        self.settings = settings;
+        self.save()
+            .map_err(|e| CompanionError::Settings(e.to_string()))
+    }
+
+    /// Change some settings and save them
+    pub fn update(&mut self, change: impl FnOnce(&mut Settings)) -> Result<(), CompanionError> {
+        let mut settings = self.settings.clone();
+        change(&mut settings);
+        self.replace(settings)
+    }
+
+    /// Write settings atomically and durably
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let file = SettingsFile {
+            version: SETTINGS_VERSION,
+            settings: self.settings.clone(),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_synced(path, &serde_json::to_vec_pretty(&file)?)
+    }
+}
+
+/// Write a file through a synced temp file and rename, then sync the rename
+fn write_synced(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(content)?;
+    tmp.sync_all()?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, path)?;
+    sync_dir(path);
+    Ok(())
+}
+
+/// Parse a settings file of any known version
+fn parse(content: &str) -> Result<Settings, CompanionError> {
+    let value: Value = serde_json::from_str(content)?;
+    let version = value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| CompanionError::Settings("missing version".to_string()))?
+        as u32;
+    migrate(value, version)
+}
+
+/// Token written by versions that kept it in settings; any format has it at
+/// `settings.companionToken`
+fn plaintext_token(content: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(content).ok()?;
+    value
+        .pointer("/settings/companionToken")
+        .and_then(|token| token.as_str())
+        .map(str::to_string)
+}
+
+/// Rewrite a settings file of any format without its token, atomically
+fn remove_plaintext_token(path: &Path) -> std::io::Result<()> {
+    let mut value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if let Some(settings) = value.get_mut("settings").and_then(|s| s.as_object_mut()) {
+        settings.remove("companionToken");
+    }
+
+    write_synced(path, &serde_json::to_vec_pretty(&value)?)
+}
+
+/// Rewrite a file with every companion token blanked out, if it has any.
+/// Works on files too damaged to parse, where the token cannot be located by key.
+fn scrub_tokens(path: &Path) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    let redacted = redact_tokens(&content);
+    if redacted != content {
+        if let Err(e) = write_synced(path, redacted.as_bytes()) {
+            warn!(
+                "Could not remove the companion token from {:?}: {}",
+                path, e
+            );
+        }
+    }
+}
+
+/// Replace what follows each `cmp_` prefix, which is how companion tokens start
+fn redact_tokens(content: &str) -> String {
+    let mut redacted = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("cmp_") {
+        let token = &rest[start + "cmp_".len()..];
+        let end = token
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(token.len());
+        redacted.push_str(&rest[..start]);
+        redacted.push_str("cmp_[removed]");
+        rest = &token[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+/// Upgrade a settings document from `version` to the current format
+fn migrate(mut value: Value, version: u32) -> Result<Settings, CompanionError> {
+    if version > SETTINGS_VERSION {
+        return Err(CompanionError::Settings(format!(
+            "settings version {} is newer than supported version {}",
+            version, SETTINGS_VERSION
+        )));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        value = migration(value);
+    }
+
+    let file: SettingsFile = serde_json::from_value(value)?;
+    Ok(file.settings)
+}
+
+/// Version 0 is the frontend's plugin-store file: `{ "settings": { ... } }`
+fn migrate_v0_plugin_store(value: Value) -> Value {
+    json!({
+        "version": 1,
+        "settings": value.get("settings").cloned().unwrap_or_else(|| json!({})),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(dir: &Path) -> (PathBuf, PathBuf) {
+        (
+            dir.join("companion_settings.json"),
+            dir.join("settings.json"),
+        )
+    }
+
+    #[test]
+    fn test_defaults_without_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, legacy) = paths(dir.path());
+
+        let store = SettingsStore::load(path.clone(), &legacy);
+        assert_eq!(*store.get(), Settings::default());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_settings_persist_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, legacy) = paths(dir.path());
+
+        let mut store = SettingsStore::load(path.clone(), &legacy);
+        store
+            .update(|s| {
+                s.eft_path = Some("D:\\EFT".to_string());
+                s.auto_watch = false;
+            })
+            .unwrap();
+
+        let restored = SettingsStore::load(path, &legacy);
+        assert_eq!(restored.get().eft_path.as_deref(), Some("D:\\EFT"));
+        assert!(!restored.get().auto_watch);
+        assert!(!dir.path().join("companion_settings.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_merge_keeps_fields_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, legacy) = paths(dir.path());
+
+        let mut store = SettingsStore::load(path.clone(), &legacy);
+        // The backend learns the install folder after the frontend read its copy
+        store
+            .update(|s| s.eft_path = Some("D:\\EFT".to_string()))
+            .unwrap();
+
+        let changes = json!({"autoStart": true, "apiBase": null});
+        store.merge(changes.as_object().unwrap().clone()).unwrap();
+        assert!(store.get().auto_start);
+        assert_eq!(store.get().eft_path.as_deref(), Some("D:\\EFT"));
+
+        // A field sent as null is cleared
+        let changes = json!({"eftPath": null});
+        store.merge(changes.as_object().unwrap().clone()).unwrap();
+        assert_eq!(SettingsStore::load(path, &legacy).get().eft_path, None);
+
+        let invalid = json!({"autoStart": "yes"});
+        assert!(store.merge(invalid.as_object().unwrap().clone()).is_err());
+        assert!(store.get().auto_start);
+    }
+
+    #[test]
+    fn test_imports_plugin_store_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, legacy) = paths(dir.path());
+        fs::write(
+            &legacy,
+            r#"{"settings": {"companionToken": "cmp_old", "eftPath": "C:\\EFT",
+                "autoStart": true, "autoWatch": true, "notifications": false,
+                "notificationSound": true}}"#,
+        )
+        .unwrap();
+
+        let store = SettingsStore::load(path.clone(), &legacy);
+        assert_eq!(store.plaintext_token(), Some("cmp_old"));
+        assert!(store.get().auto_start);
+        assert!(!store.get().notifications);
+        assert_eq!(store.get().api_base, None);
+        // The import is saved in the current format
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_forget_plaintext_token_scrubs_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, legacy) = paths(dir.path());
+        fs::write(
+            &legacy,
+            r#"{"settings": {"companionToken": "cmp_old", "autoStart": true}}"#,
+        )
+        .unwrap();
+
+        let mut store = SettingsStore::load(path.clone(), &legacy);
+        assert!(store.has_plaintext_files());
+        store.forget_plaintext_token().unwrap();
+
+        assert_eq!(store.plaintext_token(), None);
+        for file in [&path, &legacy] {
+            let content = fs::read_to_string(file).unwrap();
+            assert!(!content.contains("cmp_old"), "{:?} kept the token", file);
+        }
+        // The plugin-store file keeps its other settings
+        assert!(fs::read_to_string(&legacy).unwrap().contains("autoStart"));
+
+        let restarted = SettingsStore::load(path, &legacy);
+        assert!(!restarted.has_plaintext_files());
+        assert!(restarted.get().auto_start);
+    }
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let settings = parse(r#"{"version": 1, "settings": {"autoStart": true}}"#).unwrap();
+        assert!(settings.auto_start);
+        assert!(settings.auto_watch);
+        assert!(settings.notification_sound);
+    }
+
+    #[test]
+    fn test_rejects_newer_versions() {
+        assert_eq!(
+            parse(r#"{"version": 99, "settings": {}}"#)
+                .unwrap_err()
+                .code(),
+            "settings"
+        );
+        assert_eq!(parse(r#"{"settings": {}}"#).unwrap_err().code(), "settings");
+        assert_eq!(parse("{").unwrap_err().code(), "parse");
+    }
+
+    #[test]
+    fn test_corrupt_file_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, legacy) = paths(dir.path());
+        fs::write(
+            &path,
+            r#"{ not json, "settings": {"companionToken": "cmp_0123abcd"}"#,
+        )
+        .unwrap();
+
+        let store = SettingsStore::load(path.clone(), &legacy);
+        assert_eq!(*store.get(), Settings::default());
+
+        // The copy kept for inspection does not keep the token
+        let corrupt =
+            fs::read_to_string(dir.path().join("companion_settings.json.corrupt")).unwrap();
+        assert!(!corrupt.contains("cmp_0123abcd"));
+        assert!(corrupt.contains(r#""companionToken": "cmp_[removed]""#));
+    }
+
+    #[test]
+    fn test_in_memory_store_is_not_saved() {
+        let mut store = SettingsStore::in_memory();
+        store.update(|s| s.auto_start = true).unwrap();
+        assert!(store.get().auto_start);
+    }
+}
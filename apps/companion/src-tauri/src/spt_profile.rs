@@ -0,0 +1,306 @@
+//! SPT Profile Quests
+//!
+//! SPT keeps quest progress in the profile files under `user/profiles`, which the
+//! local server rewrites as the player progresses:
+//! 1. Each profile lists its PMC quests with a status, as a name or its number
+//! 2. Statuses are mapped onto the started/finished/failed events the live logs give
+//! 3. Changes between two reads of a profile become quest events
+//! 4. The client log reports most changes too; whichever source reports one first wins
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::event_dedup::content_hash;
+use crate::log_watcher::{EventOrigin, QuestEvent, QuestEventStatus};
+
+/// Session name given to events read from profiles
+pub const PROFILE_SESSION: &str = "spt-profile";
+
+#[derive(Debug, Deserialize)]
+struct Profile {
+    characters: Characters,
+}
+
+#[derive(Debug, Deserialize)]
+struct Characters {
+    pmc: Character,
+}
+
+#[derive(Debug, Deserialize)]
+struct Character {
+    #[serde(rename = "Quests", default)]
+    quests: Vec<ProfileQuest>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileQuest {
+    qid: String,
+    status: QuestStatus,
+    #[serde(rename = "statusTimers", default)]
+    status_timers: HashMap<String, i64>,
+}
+
+/// Quest status as written by the SPT server, by name or by number
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum QuestStatus {
+    Code(u32),
+    Name(String),
+}
+
+impl QuestStatus {
+    /// Number of the status in the game's `QuestStatus` enum
+    fn code(&self) -> Option<u32> {
+        const NAMES: &[&str] = &[
+            "Locked",
+            "AvailableForStart",
+            "Started",
+            "AvailableForFinish",
+            "Success",
+            "Fail",
+            "FailRestartable",
+            "MarkedAsFailed",
+            "Expired",
+            "AvailableAfter",
+        ];
+        match self {
+            QuestStatus::Code(code) => Some(*code),
+            QuestStatus::Name(name) => NAMES.iter().position(|n| n == name).map(|i| i as u32),
+        }
+    }
+}
+
+/// Event status for a profile quest status; quests not yet started have none
+fn event_status(code: u32) -> Option<QuestEventStatus> {
+    match code {
+        2 | 3 => Some(QuestEventStatus::Started),
+        4 => Some(QuestEventStatus::Finished),
+        5..=7 => Some(QuestEventStatus::Failed),
+        _ => None,
+    }
+}
+
+/// A quest's state in a profile
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuestState {
+    pub status: QuestEventStatus,
+    /// When the quest entered this status, if the profile records it
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Quest states of a profile, keyed by quest id
+pub fn parse_profile(content: &str) -> Result<HashMap<String, QuestState>, String> {
+    let profile: Profile =
+        serde_json::from_str(content).map_err(|e| format!("Invalid SPT profile: {}", e))?;
+
+    Ok(profile
+        .characters
+        .pmc
+        .quests
+        .into_iter()
+        .filter_map(|quest| {
+            let code = quest.status.code()?;
+            let status = event_status(code)?;
+            let since = quest
+                .status_timers
+                .get(&code.to_string())
+                .and_then(|&secs| Utc.timestamp_opt(secs, 0).single());
+            Some((quest.qid, QuestState { status, since }))
+        })
+        .collect())
+}
+
+/// Quest and status of an event, to match the same change read from both sources
+type ChangeKey = (String, &'static str);
+
+fn change_key(event: &QuestEvent) -> ChangeKey {
+    (event.quest_id.clone(), event.status.as_str())
+}
+
+/// Remembers the quest states of each profile to report what changed
+#[derive(Default)]
+pub struct ProfileTracker {
+    profiles: HashMap<PathBuf, HashMap<String, QuestState>>,
+    /// Changes the client log reported that no profile has shown yet
+    logged: HashSet<ChangeKey>,
+    /// Changes a profile reported that the client log has not shown yet
+    from_profiles: HashSet<ChangeKey>,
+}
+
+impl ProfileTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a client log event is new, rather than a change a profile already
+    /// reported. Always true while no profile is tracked, e.g. for live installs.
+    pub fn first_report(&mut self, event: &QuestEvent) -> bool {
+        if self.profiles.is_empty() {
+            return true;
+        }
+        let key = change_key(event);
+        if self.from_profiles.remove(&key) {
+            return false;
+        }
+        self.logged.insert(key);
+        true
+    }
+
+    /// Read a profile and return events for quests whose status changed.
+    /// The first read of a profile only records its states.
+    pub fn update(&mut self, path: &Path, content: &str) -> Result<Vec<QuestEvent>, String> {
+        let quests = parse_profile(content)?;
+        let Some(previous) = self.profiles.insert(path.to_path_buf(), quests.clone()) else {
+            return Ok(Vec::new());
+        };
+
+        let log_file = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut events: Vec<QuestEvent> = quests
+            .into_iter()
+            .filter(|(quest_id, state)| {
+                previous.get(quest_id).map(|p| &p.status) != Some(&state.status)
+            })
+            .map(|(quest_id, state)| {
+                let timestamp = state.since.unwrap_or_else(Utc::now);
+                QuestEvent {
+                    origin: EventOrigin {
+                        from_log: true,
+                        session: PROFILE_SESSION.to_string(),
+                        // Profiles have no log position; the time the quest entered
+                        // its status tells a repeat, e.g. a restarted quest, apart
+                        offset: timestamp.timestamp().max(0) as u64,
+                        // Profiles have no message id either; the status time stands in
+                        content_hash: content_hash(&format!(
+                            "{}:{}:{}@{}",
+                            log_file,
+                            quest_id,
+                            state.status.as_str(),
+                            timestamp.timestamp()
+                        )),
+                    },
+                    quest_id,
+                    status: state.status,
+                    timestamp,
+                    log_file: log_file.clone(),
+                }
+            })
+            .collect();
+        events.sort_by_key(|event| event.timestamp);
+
+        // Drop changes the client log already reported
+        events.retain(|event| {
+            let key = change_key(event);
+            if self.logged.remove(&key) {
+                return false;
+            }
+            self.from_profiles.insert(key);
+            true
+        });
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = include_str!("../tests/fixtures/spt/profile.json");
+
+    #[test]
+    fn test_parse_profile() {
+        let quests = parse_profile(PROFILE).unwrap();
+
+        assert_eq!(quests.len(), 3);
+        assert_eq!(
+            quests["5936d90786f7742b1420ba5b"],
+            QuestState {
+                status: QuestEventStatus::Finished,
+                since: Utc.timestamp_opt(1705318245, 0).single(),
+            }
+        );
+        assert_eq!(
+            quests["59674cd986f7744ab26e32f2"].status,
+            QuestEventStatus::Started
+        );
+        assert_eq!(
+            quests["5c0bd01e86f7747cdd799e56"].status,
+            QuestEventStatus::Failed
+        );
+        // Available but not started
+        assert!(!quests.contains_key("5967530a86f77462ba22226b"));
+    }
+
+    #[test]
+    fn test_rejects_other_json() {
+        assert!(parse_profile(r#"{"settings": {}}"#).is_err());
+        assert!(parse_profile("not json").is_err());
+    }
+
+    #[test]
+    fn test_tracker_reports_changes_only() {
+        let path = Path::new("/spt/user/profiles/6650a1b2c3d4e5f601234567.json");
+        let mut tracker = ProfileTracker::new();
+
+        // The first read is the baseline
+        assert!(tracker.update(path, PROFILE).unwrap().is_empty());
+        assert!(tracker.update(path, PROFILE).unwrap().is_empty());
+
+        let progressed = PROFILE
+            .replace(r#""status": 2,"#, r#""status": 4,"#)
+            .replace(r#""status": "AvailableForStart""#, r#""status": "Started""#);
+        let events = tracker.update(path, &progressed).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .any(|e| e.quest_id == "59674cd986f7744ab26e32f2"
+                && e.status == QuestEventStatus::Finished));
+        assert!(events
+            .iter()
+            .any(|e| e.quest_id == "5967530a86f77462ba22226b"
+                && e.status == QuestEventStatus::Started));
+        assert!(events.iter().all(|e| e.origin.session == PROFILE_SESSION
+            && e.log_file == "6650a1b2c3d4e5f601234567.json"));
+        // A repeat of the same status later gets another position
+        assert!(events
+            .iter()
+            .all(|e| e.origin.offset == e.timestamp.timestamp() as u64));
+    }
+
+    #[test]
+    fn test_change_is_reported_by_one_source() {
+        let path = Path::new("/spt/user/profiles/6650a1b2c3d4e5f601234567.json");
+        let mut tracker = ProfileTracker::new();
+        tracker.update(path, PROFILE).unwrap();
+
+        let finished = PROFILE.replace(r#""status": 2,"#, r#""status": 4,"#);
+        let logged = |quest_id: &str, status| QuestEvent {
+            quest_id: quest_id.to_string(),
+            status,
+            timestamp: Utc::now(),
+            log_file: "notifications.log".to_string(),
+            origin: EventOrigin::default(),
+        };
+
+        // The client log reported the change first
+        let event = logged("59674cd986f7744ab26e32f2", QuestEventStatus::Finished);
+        assert!(tracker.first_report(&event));
+        assert!(tracker.update(path, &finished).unwrap().is_empty());
+
+        // The profile reported the change first
+        let failed = finished.replace(r#""status": 4,"#, r#""status": 5,"#);
+        assert_eq!(tracker.update(path, &failed).unwrap().len(), 1);
+        let event = logged("59674cd986f7744ab26e32f2", QuestEventStatus::Failed);
+        assert!(!tracker.first_report(&event));
+
+        // A later repeat is reported again
+        assert!(tracker.first_report(&event));
+    }
+}
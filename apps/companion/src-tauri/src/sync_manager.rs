@@ -1,35 +1,66 @@
 //! Sync Manager
 //!
 //! Handles queuing and syncing quest events to the EFT Tracker API.
-//! Supports offline operation with local queue persistence.
+//! Supports offline operation, with the queue kept in an on-disk `EventJournal`.
+//! The companion token lives in a `TokenVault`, never in plain settings.
 
 use chrono::{DateTime, Utc};
-use log::{error, info};
+use log::{debug, error, info, warn};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
 
+use crate::eft_detector::{version_from_session_name, GameMode};
+use crate::error::CompanionError;
+use crate::event_dedup::{content_hash, EventDeduplicator};
+use crate::event_journal::EventJournal;
 use crate::log_watcher::QuestEvent;
+use crate::sync_retry::{parse_retry_after, SyncBackoff, SyncFailure};
+use crate::token_vault::TokenVault;
 
-/// Maximum events to queue before forcing a sync
-const MAX_QUEUE_SIZE: usize = 100;
+/// Most events the server accepts in one request; a queue this long is synced
+/// without waiting for more events
+pub const MAX_BATCH_SIZE: usize = 100;
 
 /// Delay before auto-sync in seconds (allows batching multiple rapid events)
-const AUTO_SYNC_DELAY_SECS: u64 = 5;
+pub const AUTO_SYNC_DELAY_SECS: u64 = 5;
+
+/// Header carrying a batch's idempotency key
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// How often the server is probed while it cannot be reached
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a health probe may take before the server counts as unreachable
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Sync event for API
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncEvent {
+    /// Client-generated id the server acknowledges the event by
+    #[serde(rename = "eventId")]
+    pub event_id: String,
     #[serde(rename = "questId")]
     pub quest_id: String,
     pub status: String,
     pub timestamp: String,
+    /// Client version of the session that logged the event
+    #[serde(
+        rename = "gameVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub game_version: Option<String>,
 }
 
 impl From<QuestEvent> for SyncEvent {
     fn from(event: QuestEvent) -> Self {
         Self {
+            event_id: event_id(&event),
+            // The session folder names the client version that wrote the event
+            game_version: version_from_session_name(&event.origin.session),
             quest_id: event.quest_id,
             status: event.status.as_str().to_string(),
             timestamp: event.timestamp.to_rfc3339(),
@@ -37,6 +68,37 @@ impl From<QuestEvent> for SyncEvent {
     }
 }
 
+/// Stable id of an event and its idempotency key; reading the same notification
+/// again gives the same id
+fn event_id(event: &QuestEvent) -> String {
+    let origin = &event.origin;
+    content_hash(&format!(
+        "{}:{}:{}:{}/{}@{}:{}",
+        event.quest_id,
+        event.status.as_str(),
+        event.timestamp.to_rfc3339(),
+        origin.session,
+        event.log_file,
+        origin.offset,
+        origin.content_hash
+    ))
+}
+
+/// Idempotency key of a batch; the same events in the same order give the same key
+fn batch_key(events: &[SyncEvent]) -> String {
+    let ids: Vec<&str> = events.iter().map(|e| e.event_id.as_str()).collect();
+    content_hash(&ids.join(","))
+}
+
+/// Outcome of queuing an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueOutcome {
+    /// Event was queued; `auto_sync` is true if an auto-sync should be scheduled
+    Queued { auto_sync: bool },
+    /// Event had already been seen and was dropped
+    Duplicate,
+}
+
 /// Sync result from API
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SyncResult {
@@ -44,12 +106,45 @@ pub struct SyncResult {
     pub errors: Vec<SyncError>,
     #[serde(rename = "unlockedQuests")]
     pub unlocked_quests: Vec<String>,
+    /// Ids of events that were applied
+    #[serde(default)]
+    pub accepted: Vec<String>,
+    /// Ids of events that can never apply, e.g. an unknown quest
+    #[serde(default)]
+    pub rejected: Vec<String>,
+    /// Ids of events that failed for a transient reason
+    #[serde(default)]
+    pub retryable: Vec<String>,
+}
+
+impl SyncResult {
+    /// Ids of the sent events that can leave the queue: applied or rejected for good.
+    /// Servers that predate event ids only name failed events, by quest; those
+    /// stay queued.
+    fn acknowledged(&self, sent: &[SyncEvent]) -> HashSet<String> {
+        if !self.accepted.is_empty() || !self.rejected.is_empty() || !self.retryable.is_empty() {
+            return self
+                .accepted
+                .iter()
+                .chain(&self.rejected)
+                .cloned()
+                .collect();
+        }
+
+        let failed: HashSet<&str> = self.errors.iter().map(|e| e.quest_id.as_str()).collect();
+        sent.iter()
+            .filter(|event| !failed.contains(event.quest_id.as_str()))
+            .map(|event| event.event_id.clone())
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SyncError {
     #[serde(rename = "questId")]
     pub quest_id: String,
+    #[serde(rename = "eventId", default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
     pub error: String,
 }
 
@@ -82,56 +177,246 @@ pub struct UserStats {
 
 pub struct SyncManager {
     api_base: String,
-    token: Option<String>,
+    vault: TokenVault,
     client: Client,
     event_queue: VecDeque<SyncEvent>,
+    journal: Option<EventJournal>,
     last_sync: Option<DateTime<Utc>>,
     total_synced: usize,
     total_errors: usize,
+    dedup: EventDeduplicator,
+    game_version: Option<String>,
+    /// Whether events come from the live game or SPT; not the account's PVP/PVE mode
+    client_mode: GameMode,
+    backoff: SyncBackoff,
+    /// Set while the server cannot be reached
+    offline_since: Option<DateTime<Utc>>,
+}
+
+/// Cheap request that tells whether the server can be reached
+#[derive(Clone)]
+pub struct HealthProbe {
+    client: Client,
+    url: String,
+}
+
+impl HealthProbe {
+    /// Whether the server answered with a success status; an error page from a proxy does not count
+    pub async fn run(&self) -> bool {
+        self.client
+            .get(&self.url)
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .is_ok_and(|response| response.status().is_success())
+    }
+}
+
+/// Checks a companion token with the server
+#[derive(Clone)]
+pub struct TokenValidator {
+    client: Client,
+    url: String,
+}
+
+impl TokenValidator {
+    /// Validate a companion token with the server; a rejected token is `Unauthorized`
+    pub async fn validate(&self, token: &str) -> Result<Value, CompanionError> {
+        let response = self
+            .client
+            .get(&self.url)
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if status.is_success() {
+            let validation: TokenValidationResponse = response.json().await?;
+
+            Ok(json!({
+                "valid": validation.valid,
+                "userId": validation.user_id,
+                "userName": validation.user_name,
+                "playerLevel": validation.player_level,
+                "deviceName": validation.device_name,
+                "gameMode": validation.game_mode,
+                "stats": validation.stats
+            }))
+        } else if status == 401 {
+            Err(CompanionError::Unauthorized)
+        } else {
+            Err(CompanionError::Server {
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            })
+        }
+    }
+}
+
+/// Events taken from the queue, ready to send
+pub struct SyncBatch {
+    client: Client,
+    url: String,
+    token: String,
+    events: Vec<SyncEvent>,
+    body: Value,
+}
+
+/// What came back for a batch
+pub enum BatchResponse {
+    /// The server could not be reached, or the connection dropped
+    Unreachable(String),
+    /// The server answered
+    Answered {
+        status: u16,
+        retry_after: Option<Duration>,
+        body: String,
+    },
+}
+
+impl SyncBatch {
+    /// Send the batch; needs no access to the manager
+    pub async fn send(&self) -> BatchResponse {
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header(IDEMPOTENCY_KEY_HEADER, batch_key(&self.events))
+            .json(&self.body)
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => return BatchResponse::Unreachable(e.to_string()),
+        };
+
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, Utc::now()));
+        match response.text().await {
+            Ok(body) => BatchResponse::Answered {
+                status,
+                retry_after,
+                body,
+            },
+            Err(e) => BatchResponse::Unreachable(e.to_string()),
+        }
+    }
 }
 
 impl SyncManager {
     pub fn new(api_base: String) -> Self {
         Self {
             api_base,
-            token: None,
+            vault: TokenVault::in_memory(),
             client: Client::new(),
             event_queue: VecDeque::new(),
+            journal: None,
             last_sync: None,
             total_synced: 0,
             total_errors: 0,
+            dedup: EventDeduplicator::new(),
+            game_version: None,
+            client_mode: GameMode::Live,
+            backoff: SyncBackoff::new(),
+            offline_since: None,
+        }
+    }
+
+    /// Replace the deduplicator, e.g. with one backed by the persisted seen-set
+    pub fn set_deduplicator(&mut self, dedup: EventDeduplicator) {
+        self.dedup = dedup;
+    }
+
+    /// Back the queue with a journal, putting the events it recovered ahead of any
+    /// already queued
+    pub fn set_journal(&mut self, mut journal: EventJournal, recovered: Vec<SyncEvent>) {
+        for event in &self.event_queue {
+            if let Err(e) = journal.append(event) {
+                warn!("Failed to journal queued event {}: {}", event.quest_id, e);
+            }
         }
+        let queued = std::mem::take(&mut self.event_queue);
+        self.event_queue = recovered.into_iter().chain(queued).collect();
+        self.journal = Some(journal);
+    }
+
+    /// Set the client version reported in `deviceInfo`
+    pub fn set_game_version(&mut self, version: Option<String>) {
+        self.game_version = version;
+    }
+
+    /// Set whether events come from the live game or SPT, reported in `deviceInfo`
+    pub fn set_client_mode(&mut self, mode: GameMode) {
+        self.client_mode = mode;
+    }
+
+    /// Point syncing at a different server
+    pub fn set_api_base(&mut self, api_base: String) {
+        self.api_base = api_base;
+    }
+
+    /// Keep the token in `vault`, e.g. the OS credential store, instead of memory
+    pub fn set_vault(&mut self, vault: TokenVault) {
+        self.vault = vault;
     }
 
-    /// Set the companion token
-    pub fn set_token(&mut self, token: String) {
-        self.token = Some(token);
+    /// Set the companion token, storing it in the vault
+    pub fn set_token(&mut self, token: String) -> Result<(), CompanionError> {
+        self.vault.set(token)
+    }
+
+    /// Remove the companion token from the vault
+    pub fn clear_token(&mut self) -> Result<(), CompanionError> {
+        self.vault.clear()
     }
 
     /// Get current token
     pub fn get_token(&self) -> Option<&String> {
-        self.token.as_ref()
+        self.vault.get()
+    }
+
+    /// The token to send; a stored token that cannot be read is an error, not `NoToken`
+    pub fn require_token(&self) -> Result<&String, CompanionError> {
+        self.vault.require()
     }
 
     /// Check if a token is set
     pub fn has_token(&self) -> bool {
-        self.token.is_some()
+        self.vault.get().is_some()
     }
 
-    /// Queue an event for syncing
-    /// Returns true if auto-sync should be triggered
-    pub async fn queue_event(&mut self, event: QuestEvent) -> Result<bool, String> {
-        let sync_event: SyncEvent = event.into();
-        self.event_queue.push_back(sync_event);
+    /// Queue an event for syncing, dropping it if it has been seen before.
+    /// The event only counts as seen once it is safely in the journal.
+    pub fn queue_event(&mut self, event: QuestEvent) -> Result<QueueOutcome, CompanionError> {
+        if !self.dedup.check(&event) {
+            debug!(
+                "Dropping duplicate quest event {} {}",
+                event.quest_id,
+                event.status.as_str()
+            );
+            return Ok(QueueOutcome::Duplicate);
+        }
 
-        // Auto-sync if queue is getting large
-        if self.event_queue.len() >= MAX_QUEUE_SIZE {
-            let _ = self.sync_pending().await;
-            return Ok(false); // Already synced
+        // Events replayed from older sessions keep their own version; others
+        // are labelled with the installed client's
+        let mut sync_event: SyncEvent = event.clone().into();
+        if sync_event.game_version.is_none() {
+            sync_event.game_version = self.game_version.clone();
         }
+        self.journal_event(&sync_event)?;
+        self.dedup.record(&event);
+        self.event_queue.push_back(sync_event);
 
-        // Signal that auto-sync should be scheduled
-        Ok(self.has_token())
+        // Signal that auto-sync should be scheduled; the sync scheduler sends a full
+        // queue right away
+        Ok(QueueOutcome::Queued {
+            auto_sync: self.has_token(),
+        })
     }
 
     /// Get the auto-sync delay in seconds
@@ -144,107 +429,225 @@ impl SyncManager {
         self.event_queue.len()
     }
 
-    /// Sync pending events to the server
-    pub async fn sync_pending(&mut self) -> Result<Value, String> {
-        if self.event_queue.is_empty() {
+    /// Sync the next batch of pending events, holding the manager throughout.
+    /// The sync scheduler drains the whole queue without holding it.
+    pub async fn sync_pending(&mut self) -> Result<Value, CompanionError> {
+        let Some(batch) = self.begin_sync()? else {
             return Ok(json!({
                 "synced": 0,
                 "message": "No pending events"
             }));
+        };
+        let response = batch.send().await;
+        self.finish_sync(&batch, response)
+    }
+
+    /// Take the next batch to send, if any events are queued. The batch is sent
+    /// without holding on to the manager, then handed back to `finish_sync`.
+    pub fn begin_sync(&self) -> Result<Option<SyncBatch>, CompanionError> {
+        if self.event_queue.is_empty() {
+            return Ok(None);
         }
 
-        let token = self.token.as_ref().ok_or("No companion token configured")?;
+        let token = self.vault.require()?;
 
-        // Collect events to sync (up to 100)
-        let events: Vec<SyncEvent> = self.event_queue.iter().take(100).cloned().collect();
+        let events: Vec<SyncEvent> = self
+            .event_queue
+            .iter()
+            .take(MAX_BATCH_SIZE)
+            .cloned()
+            .collect();
 
-        let response = self
-            .client
-            .post(format!("{}/api/companion/sync", self.api_base))
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&json!({
+        Ok(Some(SyncBatch {
+            client: self.client.clone(),
+            url: format!("{}/api/companion/sync", self.api_base),
+            token: token.clone(),
+            body: json!({
                 "events": events,
                 "deviceInfo": {
                     "version": env!("CARGO_PKG_VERSION"),
-                    "os": "windows"
+                    "os": "windows",
+                    "gameVersion": self.game_version,
+                    "clientMode": self.client_mode.as_str()
                 }
-            }))
-            .send()
-            .await
-            .map_err(|e| format!("Network error: {}", e))?;
+            }),
+            events,
+        }))
+    }
 
-        let status = response.status();
+    /// Apply the server's answer to a batch from `begin_sync`
+    pub fn finish_sync(
+        &mut self,
+        batch: &SyncBatch,
+        response: BatchResponse,
+    ) -> Result<Value, CompanionError> {
+        let (status, retry_after, body) = match response {
+            BatchResponse::Answered {
+                status,
+                retry_after,
+                body,
+            } => (status, retry_after, body),
+            BatchResponse::Unreachable(e) => {
+                let transient = SyncFailure::Transient { retry_after: None };
+                self.set_online(false);
+                return Err(self.record_failure(transient, CompanionError::Network(e)));
+            }
+        };
+        // A 5xx may come from a proxy in front of a down server, as for the health probe
+        self.set_online(status < 500);
+
+        if (200..300).contains(&status) {
+            let result: SyncResult = match serde_json::from_str(&body) {
+                Ok(result) => result,
+                Err(e) => {
+                    let transient = SyncFailure::Transient { retry_after: None };
+                    return Err(self.record_failure(transient, e.into()));
+                }
+            };
 
-        if status.is_success() {
-            let result: SyncResult = response
-                .json()
-                .await
-                .map_err(|e| format!("Failed to parse response: {}", e))?;
+            // Remove exactly the events the server acknowledged; retryable ones stay queued
+            self.remove_events(result.acknowledged(&batch.events));
 
-            // Remove synced events from queue
             let synced_count = result.synced;
-            for _ in 0..synced_count {
-                self.event_queue.pop_front();
-            }
-
             self.total_synced += synced_count;
             self.total_errors += result.errors.len();
             self.last_sync = Some(Utc::now());
 
             info!(
-                "Synced {} events, {} errors",
+                "Synced {} events, {} errors, {} to retry",
                 synced_count,
-                result.errors.len()
+                result.errors.len(),
+                result.retryable.len()
             );
 
+            // Events that failed on the server for a transient reason are retried like the batch
+            if result.retryable.is_empty() {
+                self.backoff.record_success();
+            } else {
+                self.backoff
+                    .record_failure(&SyncFailure::Transient { retry_after: None }, Utc::now());
+            }
+
             Ok(json!({
                 "synced": synced_count,
                 "errors": result.errors,
                 "unlockedQuests": result.unlocked_quests,
                 "pendingCount": self.event_queue.len()
             }))
-        } else if status.as_u16() == 401 {
-            Err("Invalid or expired companion token".to_string())
+        } else if status == 401 {
+            Err(self.record_failure(SyncFailure::Permanent, CompanionError::Unauthorized))
         } else {
-            let error_text = response.text().await.unwrap_or_default();
-            error!("Sync failed with status {}: {}", status, error_text);
-            Err(format!("Sync failed: {} - {}", status, error_text))
+            error!("Sync failed with status {}: {}", status, body);
+            let failure = SyncFailure::from_status(status, retry_after);
+            if failure == SyncFailure::Permanent {
+                // Sending the batch again would be refused again and hold up the
+                // rest of the queue, so its events are dropped as errors
+                let ids: Vec<&str> = batch.events.iter().map(|e| e.event_id.as_str()).collect();
+                error!(
+                    "Dropping {} events the server refused: {}",
+                    ids.len(),
+                    ids.join(", ")
+                );
+                self.total_errors += batch.events.len();
+                self.remove_events(batch.events.iter().map(|e| e.event_id.clone()).collect());
+            }
+            let error = match status {
+                429 => CompanionError::RateLimited { retry_after },
+                _ => CompanionError::Server {
+                    status,
+                    message: body,
+                },
+            };
+            Err(self.record_failure(failure, error))
         }
     }
 
-    /// Validate a companion token with the server
-    pub async fn validate_token(&self, token: &str) -> Result<Value, String> {
-        let response = self
-            .client
-            .get(format!("{}/api/companion/status", self.api_base))
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await
-            .map_err(|e| format!("Network error: {}", e))?;
+    /// Take events out of the queue and the journal
+    fn remove_events(&mut self, ids: HashSet<String>) {
+        self.event_queue
+            .retain(|event| !ids.contains(&event.event_id));
+        if let Some(journal) = &mut self.journal {
+            let ids: Vec<String> = ids.into_iter().collect();
+            if let Err(e) = journal.acknowledge(&ids, &self.event_queue) {
+                warn!("Failed to record removed events in the journal: {}", e);
+            }
+        }
+    }
 
-        let status = response.status();
+    /// Whether every event in the batch has left the queue
+    pub fn fully_acknowledged(&self, batch: &SyncBatch) -> bool {
+        let sent: HashSet<&str> = batch.events.iter().map(|e| e.event_id.as_str()).collect();
+        !self
+            .event_queue
+            .iter()
+            .any(|event| sent.contains(event.event_id.as_str()))
+    }
 
-        if status.is_success() {
-            let validation: TokenValidationResponse = response
-                .json()
-                .await
-                .map_err(|e| format!("Failed to parse response: {}", e))?;
+    /// Count a failed sync, scheduling a retry if it is worth one, and return its error
+    fn record_failure(&mut self, failure: SyncFailure, error: CompanionError) -> CompanionError {
+        match self.backoff.record_failure(&failure, Utc::now()) {
+            Some(at) => warn!("{}; retrying at {}", error, at.to_rfc3339()),
+            None => warn!("{}; not retrying", error),
+        }
+        error
+    }
 
-            Ok(json!({
-                "valid": validation.valid,
-                "userId": validation.user_id,
-                "userName": validation.user_name,
-                "playerLevel": validation.player_level,
-                "deviceName": validation.device_name,
-                "gameMode": validation.game_mode,
-                "stats": validation.stats
-            }))
-        } else {
-            let error_text = response.text().await.unwrap_or_default();
-            Ok(json!({
-                "valid": false,
-                "error": error_text
-            }))
+    /// Record whether the server could be reached, logging changes
+    fn set_online(&mut self, online: bool) {
+        match (online, self.offline_since) {
+            (true, Some(since)) => {
+                info!(
+                    "Server reachable again after being offline since {}",
+                    since.to_rfc3339()
+                );
+                self.offline_since = None;
+            }
+            (false, None) => {
+                warn!(
+                    "Server unreachable, {} events stay queued",
+                    self.event_queue.len()
+                );
+                self.offline_since = Some(Utc::now());
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the server could be reached on the last attempt
+    pub fn is_online(&self) -> bool {
+        self.offline_since.is_none()
+    }
+
+    /// Probe for checking connectivity without holding on to the manager
+    pub fn health_probe(&self) -> HealthProbe {
+        HealthProbe {
+            client: self.client.clone(),
+            url: format!("{}/api/health", self.api_base),
+        }
+    }
+
+    /// Record the result of a health probe; returns true if the server is back
+    pub fn record_probe(&mut self, reachable: bool) -> bool {
+        let was_offline = !self.is_online();
+        self.set_online(reachable);
+        was_offline && reachable
+    }
+
+    /// When the next retry of a failed sync is due, if one is scheduled
+    pub fn next_retry_at(&self) -> Option<DateTime<Utc>> {
+        self.backoff.next_retry_at()
+    }
+
+    /// Whether a failed sync is waiting to be retried; syncing earlier would only fail again
+    pub fn is_backing_off(&self) -> bool {
+        self.backoff.is_waiting(Utc::now())
+    }
+
+    /// Token check that runs without holding on to the manager
+    pub fn token_validator(&self) -> TokenValidator {
+        TokenValidator {
+            client: self.client.clone(),
+            url: format!("{}/api/companion/status", self.api_base),
         }
     }
 
@@ -252,16 +655,30 @@ impl SyncManager {
     pub fn get_status(&self) -> Value {
         json!({
             "hasToken": self.has_token(),
+            "tokenStorage": self.vault.backend_name(),
+            "tokenError": self.vault.load_error(),
             "pendingCount": self.event_queue.len(),
             "totalSynced": self.total_synced,
             "totalErrors": self.total_errors,
-            "lastSync": self.last_sync.map(|t| t.to_rfc3339())
+            "duplicatesDropped": self.dedup.duplicates_dropped(),
+            "lastSync": self.last_sync.map(|t| t.to_rfc3339()),
+            "gameVersion": self.game_version,
+            "clientMode": self.client_mode.as_str(),
+            "nextRetryAt": self.next_retry_at().map(|t| t.to_rfc3339()),
+            "consecutiveFailures": self.backoff.consecutive_failures(),
+            "online": self.is_online(),
+            "offlineSince": self.offline_since.map(|t| t.to_rfc3339())
         })
     }
 
     /// Clear the event queue
     pub fn clear_queue(&mut self) {
         self.event_queue.clear();
+        if let Some(journal) = &mut self.journal {
+            if let Err(e) = journal.compact(&self.event_queue) {
+                warn!("Failed to clear the event journal: {}", e);
+            }
+        }
     }
 
     /// Export queue for persistence
@@ -270,16 +687,44 @@ impl SyncManager {
     }
 
     /// Import queue from persistence
-    pub fn import_queue(&mut self, events: Vec<SyncEvent>) {
+    pub fn import_queue(&mut self, events: Vec<SyncEvent>) -> Result<(), CompanionError> {
         for event in events {
+            self.journal_event(&event)?;
             self.event_queue.push_back(event);
         }
+        Ok(())
+    }
+
+    /// Write an event to the journal before it is queued
+    fn journal_event(&mut self, event: &SyncEvent) -> Result<(), CompanionError> {
+        if let Some(journal) = &mut self.journal {
+            journal.append(event).map_err(|e| {
+                warn!("Failed to journal queued event {}: {}", event.quest_id, e);
+                CompanionError::from(e)
+            })?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::log_watcher::{EventOrigin, QuestEventStatus};
+    use crate::test_server::{
+        backlog, manager_with_backlog, mock_error_server, mock_sync_server, MockSync,
+    };
+
+    /// Event for a quest change, read at no particular place in the logs
+    fn quest_event(quest_id: &str, status: QuestEventStatus) -> QuestEvent {
+        QuestEvent {
+            quest_id: quest_id.to_string(),
+            status,
+            timestamp: Utc::now(),
+            log_file: "application.log".to_string(),
+            origin: EventOrigin::default(),
+        }
+    }
 
     #[test]
     fn test_sync_manager_creation() {
@@ -293,55 +738,77 @@ mod tests {
         let mut manager = SyncManager::new("https://test.com".to_string());
         assert!(!manager.has_token());
 
-        manager.set_token("cmp_test123".to_string());
+        manager.set_token("cmp_test123".to_string()).unwrap();
         assert!(manager.has_token());
         assert_eq!(manager.get_token(), Some(&"cmp_test123".to_string()));
+
+        manager.clear_token().unwrap();
+        assert!(!manager.has_token());
     }
 
     #[test]
-    fn test_sync_event_conversion() {
-        use crate::log_watcher::QuestEventStatus;
+    fn test_token_persists_in_vault() {
+        use crate::token_vault::EncryptedFileBackend;
 
-        let quest_event = QuestEvent {
-            quest_id: "test_quest".to_string(),
-            status: QuestEventStatus::Started,
-            timestamp: Utc::now(),
-            log_file: "test.log".to_string(),
-        };
+        let dir = tempfile::tempdir().unwrap();
+        let vault = || TokenVault::new(Box::new(EncryptedFileBackend::new(dir.path())));
+
+        let mut manager = SyncManager::new("https://test.com".to_string());
+        manager.set_vault(vault());
+        manager.set_token("cmp_test123".to_string()).unwrap();
+        assert_eq!(manager.get_status()["tokenStorage"], "encrypted-file");
+
+        let mut restarted = SyncManager::new("https://test.com".to_string());
+        restarted.set_vault(vault());
+        assert_eq!(restarted.get_token(), Some(&"cmp_test123".to_string()));
+    }
 
-        let sync_event: SyncEvent = quest_event.into();
+    #[test]
+    fn test_sync_event_conversion() {
+        let event = quest_event("test_quest", QuestEventStatus::Started);
+
+        let sync_event: SyncEvent = event.into();
         assert_eq!(sync_event.quest_id, "test_quest");
         assert_eq!(sync_event.status, "STARTED");
     }
 
     #[test]
-    fn test_sync_event_conversion_finished() {
-        use crate::log_watcher::QuestEventStatus;
-
-        let quest_event = QuestEvent {
-            quest_id: "59ca2eb686f77445a80ed049".to_string(),
-            status: QuestEventStatus::Finished,
-            timestamp: Utc::now(),
-            log_file: "application.log".to_string(),
+    fn test_event_ids_are_stable() {
+        let event = QuestEvent {
+            origin: EventOrigin {
+                from_log: true,
+                session: "log_2024.01.15_10-30-45".to_string(),
+                offset: 120,
+                content_hash: "0123456789abcdef".to_string(),
+            },
+            ..quest_event("59ca2eb686f77445a80ed049", QuestEventStatus::Finished)
         };
+        let mut later = event.clone();
+        later.origin.offset = 480;
 
-        let sync_event: SyncEvent = quest_event.into();
+        let first: SyncEvent = event.clone().into();
+        let again: SyncEvent = event.into();
+        let other: SyncEvent = later.into();
+
+        assert_eq!(first.event_id, again.event_id);
+        assert_ne!(first.event_id, other.event_id);
+        assert!(serde_json::to_string(&first).unwrap().contains("eventId"));
+    }
+
+    #[test]
+    fn test_sync_event_conversion_finished() {
+        let event = quest_event("59ca2eb686f77445a80ed049", QuestEventStatus::Finished);
+
+        let sync_event: SyncEvent = event.into();
         assert_eq!(sync_event.quest_id, "59ca2eb686f77445a80ed049");
         assert_eq!(sync_event.status, "FINISHED");
     }
 
     #[test]
     fn test_sync_event_conversion_failed() {
-        use crate::log_watcher::QuestEventStatus;
+        let event = quest_event("5d4bec3486f7743cac246665", QuestEventStatus::Failed);
 
-        let quest_event = QuestEvent {
-            quest_id: "5d4bec3486f7743cac246665".to_string(),
-            status: QuestEventStatus::Failed,
-            timestamp: Utc::now(),
-            log_file: "test.log".to_string(),
-        };
-
-        let sync_event: SyncEvent = quest_event.into();
+        let sync_event: SyncEvent = event.into();
         assert_eq!(sync_event.quest_id, "5d4bec3486f7743cac246665");
         assert_eq!(sync_event.status, "FAILED");
     }
@@ -355,13 +822,18 @@ mod tests {
         assert_eq!(status["pendingCount"], 0);
         assert_eq!(status["totalSynced"], 0);
         assert_eq!(status["totalErrors"], 0);
+        assert_eq!(status["duplicatesDropped"], 0);
+        assert_eq!(status["clientMode"], "live");
         assert!(status["lastSync"].is_null());
+        assert!(status["nextRetryAt"].is_null());
+        assert_eq!(status["consecutiveFailures"], 0);
+        assert_eq!(status["online"], true);
     }
 
     #[test]
     fn test_get_status_with_token() {
         let mut manager = SyncManager::new("https://test.com".to_string());
-        manager.set_token("cmp_test123".to_string());
+        manager.set_token("cmp_test123".to_string()).unwrap();
         let status = manager.get_status();
 
         assert_eq!(status["hasToken"], true);
@@ -379,16 +851,7 @@ mod tests {
         let mut manager = SyncManager::new("https://test.com".to_string());
 
         // Add some events directly to the queue
-        manager.event_queue.push_back(SyncEvent {
-            quest_id: "quest1".to_string(),
-            status: "FINISHED".to_string(),
-            timestamp: Utc::now().to_rfc3339(),
-        });
-        manager.event_queue.push_back(SyncEvent {
-            quest_id: "quest2".to_string(),
-            status: "STARTED".to_string(),
-            timestamp: Utc::now().to_rfc3339(),
-        });
+        manager.event_queue.extend(backlog(2));
 
         assert_eq!(manager.pending_count(), 2);
 
@@ -400,36 +863,28 @@ mod tests {
     fn test_export_import_queue() {
         let mut manager = SyncManager::new("https://test.com".to_string());
 
-        // Add events
-        manager.event_queue.push_back(SyncEvent {
-            quest_id: "quest1".to_string(),
-            status: "FINISHED".to_string(),
-            timestamp: "2024-01-15T10:30:00Z".to_string(),
-        });
-        manager.event_queue.push_back(SyncEvent {
-            quest_id: "quest2".to_string(),
-            status: "STARTED".to_string(),
-            timestamp: "2024-01-15T10:31:00Z".to_string(),
-        });
+        manager.event_queue.extend(backlog(2));
 
         // Export
         let exported = manager.export_queue();
         assert_eq!(exported.len(), 2);
-        assert_eq!(exported[0].quest_id, "quest1");
-        assert_eq!(exported[1].quest_id, "quest2");
+        assert_eq!(exported[0].quest_id, "quest0");
+        assert_eq!(exported[1].quest_id, "quest1");
 
         // Clear and import into new manager
         let mut new_manager = SyncManager::new("https://test.com".to_string());
-        new_manager.import_queue(exported);
+        new_manager.import_queue(exported).unwrap();
         assert_eq!(new_manager.pending_count(), 2);
     }
 
     #[test]
     fn test_sync_event_serialization() {
         let event = SyncEvent {
+            event_id: "event5".to_string(),
             quest_id: "59ca2eb686f77445a80ed049".to_string(),
             status: "FINISHED".to_string(),
             timestamp: "2024-01-15T10:30:00Z".to_string(),
+            game_version: None,
         };
 
         let json = serde_json::to_string(&event).unwrap();
@@ -471,6 +926,38 @@ mod tests {
         assert_eq!(result.errors[0].error, "Quest not found");
     }
 
+    #[test]
+    fn test_acknowledged_events_by_id() {
+        let sent = backlog(3);
+
+        let json = r#"{
+            "synced": 1,
+            "errors": [
+                {"questId": "quest1", "eventId": "event1", "error": "Failed to process event"},
+                {"questId": "quest2", "eventId": "event2", "error": "Quest not found"}
+            ],
+            "unlockedQuests": [],
+            "accepted": ["event0"],
+            "rejected": ["event2"],
+            "retryable": ["event1"]
+        }"#;
+        let result: SyncResult = serde_json::from_str(json).unwrap();
+        let acknowledged = result.acknowledged(&sent);
+        assert_eq!(acknowledged.len(), 2);
+        assert!(acknowledged.contains("event0") && acknowledged.contains("event2"));
+
+        // A server without event ids only names failures, and they are kept
+        let json = r#"{
+            "synced": 2,
+            "errors": [{"questId": "quest1", "error": "Failed to process event"}],
+            "unlockedQuests": []
+        }"#;
+        let legacy: SyncResult = serde_json::from_str(json).unwrap();
+        let acknowledged = legacy.acknowledged(&sent);
+        assert_eq!(acknowledged.len(), 2);
+        assert!(!acknowledged.contains("event1"));
+    }
+
     #[test]
     fn test_token_validation_response_valid() {
         let json = r#"{
@@ -511,52 +998,150 @@ mod tests {
         assert!(response.user_id.is_none());
     }
 
-    #[tokio::test]
-    async fn test_queue_event_without_token() {
-        use crate::log_watcher::QuestEventStatus;
-
+    #[test]
+    fn test_queue_event_without_token() {
         let mut manager = SyncManager::new("https://test.com".to_string());
         // Don't set a token
 
-        let event = QuestEvent {
-            quest_id: "test_quest".to_string(),
-            status: QuestEventStatus::Finished,
-            timestamp: Utc::now(),
-            log_file: "test.log".to_string(),
-        };
+        let event = quest_event("test_quest", QuestEventStatus::Finished);
 
-        let result = manager.queue_event(event).await;
+        let result = manager.queue_event(event);
         assert!(result.is_ok());
         // Should not trigger auto-sync without token
-        assert_eq!(result.unwrap(), false);
+        assert_eq!(result.unwrap(), QueueOutcome::Queued { auto_sync: false });
         assert_eq!(manager.pending_count(), 1);
     }
 
-    #[tokio::test]
-    async fn test_queue_event_with_token() {
-        use crate::log_watcher::QuestEventStatus;
+    #[test]
+    fn test_queue_event_with_token() {
+        let mut manager = SyncManager::new("https://test.com".to_string());
+        manager.set_token("cmp_test123".to_string()).unwrap();
+
+        let event = quest_event("test_quest", QuestEventStatus::Finished);
+
+        let result = manager.queue_event(event);
+        assert!(result.is_ok());
+        // Should trigger auto-sync with token
+        assert_eq!(result.unwrap(), QueueOutcome::Queued { auto_sync: true });
+        assert_eq!(manager.pending_count(), 1);
+    }
 
+    #[test]
+    fn test_queue_event_drops_duplicates() {
+        use crate::event_dedup::content_hash;
         let mut manager = SyncManager::new("https://test.com".to_string());
-        manager.set_token("cmp_test123".to_string());
 
         let event = QuestEvent {
-            quest_id: "test_quest".to_string(),
-            status: QuestEventStatus::Finished,
-            timestamp: Utc::now(),
-            log_file: "test.log".to_string(),
+            origin: EventOrigin {
+                from_log: true,
+                session: "log_2024.01.15_10-30-45".to_string(),
+                offset: 120,
+                content_hash: content_hash("{\"_id\": \"abc\"}"),
+            },
+            ..quest_event("59ca2eb686f77445a80ed049", QuestEventStatus::Finished)
         };
 
-        let result = manager.queue_event(event).await;
-        assert!(result.is_ok());
-        // Should trigger auto-sync with token
-        assert_eq!(result.unwrap(), true);
+        let first = manager.queue_event(event.clone()).unwrap();
+        let second = manager.queue_event(event).unwrap();
+
+        assert_eq!(first, QueueOutcome::Queued { auto_sync: false });
+        assert_eq!(second, QueueOutcome::Duplicate);
         assert_eq!(manager.pending_count(), 1);
+        assert_eq!(manager.get_status()["duplicatesDropped"], 1);
+    }
+
+    #[test]
+    fn test_event_is_not_seen_until_journaled() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = SyncManager::new("https://test.com".to_string());
+        manager.journal = Some(EventJournal::failing(dir.path().join("queue.journal")).unwrap());
+
+        let event = QuestEvent {
+            origin: EventOrigin {
+                from_log: true,
+                session: "log_2024.01.15_10-30-45".to_string(),
+                offset: 120,
+                content_hash: content_hash("{\"_id\": \"abc\"}"),
+            },
+            ..quest_event("59ca2eb686f77445a80ed049", QuestEventStatus::Finished)
+        };
+        assert!(matches!(
+            manager.queue_event(event.clone()),
+            Err(CompanionError::Io(_))
+        ));
+        assert_eq!(manager.pending_count(), 0);
+
+        // Once the journal works again the event is queued, not dropped as seen
+        let (journal, _) = EventJournal::open(dir.path().join("queue.journal")).unwrap();
+        manager.journal = Some(journal);
+        assert_eq!(
+            manager.queue_event(event).unwrap(),
+            QueueOutcome::Queued { auto_sync: false }
+        );
+    }
+
+    #[test]
+    fn test_queue_event_records_session_game_version() {
+        let mut manager = SyncManager::new("https://test.com".to_string());
+        manager.set_game_version(Some("0.14.0.2.28375".to_string()));
+
+        let event = |session: &str| QuestEvent {
+            origin: EventOrigin {
+                session: session.to_string(),
+                ..EventOrigin::default()
+            },
+            ..quest_event("59ca2eb686f77445a80ed049", QuestEventStatus::Started)
+        };
+        // A replayed event from a session of the previous client version
+        manager
+            .queue_event(event("log_2024.01.10_20-00-00_0.14.0.1.28000"))
+            .unwrap();
+        manager.queue_event(event("spt-profile")).unwrap();
+
+        let versions: Vec<_> = manager
+            .event_queue
+            .iter()
+            .map(|e| e.game_version.as_deref())
+            .collect();
+        assert_eq!(
+            versions,
+            vec![Some("0.14.0.1.28000"), Some("0.14.0.2.28375")]
+        );
+        // The installed version is not overwritten by replayed events
+        assert_eq!(manager.get_status()["gameVersion"], "0.14.0.2.28375");
+    }
+
+    #[test]
+    fn test_queue_survives_restart_through_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("event_queue.journal");
+
+        let mut manager = SyncManager::new("https://test.com".to_string());
+        let (journal, recovered) = EventJournal::open(path.clone()).unwrap();
+        manager.set_journal(journal, recovered);
+        for quest_id in ["quest1", "quest2"] {
+            let event = quest_event(quest_id, QuestEventStatus::Finished);
+            manager.queue_event(event).unwrap();
+        }
+        drop(manager);
+
+        let mut restarted = SyncManager::new("https://test.com".to_string());
+        let (journal, recovered) = EventJournal::open(path.clone()).unwrap();
+        restarted.set_journal(journal, recovered);
+        let queued = restarted.export_queue();
+        assert_eq!(queued.len(), 2);
+        assert_eq!(queued[0].quest_id, "quest1");
+        assert_eq!(queued[1].quest_id, "quest2");
+
+        restarted.clear_queue();
+        let (_, recovered) = EventJournal::open(path).unwrap();
+        assert!(recovered.is_empty());
     }
 
     #[tokio::test]
     async fn test_sync_pending_empty() {
         let mut manager = SyncManager::new("https://test.com".to_string());
-        manager.set_token("cmp_test123".to_string());
+        manager.set_token("cmp_test123".to_string()).unwrap();
 
         let result = manager.sync_pending().await;
         assert!(result.is_ok());
@@ -569,14 +1154,129 @@ mod tests {
     async fn test_sync_pending_no_token() {
         let mut manager = SyncManager::new("https://test.com".to_string());
         // Add an event but no token
-        manager.event_queue.push_back(SyncEvent {
-            quest_id: "quest1".to_string(),
-            status: "FINISHED".to_string(),
-            timestamp: Utc::now().to_rfc3339(),
-        });
+        manager.event_queue.extend(backlog(1));
 
         let result = manager.sync_pending().await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("No companion token"));
+        assert_eq!(result.unwrap_err(), CompanionError::NoToken);
+    }
+
+    #[tokio::test]
+    async fn test_lost_response_is_retried_with_the_same_event_ids() {
+        let (url, log) = mock_sync_server(MockSync {
+            drop_responses: 1,
+            ..MockSync::default()
+        })
+        .await;
+        let mut manager = manager_with_backlog(url, 2);
+
+        // The server committed, but the response was lost
+        let lost = manager.sync_pending().await;
+        assert_eq!(lost.unwrap_err().code(), "network");
+        assert_eq!(manager.pending_count(), 2);
+
+        let retried = manager.sync_pending().await.unwrap();
+        assert_eq!(retried["pendingCount"], 0);
+
+        // The server only avoids applying the events twice if the retry names
+        // them by the same ids
+        let log = log.lock().unwrap();
+        assert_eq!(log.batches, vec![vec!["event0", "event1"]; 2]);
+        assert_eq!(log.batch_keys.len(), 2);
+        assert_eq!(log.batch_keys[0], log.batch_keys[1]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_syncs_are_retried_only_when_transient() {
+        let mut manager = SyncManager::new(
+            mock_error_server("503 Service Unavailable", "Retry-After: 120\r\n").await,
+        );
+        manager.set_token("cmp_test123".to_string()).unwrap();
+        manager.import_queue(backlog(1)).unwrap();
+
+        let before = Utc::now();
+        assert!(matches!(
+            manager.sync_pending().await,
+            Err(CompanionError::Server { status: 503, .. })
+        ));
+        let retry_at = manager.next_retry_at().unwrap();
+        assert!(retry_at >= before + chrono::Duration::seconds(120));
+        assert!(retry_at <= Utc::now() + chrono::Duration::seconds(120));
+        assert!(manager.is_backing_off());
+        assert_eq!(manager.get_status()["consecutiveFailures"], 1);
+        assert!(!manager.is_online());
+
+        // A rejected token is never retried, but the server did answer
+        manager.set_api_base(mock_error_server("401 Unauthorized", "").await);
+        let err = manager.sync_pending().await.unwrap_err();
+        assert_eq!(err, CompanionError::Unauthorized);
+        assert!(manager.is_online());
+        assert_eq!(manager.next_retry_at(), None);
+        assert_eq!(manager.get_status()["consecutiveFailures"], 2);
+        assert_eq!(manager.pending_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_validation_errors_keep_their_status() {
+        let manager = SyncManager::new(mock_error_server("401 Unauthorized", "").await);
+        let err = manager.token_validator().validate("cmp_test123").await;
+        assert_eq!(err.unwrap_err(), CompanionError::Unauthorized);
+
+        let manager = SyncManager::new(mock_error_server("502 Bad Gateway", "").await);
+        let err = manager.token_validator().validate("cmp_test123").await;
+        assert!(matches!(
+            err,
+            Err(CompanionError::Server { status: 502, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_refused_batch_is_dropped_and_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_path = dir.path().join("queue.journal");
+        let mut manager = SyncManager::new(mock_error_server("422 Unprocessable Entity", "").await);
+        manager.set_token("cmp_test123".to_string()).unwrap();
+        let (journal, recovered) = EventJournal::open(journal_path.clone()).unwrap();
+        manager.set_journal(journal, recovered);
+        manager.import_queue(backlog(2)).unwrap();
+
+        assert!(matches!(
+            manager.sync_pending().await,
+            Err(CompanionError::Server { status: 422, .. })
+        ));
+        assert_eq!(manager.pending_count(), 0);
+        assert_eq!(manager.next_retry_at(), None);
+        assert_eq!(manager.get_status()["totalErrors"], 2);
+
+        // Nor do they come back after a restart
+        let (_, recovered) = EventJournal::open(journal_path).unwrap();
+        assert!(recovered.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_goes_offline_until_the_server_is_reachable() {
+        // A port nothing listens on
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let unreachable = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let mut manager = SyncManager::new(unreachable);
+        manager.set_token("cmp_test123".to_string()).unwrap();
+        manager.import_queue(backlog(1)).unwrap();
+
+        assert!(manager.sync_pending().await.is_err());
+        assert!(!manager.is_online());
+        assert!(!manager.get_status()["offlineSince"].is_null());
+        assert!(!manager.health_probe().run().await);
+        assert!(!manager.record_probe(false));
+
+        // An error status, e.g. from a proxy in front of a down server, is not reachable
+        manager.set_api_base(mock_error_server("503 Service Unavailable", "").await);
+        assert!(!manager.health_probe().run().await);
+
+        manager.set_api_base(mock_error_server("200 OK", "").await);
+        assert!(manager.health_probe().run().await);
+        assert!(manager.record_probe(true));
+        assert!(manager.is_online());
+        assert_eq!(manager.pending_count(), 1);
     }
 }
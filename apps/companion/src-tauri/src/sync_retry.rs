@@ -0,0 +1,210 @@
+//! Sync Retry
+//!
+//! Decides whether and when a failed sync is tried again:
+//! 1. Network errors, 5xx and 429 responses are transient and retried
+//! 2. Retries back off exponentially up to a cap, with jitter so clients that
+//!    failed together do not all retry together
+//! 3. A `Retry-After` header from the server takes precedence over the backoff
+//! 4. Rejected tokens and invalid requests are never retried
+
+use chrono::{DateTime, Utc};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Delay before the first retry
+const BASE_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest delay between retries
+const MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// Longest `Retry-After` honoured, so a bad header cannot park the queue for days
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+
+/// Why a sync attempt failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncFailure {
+    /// Worth retrying, no sooner than `retry_after` if the server asked for a delay
+    Transient { retry_after: Option<Duration> },
+    /// Sending the same request again cannot succeed
+    Permanent,
+}
+
+impl SyncFailure {
+    /// Classify an error response by its status code
+    pub fn from_status(status: u16, retry_after: Option<Duration>) -> Self {
+        match status {
+            408 | 429 | 500..=599 => SyncFailure::Transient { retry_after },
+            _ => SyncFailure::Permanent,
+        }
+    }
+}
+
+/// Parse a `Retry-After` value, given either in seconds or as an HTTP date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Consecutive sync failures and when to try again
+#[derive(Debug, Default)]
+pub struct SyncBackoff {
+    consecutive_failures: u32,
+    next_retry_at: Option<DateTime<Utc>>,
+}
+
+impl SyncBackoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget earlier failures after a successful sync
+    pub fn record_success(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Count a failed attempt and return when to retry, if it is worth retrying
+    pub fn record_failure(
+        &mut self,
+        failure: &SyncFailure,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        self.consecutive_failures += 1;
+        self.next_retry_at = match failure {
+            SyncFailure::Permanent => None,
+            SyncFailure::Transient { retry_after } => {
+                let delay = match retry_after {
+                    Some(retry_after) => (*retry_after).min(MAX_RETRY_AFTER),
+                    None => backoff_delay(self.consecutive_failures, jitter()),
+                };
+                chrono::Duration::from_std(delay)
+                    .ok()
+                    .map(|delay| now + delay)
+            }
+        };
+        self.next_retry_at
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn next_retry_at(&self) -> Option<DateTime<Utc>> {
+        self.next_retry_at
+    }
+
+    /// Whether a retry is scheduled after `now`
+    pub fn is_waiting(&self, now: DateTime<Utc>) -> bool {
+        self.next_retry_at.is_some_and(|at| at > now)
+    }
+}
+
+/// Delay before retry number `attempt`, counted from 1: half of the capped
+/// exponential delay, plus `jitter` (in [0, 1)) of the other half
+fn backoff_delay(attempt: u32, jitter: f64) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let capped = BASE_DELAY.saturating_mul(1 << exponent).min(MAX_DELAY);
+    capped / 2 + capped.mul_f64(jitter.clamp(0.0, 1.0) / 2.0)
+}
+
+/// Random number in [0, 1), from the randomly keyed std hasher
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_backoff_grows_to_cap_with_jitter() {
+        assert_eq!(backoff_delay(1, 0.0), Duration::from_millis(2500));
+        assert_eq!(backoff_delay(1, 1.0), BASE_DELAY);
+        assert_eq!(backoff_delay(2, 0.0), BASE_DELAY);
+        assert_eq!(backoff_delay(3, 0.5), Duration::from_secs(15));
+        assert_eq!(backoff_delay(40, 1.0), MAX_DELAY);
+        assert_eq!(backoff_delay(40, 0.0), MAX_DELAY / 2);
+
+        for _ in 0..100 {
+            let jitter = jitter();
+            assert!((0.0..1.0).contains(&jitter));
+        }
+    }
+
+    #[test]
+    fn test_classifies_failures() {
+        let retry_after = Some(Duration::from_secs(30));
+        assert_eq!(
+            SyncFailure::from_status(429, retry_after),
+            SyncFailure::Transient { retry_after }
+        );
+        assert_eq!(
+            SyncFailure::from_status(503, None),
+            SyncFailure::Transient { retry_after: None }
+        );
+        assert_eq!(SyncFailure::from_status(401, None), SyncFailure::Permanent);
+        assert_eq!(SyncFailure::from_status(400, None), SyncFailure::Permanent);
+        assert_eq!(SyncFailure::from_status(422, None), SyncFailure::Permanent);
+    }
+
+    #[test]
+    fn test_parses_retry_after() {
+        let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 27, 0).unwrap();
+
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::from_secs(60))
+        );
+        // A date in the past means now
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_schedules_retries_until_success() {
+        let now = Utc::now();
+        let mut backoff = SyncBackoff::new();
+
+        let first = backoff
+            .record_failure(&SyncFailure::Transient { retry_after: None }, now)
+            .unwrap();
+        assert!(first > now && first <= now + chrono::Duration::seconds(5));
+        assert!(backoff.is_waiting(now));
+
+        let asked = SyncFailure::Transient {
+            retry_after: Some(Duration::from_secs(7200)),
+        };
+        assert_eq!(
+            backoff.record_failure(&asked, now),
+            Some(now + chrono::Duration::hours(1))
+        );
+        assert_eq!(backoff.consecutive_failures(), 2);
+
+        // A rejected token is not retried, but still counts as a failure
+        assert_eq!(backoff.record_failure(&SyncFailure::Permanent, now), None);
+        assert_eq!(backoff.consecutive_failures(), 3);
+        assert!(!backoff.is_waiting(now));
+
+        backoff.record_success();
+        assert_eq!(backoff.consecutive_failures(), 0);
+        assert_eq!(backoff.next_retry_at(), None);
+    }
+}
@@ -0,0 +1,717 @@
+//! Sync Scheduler
+//!
+//! Runs every sync from a single task, fed through a channel:
+//! 1. Queued events are debounced, so a burst of events goes out as one batch
+//! 2. A full queue, a manual sync or the game exiting syncs right away
+//! 3. A sync drains the whole queue in ordered chunks, reporting progress, until it
+//!    is empty, a chunk fails or the user cancels; a failed sync still reports the
+//!    chunks it got through
+//! 4. Failed syncs are retried when the backoff says so
+//! 5. Requests are sent without holding the sync manager, so status reads never
+//!    wait on the network
+
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::CompanionError;
+use crate::sync_manager::{SyncManager, AUTO_SYNC_DELAY_SECS, MAX_BATCH_SIZE};
+
+/// Longest queued events are held back while more keep arriving
+const MAX_SYNC_DELAY: Duration = Duration::from_secs(30);
+
+/// When queued events are synced
+#[derive(Debug, Clone)]
+pub struct SyncSchedulerConfig {
+    /// Quiet period after the last queued event before syncing
+    pub debounce: Duration,
+    /// Longest events are held back while more keep arriving
+    pub max_delay: Duration,
+    /// Queue length that is synced without waiting
+    pub max_batch: usize,
+}
+
+impl Default for SyncSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_secs(AUTO_SYNC_DELAY_SECS),
+            max_delay: MAX_SYNC_DELAY,
+            max_batch: MAX_BATCH_SIZE,
+        }
+    }
+}
+
+/// Why a background sync runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncCause {
+    /// Queued events have settled
+    Events,
+    /// Enough events are queued to fill a batch
+    FullBatch,
+    /// A failed sync is due to be retried
+    Retry,
+    /// The server can be reached again after being offline
+    Reconnected,
+    /// Something else asked for a sync, e.g. the game exiting
+    Requested(&'static str),
+}
+
+impl SyncCause {
+    /// Name used in logs
+    pub fn label(&self) -> &'static str {
+        match self {
+            SyncCause::Events => "Auto-sync",
+            SyncCause::FullBatch => "Full queue sync",
+            SyncCause::Retry => "Retry",
+            SyncCause::Reconnected => "Back online",
+            SyncCause::Requested(context) => context,
+        }
+    }
+
+    /// Whether the sync goes ahead while a retry is scheduled
+    fn overrides_backoff(&self) -> bool {
+        matches!(self, SyncCause::Retry | SyncCause::Reconnected)
+    }
+}
+
+/// How far a sync has got through the queue
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    /// Chunks sent so far
+    pub chunks: usize,
+    /// Events applied so far
+    pub synced: usize,
+    /// Events still queued
+    pub remaining: usize,
+}
+
+/// A sync that stopped on an error, with the result of the chunks sent before it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyncError {
+    #[serde(flatten)]
+    pub error: CompanionError,
+    /// Same shape as a successful result; absent if the sync never started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partial: Option<Value>,
+}
+
+impl From<CompanionError> for SyncError {
+    fn from(error: CompanionError) -> Self {
+        Self {
+            error,
+            partial: None,
+        }
+    }
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+/// Something the owner of the scheduler should show
+#[derive(Debug)]
+pub enum SyncReport {
+    /// A chunk of the queue was synced
+    Progress(SyncProgress),
+    /// A background sync finished
+    Completed {
+        cause: SyncCause,
+        result: Result<Value, SyncError>,
+    },
+    /// The server became reachable or unreachable
+    ConnectivityChanged,
+}
+
+enum SyncTrigger {
+    EventQueued { pending: usize },
+    Requested(SyncCause),
+    Manual(oneshot::Sender<Result<Value, SyncError>>),
+}
+
+/// Handle for asking the sync task to sync
+#[derive(Clone)]
+pub struct SyncScheduler {
+    tx: mpsc::UnboundedSender<SyncTrigger>,
+    /// Cancels the sync in progress
+    cancel: Arc<std::sync::Mutex<CancellationToken>>,
+}
+
+impl SyncScheduler {
+    /// Create the handle and the task it feeds; the task does nothing until run
+    pub fn new(config: SyncSchedulerConfig) -> (Self, SyncActor) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cancel = Arc::new(std::sync::Mutex::new(CancellationToken::new()));
+        (
+            Self {
+                tx,
+                cancel: cancel.clone(),
+            },
+            SyncActor { config, rx, cancel },
+        )
+    }
+
+    /// Stop the sync in progress; the request in flight is abandoned and its
+    /// events stay queued
+    pub fn cancel(&self) {
+        info!("Sync cancelled");
+        self.cancel.lock().unwrap().cancel();
+    }
+
+    /// An event was queued, leaving `pending` events in the queue
+    pub fn event_queued(&self, pending: usize) {
+        let _ = self.tx.send(SyncTrigger::EventQueued { pending });
+    }
+
+    /// Sync as soon as possible, unless a retry is scheduled
+    pub fn request(&self, context: &'static str) {
+        let _ = self
+            .tx
+            .send(SyncTrigger::Requested(SyncCause::Requested(context)));
+    }
+
+    /// Flush the queue now that the server can be reached again
+    pub fn reconnected(&self) {
+        let _ = self.tx.send(SyncTrigger::Requested(SyncCause::Reconnected));
+    }
+
+    /// Sync now, after any sync in flight, and return the result
+    pub async fn sync_now(&self) -> Result<Value, SyncError> {
+        let (reply, result) = oneshot::channel();
+        self.tx
+            .send(SyncTrigger::Manual(reply))
+            .map_err(|_| CompanionError::SyncUnavailable)?;
+        result.await.map_err(|_| CompanionError::SyncUnavailable)?
+    }
+}
+
+/// The task that performs every sync
+pub struct SyncActor {
+    config: SyncSchedulerConfig,
+    rx: mpsc::UnboundedReceiver<SyncTrigger>,
+    cancel: Arc<std::sync::Mutex<CancellationToken>>,
+}
+
+impl SyncActor {
+    /// Handle triggers until every `SyncScheduler` handle is dropped
+    pub async fn run<R>(mut self, sync_manager: Arc<Mutex<SyncManager>>, report: R)
+    where
+        R: Fn(SyncReport),
+    {
+        let mut plan = SyncPlan::new(self.config.clone());
+
+        loop {
+            let deadline = plan.next_deadline(Instant::now());
+
+            tokio::select! {
+                trigger = self.rx.recv() => {
+                    let Some(trigger) = trigger else {
+                        break;
+                    };
+                    match trigger {
+                        SyncTrigger::EventQueued { pending } => {
+                            plan.record_event(pending, Instant::now());
+                        }
+                        SyncTrigger::Requested(cause) => plan.request(cause),
+                        SyncTrigger::Manual(reply) => {
+                            let cancel = self.new_cancel_token();
+                            let attempt = drain(&sync_manager, None, &cancel, &report).await;
+                            if let Some(attempt) = attempt {
+                                plan.synced(attempt.next_retry_at);
+                                if attempt.connectivity_changed {
+                                    report(SyncReport::ConnectivityChanged);
+                                }
+                                let _ = reply.send(attempt.result);
+                            }
+                        }
+                    }
+                }
+                _ = sleep_until(deadline) => {
+                    let Some(cause) = plan.take_due(Instant::now()) else {
+                        continue;
+                    };
+                    let cancel = self.new_cancel_token();
+                    let Some(attempt) = drain(&sync_manager, Some(cause), &cancel, &report).await
+                    else {
+                        continue;
+                    };
+                    plan.synced(attempt.next_retry_at);
+
+                    match &attempt.result {
+                        Ok(result) => info!("{} complete: {:?}", cause.label(), result),
+                        Err(e) => error!("{} failed: {}", cause.label(), e),
+                    }
+                    report(SyncReport::Completed {
+                        cause,
+                        result: attempt.result,
+                    });
+                    if attempt.connectivity_changed {
+                        report(SyncReport::ConnectivityChanged);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Token for the sync about to start, replacing the last one
+    fn new_cancel_token(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        *self.cancel.lock().unwrap() = token.clone();
+        token
+    }
+}
+
+/// Outcome of one sync
+struct SyncAttempt {
+    result: Result<Value, SyncError>,
+    connectivity_changed: bool,
+    next_retry_at: Option<DateTime<Utc>>,
+}
+
+/// Sync the queue chunk by chunk, holding the manager only to take each chunk and
+/// to apply the answer. Background syncs (`cause` set) are skipped when they could
+/// not succeed.
+async fn drain<R>(
+    sync_manager: &Mutex<SyncManager>,
+    cause: Option<SyncCause>,
+    cancel: &CancellationToken,
+    report: &R,
+) -> Option<SyncAttempt>
+where
+    R: Fn(SyncReport),
+{
+    let was_online = {
+        let manager = sync_manager.lock().await;
+        if let Some(cause) = cause {
+            // While offline the connectivity monitor asks for a sync once the server
+            // is back, and while a retry is scheduled it sends these events too
+            if manager.pending_count() == 0
+                || !manager.is_online()
+                || (!cause.overrides_backoff() && manager.is_backing_off())
+            {
+                return None;
+            }
+            info!(
+                "{}: syncing {} pending events",
+                cause.label(),
+                manager.pending_count()
+            );
+        }
+        manager.is_online()
+    };
+
+    let mut progress = SyncProgress {
+        chunks: 0,
+        synced: 0,
+        remaining: 0,
+    };
+    let mut errors = Vec::new();
+    let mut unlocked_quests = Vec::new();
+
+    let outcome = loop {
+        if cancel.is_cancelled() {
+            break Ok(true);
+        }
+        let batch = match sync_manager.lock().await.begin_sync() {
+            Ok(Some(batch)) => batch,
+            Ok(None) => break Ok(false),
+            Err(e) => break Err(e),
+        };
+        let response = tokio::select! {
+            _ = cancel.cancelled() => break Ok(true),
+            response = batch.send() => response,
+        };
+
+        let (result, done) = {
+            let mut manager = sync_manager.lock().await;
+            let result = manager.finish_sync(&batch, response);
+            progress.remaining = manager.pending_count();
+            // Events left queued are retried later rather than sent again straight away
+            let done = !manager.fully_acknowledged(&batch) || manager.is_backing_off();
+            (result, done)
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => break Err(e),
+        };
+
+        progress.chunks += 1;
+        progress.synced += result["synced"].as_u64().unwrap_or(0) as usize;
+        errors.extend(result["errors"].as_array().cloned().unwrap_or_default());
+        unlocked_quests.extend(
+            result["unlockedQuests"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default(),
+        );
+        report(SyncReport::Progress(progress.clone()));
+
+        if done {
+            break Ok(false);
+        }
+    };
+
+    let manager = sync_manager.lock().await;
+    let summary = |cancelled: bool| {
+        json!({
+            "synced": progress.synced,
+            "errors": errors,
+            "unlockedQuests": unlocked_quests,
+            "pendingCount": manager.pending_count(),
+            "chunks": progress.chunks,
+            "cancelled": cancelled
+        })
+    };
+    // Chunks synced before a failure are applied, so their results are kept
+    let result = match outcome {
+        Ok(cancelled) => Ok(summary(cancelled)),
+        Err(error) => Err(SyncError {
+            error,
+            partial: Some(summary(false)),
+        }),
+    };
+    Some(SyncAttempt {
+        result,
+        connectivity_changed: manager.is_online() != was_online,
+        next_retry_at: manager.next_retry_at(),
+    })
+}
+
+/// Sleep until `deadline`, or forever if there is none
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// When the next background sync is due and why
+struct SyncPlan {
+    config: SyncSchedulerConfig,
+    /// A sync that should run right away
+    requested: Option<SyncCause>,
+    /// First and latest event queued since the last sync
+    events: Option<(Instant, Instant)>,
+    retry_at: Option<Instant>,
+}
+
+impl SyncPlan {
+    fn new(config: SyncSchedulerConfig) -> Self {
+        Self {
+            config,
+            requested: None,
+            events: None,
+            retry_at: None,
+        }
+    }
+
+    /// Record a queued event, leaving `pending` events in the queue
+    fn record_event(&mut self, pending: usize, now: Instant) {
+        if pending >= self.config.max_batch {
+            self.request(SyncCause::FullBatch);
+        } else {
+            let first = self.events.map_or(now, |(first, _)| first);
+            self.events = Some((first, now));
+        }
+    }
+
+    /// Ask for a sync right away
+    fn request(&mut self, cause: SyncCause) {
+        if !self.requested.is_some_and(|c| c.overrides_backoff()) {
+            self.requested = Some(cause);
+        }
+    }
+
+    /// Record that a sync ran, and when the manager wants it retried
+    fn synced(&mut self, next_retry_at: Option<DateTime<Utc>>) {
+        self.events = None;
+        self.retry_at =
+            next_retry_at.map(|at| Instant::now() + (at - Utc::now()).to_std().unwrap_or_default());
+    }
+
+    /// When the next sync is due, if one is planned
+    fn next_deadline(&self, now: Instant) -> Option<Instant> {
+        if self.requested.is_some() {
+            return Some(now);
+        }
+        let events = self
+            .events
+            .map(|(first, last)| (last + self.config.debounce).min(first + self.config.max_delay));
+        events.into_iter().chain(self.retry_at).min()
+    }
+
+    /// Take the sync that is due at `now`; it sends everything queued, so every
+    /// other reason to sync is dropped with it
+    fn take_due(&mut self, now: Instant) -> Option<SyncCause> {
+        let requested = self.requested.take();
+        let retry_due = self.retry_at.is_some_and(|at| at <= now);
+        let events_due = self
+            .next_deadline(now)
+            .is_some_and(|deadline| deadline <= now);
+
+        let cause = if requested.is_some_and(|c| c.overrides_backoff()) {
+            requested
+        } else if retry_due {
+            Some(SyncCause::Retry)
+        } else if requested.is_some() {
+            requested
+        } else if events_due {
+            Some(SyncCause::Events)
+        } else {
+            None
+        };
+
+        if cause.is_some() {
+            self.events = None;
+            if retry_due {
+                self.retry_at = None;
+            }
+        }
+        cause
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{backlog, manager_with_backlog, mock_sync_server, MockSync};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config() -> SyncSchedulerConfig {
+        SyncSchedulerConfig {
+            debounce: Duration::from_secs(5),
+            max_delay: Duration::from_secs(30),
+            max_batch: MAX_BATCH_SIZE,
+        }
+    }
+
+    #[test]
+    fn test_burst_of_events_syncs_once() {
+        let start = Instant::now();
+        let mut plan = SyncPlan::new(config());
+
+        for i in 0..10 {
+            plan.record_event(i + 1, start + Duration::from_millis(i as u64 * 100));
+        }
+
+        let settled = start + Duration::from_millis(900) + Duration::from_secs(5);
+        assert_eq!(plan.next_deadline(start), Some(settled));
+        assert_eq!(plan.take_due(settled - Duration::from_millis(1)), None);
+        assert_eq!(plan.take_due(settled), Some(SyncCause::Events));
+        assert_eq!(plan.next_deadline(settled), None);
+
+        // Events that never stop arriving are not held back forever
+        for i in 0..100 {
+            plan.record_event(1, start + Duration::from_secs(i));
+        }
+        assert_eq!(
+            plan.next_deadline(start),
+            Some(start + Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_full_queue_and_requests_sync_at_once() {
+        let start = Instant::now();
+        let mut plan = SyncPlan::new(config());
+
+        plan.record_event(1, start);
+        plan.record_event(100, start);
+        assert_eq!(plan.next_deadline(start), Some(start));
+        assert_eq!(plan.take_due(start), Some(SyncCause::FullBatch));
+        // The queued events went out with the full batch
+        assert_eq!(plan.next_deadline(start), None);
+
+        plan.request(SyncCause::Reconnected);
+        plan.request(SyncCause::Requested("Sync after game exit"));
+        assert_eq!(plan.take_due(start), Some(SyncCause::Reconnected));
+    }
+
+    #[test]
+    fn test_due_retry_wins_over_other_causes() {
+        let start = Instant::now();
+        let mut plan = SyncPlan::new(config());
+        plan.retry_at = Some(start);
+
+        plan.request(SyncCause::Requested("Sync after game exit"));
+        assert_eq!(plan.take_due(start), Some(SyncCause::Retry));
+        assert_eq!(plan.retry_at, None);
+    }
+
+    /// Manager for `url` with `count` queued events, shared with the scheduler
+    fn shared_backlog(url: String, count: usize) -> Arc<Mutex<SyncManager>> {
+        Arc::new(Mutex::new(manager_with_backlog(url, count)))
+    }
+
+    #[tokio::test]
+    async fn test_status_reads_do_not_wait_for_sync() {
+        let (url, requests) = mock_sync_server(MockSync {
+            delay: Duration::from_millis(500),
+            ..MockSync::default()
+        })
+        .await;
+        let sync_manager = shared_backlog(url, 0);
+
+        let (scheduler, actor) = SyncScheduler::new(SyncSchedulerConfig {
+            debounce: Duration::from_millis(50),
+            ..config()
+        });
+        let reports = Arc::new(AtomicUsize::new(0));
+        let completed = reports.clone();
+        tokio::spawn(actor.run(sync_manager.clone(), move |report| {
+            if let SyncReport::Completed { result, .. } = report {
+                assert!(result.is_ok());
+                completed.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+
+        for event in backlog(10) {
+            let pending = {
+                let mut manager = sync_manager.lock().await;
+                manager.import_queue(vec![event]).unwrap();
+                manager.pending_count()
+            };
+            scheduler.event_queued(pending);
+        }
+
+        // Wait for the batch to be sent, then read the status while it is in flight
+        while requests.lock().unwrap().batches.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let status = tokio::time::timeout(Duration::from_millis(100), async {
+            sync_manager.lock().await.get_status()
+        })
+        .await
+        .expect("status read waited for the sync");
+        assert_eq!(status["pendingCount"], 10);
+
+        // A manual sync runs after the one in flight, and finds nothing left
+        let result = scheduler.sync_now().await.unwrap();
+        assert_eq!(result["synced"], 0);
+        assert_eq!(requests.lock().unwrap().batches.len(), 1);
+        assert_eq!(reports.load(Ordering::SeqCst), 1);
+        assert_eq!(sync_manager.lock().await.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_backlog_drains_in_ordered_chunks() {
+        let (url, requests) = mock_sync_server(MockSync::default()).await;
+        let sync_manager = shared_backlog(url, 350);
+
+        let (scheduler, actor) = SyncScheduler::new(config());
+        let progress = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reported = progress.clone();
+        tokio::spawn(actor.run(sync_manager.clone(), move |report| {
+            if let SyncReport::Progress(progress) = report {
+                reported.lock().unwrap().push(progress.remaining);
+            }
+        }));
+
+        let result = scheduler.sync_now().await.unwrap();
+        assert_eq!(result["synced"], 350);
+        assert_eq!(result["chunks"], 4);
+        assert_eq!(result["pendingCount"], 0);
+        assert_eq!(*progress.lock().unwrap(), [250, 150, 50, 0]);
+
+        let requests = &requests.lock().unwrap().batches;
+        let sizes: Vec<usize> = requests.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [100, 100, 100, 50]);
+        let sent: Vec<String> = requests.concat();
+        let expected: Vec<String> = (0..350).map(|i| format!("event{}", i)).collect();
+        assert_eq!(sent, expected);
+    }
+
+    #[tokio::test]
+    async fn test_drain_stops_on_first_failed_chunk() {
+        let (url, requests) = mock_sync_server(MockSync {
+            status: "401 Unauthorized",
+            ..MockSync::default()
+        })
+        .await;
+        let sync_manager = shared_backlog(url, 350);
+
+        let (scheduler, actor) = SyncScheduler::new(config());
+        tokio::spawn(actor.run(sync_manager.clone(), |_| {}));
+
+        let err = scheduler.sync_now().await.unwrap_err();
+        assert_eq!(err.error, CompanionError::Unauthorized);
+        assert_eq!(requests.lock().unwrap().batches.len(), 1);
+        assert_eq!(sync_manager.lock().await.pending_count(), 350);
+    }
+
+    #[tokio::test]
+    async fn test_failed_drain_reports_chunks_already_synced() {
+        let (url, requests) = mock_sync_server(MockSync {
+            status: "401 Unauthorized",
+            ok_batches: 2,
+            ..MockSync::default()
+        })
+        .await;
+        let sync_manager = shared_backlog(url, 350);
+
+        let (scheduler, actor) = SyncScheduler::new(config());
+        tokio::spawn(actor.run(sync_manager.clone(), |_| {}));
+
+        let err = scheduler.sync_now().await.unwrap_err();
+        assert_eq!(err.error, CompanionError::Unauthorized);
+        let partial = err.partial.unwrap();
+        assert_eq!(partial["synced"], 200);
+        assert_eq!(partial["chunks"], 2);
+        assert_eq!(partial["pendingCount"], 150);
+        assert_eq!(requests.lock().unwrap().batches.len(), 3);
+
+        let json = serde_json::to_value(SyncError {
+            error: CompanionError::Unauthorized,
+            partial: Some(partial),
+        })
+        .unwrap();
+        assert_eq!(json["code"], "unauthorized");
+        assert_eq!(json["partial"]["synced"], 200);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_drain() {
+        let (url, requests) = mock_sync_server(MockSync {
+            delay: Duration::from_secs(10),
+            ..MockSync::default()
+        })
+        .await;
+        let sync_manager = shared_backlog(url, 350);
+
+        let (scheduler, actor) = SyncScheduler::new(config());
+        tokio::spawn(actor.run(sync_manager.clone(), |_| {}));
+
+        let sync = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.sync_now().await }
+        });
+        while requests.lock().unwrap().batches.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        scheduler.cancel();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), sync)
+            .await
+            .expect("cancel did not stop the sync")
+            .unwrap()
+            .unwrap();
+        assert_eq!(result["cancelled"], true);
+        assert_eq!(result["pendingCount"], 350);
+        assert_eq!(requests.lock().unwrap().batches.len(), 1);
+
+        // A cancel only stops the sync it interrupted
+        let (url, _) = mock_sync_server(MockSync::default()).await;
+        sync_manager.lock().await.set_api_base(url);
+        let result = scheduler.sync_now().await.unwrap();
+        assert_eq!(result["cancelled"], false);
+        assert_eq!(result["pendingCount"], 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Test Server
+//!
+//! Local HTTP servers standing in for the EFT Tracker API in tests:
+//! 1. `mock_sync_server` records each batch and accepts all its events
+//! 2. `mock_error_server` answers every request with a fixed status
+//! 3. `backlog` and `manager_with_backlog` build queued events to send them
+
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::sync_manager::{SyncEvent, SyncManager};
+
+/// Read an HTTP request, returning its lowercased head and its body
+pub async fn read_request(socket: &mut TcpStream) -> (String, String) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        let Some((head, body)) = text.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let head = head.to_lowercase();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:")?.trim().parse().ok())
+            .unwrap_or(0);
+        if body.len() >= length {
+            return (head, body.to_string());
+        }
+    }
+}
+
+/// Local server answering every request with `status` and extra `headers`
+pub async fn mock_error_server(status: &'static str, headers: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            read_request(&mut socket).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+                status, headers
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    url
+}
+
+/// How the mock sync server answers
+#[derive(Clone, Copy)]
+pub struct MockSync {
+    pub status: &'static str,
+    /// Answer this many first requests with 200 before switching to `status`
+    pub ok_batches: usize,
+    /// Wait before answering each request
+    pub delay: Duration,
+    /// Drop the connection instead of answering this many first requests
+    pub drop_responses: usize,
+}
+
+impl Default for MockSync {
+    fn default() -> Self {
+        Self {
+            status: "200 OK",
+            ok_batches: 0,
+            delay: Duration::ZERO,
+            drop_responses: 0,
+        }
+    }
+}
+
+/// What the mock sync server saw
+#[derive(Default)]
+pub struct MockServerLog {
+    pub batch_keys: Vec<String>,
+    /// Event ids of each request, in the order received
+    pub batches: Vec<Vec<String>>,
+}
+
+/// Local sync endpoint that records each batch and answers as `mock` says,
+/// accepting every event when the status is 200
+pub async fn mock_sync_server(mock: MockSync) -> (String, Arc<Mutex<MockServerLog>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let log = Arc::new(Mutex::new(MockServerLog::default()));
+    let server_log = log.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let (head, body) = read_request(&mut socket).await;
+
+            let batch: Value = serde_json::from_str(&body).unwrap();
+            let ids: Vec<String> = batch["events"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["eventId"].as_str().unwrap().to_string())
+                .collect();
+
+            let (drop_response, status) = {
+                let mut log = server_log.lock().unwrap();
+                log.batch_keys.extend(
+                    head.lines()
+                        .find_map(|line| line.strip_prefix("idempotency-key:"))
+                        .map(|key| key.trim().to_string()),
+                );
+                log.batches.push(ids.clone());
+                let sent = log.batches.len();
+                let status = if sent <= mock.ok_batches {
+                    "200 OK"
+                } else {
+                    mock.status
+                };
+                (sent <= mock.drop_responses, status)
+            };
+            if drop_response {
+                // Committed, but the client never hears back
+                continue;
+            }
+
+            let body = if status.starts_with("200") {
+                json!({
+                    "synced": ids.len(),
+                    "errors": [],
+                    "unlockedQuests": [],
+                    "accepted": ids,
+                    "rejected": [],
+                    "retryable": []
+                })
+                .to_string()
+            } else {
+                String::new()
+            };
+
+            tokio::time::sleep(mock.delay).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            // The client may have given up on the request
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+
+    (url, log)
+}
+
+/// `count` finished quests, with ids `event0..` and `quest0..`
+pub fn backlog(count: usize) -> Vec<SyncEvent> {
+    (0..count)
+        .map(|i| SyncEvent {
+            event_id: format!("event{}", i),
+            quest_id: format!("quest{}", i),
+            status: "FINISHED".to_string(),
+            timestamp: "2024-01-15T10:30:00Z".to_string(),
+            game_version: None,
+        })
+        .collect()
+}
+
+/// Manager for `url` with a token and `count` queued events
+pub fn manager_with_backlog(url: String, count: usize) -> SyncManager {
+    let mut manager = SyncManager::new(url);
+    manager.set_token("cmp_test123".to_string()).unwrap();
+    manager.import_queue(backlog(count)).unwrap();
+    manager
+}
@@ -0,0 +1,594 @@
+//! Token Vault
+//!
+//! Keeps the companion token, a bearer secret, out of the settings file:
+//! 1. The OS credential store holds it (Credential Manager, Secret Service, Keychain).
+//!    At login the store may still be starting, so it is retried briefly
+//! 2. Without one, e.g. on a headless Linux box, it is written to a file encrypted
+//!    with ChaCha20-Poly1305 under a random key kept beside it (owner-only on Unix).
+//!    This keeps the token out of plain view and out of settings attached to bug
+//!    reports, but is not a security boundary: anyone who can read the whole app
+//!    data folder can decrypt it
+//! 3. A token in the file was written while the credential store was down, so it
+//!    is newer and moves into the store once the store is back
+//! 4. A stored token that cannot be read is reported as an error, not as unlinked
+//! 5. A plaintext token left in an older settings file is moved into the vault
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use log::{info, warn};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::CompanionError;
+use crate::settings::SettingsStore;
+
+/// Service and account the token is filed under in the OS credential store
+const KEYRING_SERVICE: &str = "eft-tracker-companion";
+const KEYRING_ACCOUNT: &str = "companion-token";
+
+/// First byte of the encrypted token file, so the format can change later
+const FILE_FORMAT: u8 = 1;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// Waits between attempts to reach a credential store that is still starting
+const KEYRING_RETRY_DELAYS: &[Duration] = &[
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+];
+
+/// Somewhere the token can be kept
+pub trait VaultBackend: Send {
+    /// Short name for logs and the sync status
+    fn name(&self) -> &'static str;
+    fn load(&self) -> Result<Option<String>, CompanionError>;
+    fn store(&self, token: &str) -> Result<(), CompanionError>;
+    fn clear(&self) -> Result<(), CompanionError>;
+}
+
+/// Keeps the token for this run only
+pub struct MemoryBackend;
+
+impl VaultBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn load(&self) -> Result<Option<String>, CompanionError> {
+        Ok(None)
+    }
+
+    fn store(&self, _token: &str) -> Result<(), CompanionError> {
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), CompanionError> {
+        Ok(())
+    }
+}
+
+/// The OS credential store
+pub struct KeyringBackend {
+    entry: keyring::Entry,
+}
+
+impl KeyringBackend {
+    /// Connect to the credential store, or `None` if there is none running.
+    /// A store that is not answering yet, e.g. Secret Service at login, is retried.
+    pub fn open() -> Option<Self> {
+        let mut delays = KEYRING_RETRY_DELAYS.iter();
+        loop {
+            let error = match Self::connect() {
+                Ok(backend) => return Some(backend),
+                Err(e) => e,
+            };
+            let retryable = matches!(
+                error,
+                keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_)
+            );
+            match delays.next() {
+                Some(delay) if retryable => {
+                    info!("OS credential store not ready ({}), retrying", error);
+                    std::thread::sleep(*delay);
+                }
+                _ => {
+                    warn!("OS credential store unavailable: {}", error);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn connect() -> Result<Self, keyring::Error> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)?;
+
+        // Creating an entry never fails on Linux; a read tells whether Secret Service answers
+        match entry.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(Self { entry }),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl VaultBackend for KeyringBackend {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn load(&self) -> Result<Option<String>, CompanionError> {
+        match self.entry.get_password() {
+            Ok(token) => Ok(Some(token)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn store(&self, token: &str) -> Result<(), CompanionError> {
+        Ok(self.entry.set_password(token)?)
+    }
+
+    fn clear(&self) -> Result<(), CompanionError> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Token file encrypted with a key file next to it
+pub struct EncryptedFileBackend {
+    path: PathBuf,
+    key_path: PathBuf,
+}
+
+impl EncryptedFileBackend {
+    /// Keep the token in `dir`, creating the key on first store
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join("companion_token.vault"),
+            key_path: dir.join("companion_token.key"),
+        }
+    }
+
+    fn read_key(&self) -> Result<Option<ChaCha20Poly1305>, CompanionError> {
+        let bytes = match fs::read(&self.key_path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if bytes.len() != KEY_LEN {
+            return Err(CompanionError::Vault(
+                "token key file is damaged".to_string(),
+            ));
+        }
+        Ok(Some(ChaCha20Poly1305::new(Key::from_slice(&bytes))))
+    }
+
+    fn read_or_create_key(&self) -> Result<ChaCha20Poly1305, CompanionError> {
+        if let Some(cipher) = self.read_key()? {
+            return Ok(cipher);
+        }
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        write_private(&self.key_path, &key)?;
+        Ok(ChaCha20Poly1305::new(&key))
+    }
+}
+
+impl VaultBackend for EncryptedFileBackend {
+    fn name(&self) -> &'static str {
+        "encrypted-file"
+    }
+
+    fn load(&self) -> Result<Option<String>, CompanionError> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let undecryptable =
+            || CompanionError::Vault("stored token cannot be decrypted".to_string());
+        let cipher = self.read_key()?.ok_or_else(undecryptable)?;
+        if content.len() < 1 + NONCE_LEN || content[0] != FILE_FORMAT {
+            return Err(undecryptable());
+        }
+        let (nonce, ciphertext) = content[1..].split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| undecryptable())?;
+        String::from_utf8(plaintext)
+            .map(Some)
+            .map_err(|_| undecryptable())
+    }
+
+    fn store(&self, token: &str) -> Result<(), CompanionError> {
+        let cipher = self.read_or_create_key()?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, token.as_bytes())
+            .map_err(|_| CompanionError::Vault("token could not be encrypted".to_string()))?;
+
+        let mut content = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+        content.push(FILE_FORMAT);
+        content.extend_from_slice(&nonce);
+        content.extend_from_slice(&ciphertext);
+        Ok(write_private(&self.path, &content)?)
+    }
+
+    fn clear(&self) -> Result<(), CompanionError> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Write a file only its owner can read, atomically (temp file, then rename)
+fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let _ = fs::remove_file(&tmp_path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// The companion token, read once from its backend and written through on change
+pub struct TokenVault {
+    backend: Box<dyn VaultBackend>,
+    token: Option<String>,
+    /// Why the stored token could not be read; cleared by storing a new one
+    load_error: Option<CompanionError>,
+}
+
+impl TokenVault {
+    /// A vault that forgets the token on exit
+    pub fn in_memory() -> Self {
+        Self::new(Box::new(MemoryBackend))
+    }
+
+    /// Use the OS credential store, or an encrypted file in `dir` if there is none
+    pub fn open(dir: &Path) -> Self {
+        let keyring = KeyringBackend::open().map(|k| Box::new(k) as Box<dyn VaultBackend>);
+        let vault = Self::open_with(keyring, Box::new(EncryptedFileBackend::new(dir)));
+        info!("Companion token stored in {}", vault.backend_name());
+        vault
+    }
+
+    /// Use `store` if there is one, moving a token out of `file` into it, else `file`
+    fn open_with(store: Option<Box<dyn VaultBackend>>, file: Box<dyn VaultBackend>) -> Self {
+        let Some(store) = store else {
+            return Self::new(file);
+        };
+
+        // The file is only written while the store is down, so its token is newer
+        let token = match file.load() {
+            Ok(Some(token)) => token,
+            Ok(None) => return Self::new(store),
+            Err(e) => {
+                warn!(
+                    "Could not read the companion token from {}: {}",
+                    file.name(),
+                    e
+                );
+                return Self::new(store);
+            }
+        };
+        if let Err(e) = store.store(&token) {
+            warn!(
+                "Could not move the companion token into {}: {}",
+                store.name(),
+                e
+            );
+            return Self::new(file);
+        }
+        if let Err(e) = file.clear() {
+            warn!(
+                "Could not remove the companion token from {}: {}",
+                file.name(),
+                e
+            );
+        }
+        info!(
+            "Moved the companion token from {} into {}",
+            file.name(),
+            store.name()
+        );
+        Self {
+            backend: store,
+            token: Some(token),
+            load_error: None,
+        }
+    }
+
+    /// Load the token from `backend`. A token that cannot be read is kept as an
+    /// error, so it is not mistaken for an unlinked account.
+    pub fn new(backend: Box<dyn VaultBackend>) -> Self {
+        let (token, load_error) = match backend.load() {
+            Ok(token) => (token, None),
+            Err(e) => {
+                warn!("Could not read the stored companion token: {}", e);
+                (None, Some(e))
+            }
+        };
+        Self {
+            backend,
+            token,
+            load_error,
+        }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    pub fn get(&self) -> Option<&String> {
+        self.token.as_ref()
+    }
+
+    /// Why the stored token could not be read, if it could not
+    pub fn load_error(&self) -> Option<&CompanionError> {
+        self.load_error.as_ref()
+    }
+
+    /// The token to send, or why there is none
+    pub fn require(&self) -> Result<&String, CompanionError> {
+        match (&self.token, &self.load_error) {
+            (Some(token), _) => Ok(token),
+            (None, Some(e)) => Err(e.clone()),
+            (None, None) => Err(CompanionError::NoToken),
+        }
+    }
+
+    /// Store a new token. It is used for this run even if it could not be persisted.
+    pub fn set(&mut self, token: String) -> Result<(), CompanionError> {
+        let stored = self.backend.store(&token);
+        self.token = Some(token);
+        self.load_error = None;
+        stored
+    }
+
+    /// Forget the token, e.g. when the account is unlinked
+    pub fn clear(&mut self) -> Result<(), CompanionError> {
+        self.token = None;
+        self.load_error = None;
+        self.backend.clear()
+    }
+}
+
+/// Move a plaintext token from the settings file into the vault, then rewrite the
+/// settings without it. The plaintext copy is kept if the vault cannot store it.
+pub fn migrate_plaintext_token(settings: &mut SettingsStore, vault: &mut TokenVault) {
+    if !settings.has_plaintext_files() {
+        return;
+    }
+
+    // A stored token that cannot be read may be newer; keep the plaintext copy
+    // until it can be compared
+    if let Some(e) = vault.load_error() {
+        warn!(
+            "Keeping the companion token in settings, the vault is unreadable: {}",
+            e
+        );
+        return;
+    }
+
+    // A token linked since the settings were written is newer; keep it
+    if let Some(token) = settings.plaintext_token().map(str::to_string) {
+        if vault.get().is_none() {
+            if let Err(e) = vault.set(token) {
+                warn!("Could not move the companion token out of settings: {}", e);
+                return;
+            }
+        }
+    }
+
+    match settings.forget_plaintext_token() {
+        Ok(()) => info!(
+            "Moved the companion token out of settings into {}",
+            vault.backend_name()
+        ),
+        Err(e) => warn!("Could not remove the companion token from settings: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_vault(dir: &Path) -> TokenVault {
+        TokenVault::new(Box::new(EncryptedFileBackend::new(dir)))
+    }
+
+    /// Backend sharing its token with the test; `fails` makes every call an error
+    #[derive(Clone, Default)]
+    struct SharedBackend {
+        token: std::sync::Arc<std::sync::Mutex<Option<String>>>,
+        fails: bool,
+    }
+
+    impl SharedBackend {
+        fn holding(token: &str) -> Self {
+            let backend = Self::default();
+            *backend.token.lock().unwrap() = Some(token.to_string());
+            backend
+        }
+
+        fn check(&self) -> Result<(), CompanionError> {
+            match self.fails {
+                true => Err(CompanionError::Vault("locked".to_string())),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl VaultBackend for SharedBackend {
+        fn name(&self) -> &'static str {
+            "shared"
+        }
+
+        fn load(&self) -> Result<Option<String>, CompanionError> {
+            self.check()?;
+            Ok(self.token.lock().unwrap().clone())
+        }
+
+        fn store(&self, token: &str) -> Result<(), CompanionError> {
+            self.check()?;
+            *self.token.lock().unwrap() = Some(token.to_string());
+            Ok(())
+        }
+
+        fn clear(&self) -> Result<(), CompanionError> {
+            self.check()?;
+            *self.token.lock().unwrap() = None;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encrypted_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut vault = file_vault(dir.path());
+        assert_eq!(vault.get(), None);
+        vault.set("cmp_secret123".to_string()).unwrap();
+
+        // Only ciphertext reaches the disk
+        let content = fs::read(dir.path().join("companion_token.vault")).unwrap();
+        assert!(!String::from_utf8_lossy(&content).contains("cmp_secret123"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let key = fs::metadata(dir.path().join("companion_token.key")).unwrap();
+            assert_eq!(key.permissions().mode() & 0o777, 0o600);
+        }
+
+        let restored = file_vault(dir.path());
+        assert_eq!(restored.get().map(String::as_str), Some("cmp_secret123"));
+
+        let mut restored = restored;
+        restored.clear().unwrap();
+        assert_eq!(file_vault(dir.path()).get(), None);
+    }
+
+    #[test]
+    fn test_file_token_moves_into_the_credential_store() {
+        let store = SharedBackend::holding("cmp_old");
+        let file = SharedBackend::holding("cmp_new");
+        let vault = TokenVault::open_with(Some(Box::new(store.clone())), Box::new(file.clone()));
+        assert_eq!(vault.get().map(String::as_str), Some("cmp_new"));
+        assert_eq!(store.token.lock().unwrap().as_deref(), Some("cmp_new"));
+        assert_eq!(*file.token.lock().unwrap(), None);
+
+        // Nothing in the file leaves the store's token in use
+        let vault = TokenVault::open_with(Some(Box::new(store)), Box::new(file.clone()));
+        assert_eq!(vault.get().map(String::as_str), Some("cmp_new"));
+
+        // A store that refuses the token leaves it in the file
+        let file = SharedBackend::holding("cmp_file");
+        let locked = SharedBackend {
+            fails: true,
+            ..SharedBackend::default()
+        };
+        let vault = TokenVault::open_with(Some(Box::new(locked)), Box::new(file.clone()));
+        assert_eq!(vault.get().map(String::as_str), Some("cmp_file"));
+        assert_eq!(file.token.lock().unwrap().as_deref(), Some("cmp_file"));
+    }
+
+    #[test]
+    fn test_unreadable_token_is_not_unlinked() {
+        let locked = SharedBackend {
+            fails: true,
+            ..SharedBackend::holding("cmp_secret123")
+        };
+        let mut vault = TokenVault::new(Box::new(locked));
+        assert_eq!(vault.get(), None);
+        assert_eq!(vault.require().unwrap_err().code(), "vault");
+
+        let dir = tempfile::tempdir().unwrap();
+        let settings_path = dir.path().join("companion_settings.json");
+        fs::write(
+            &settings_path,
+            r#"{"version": 1, "settings": {"companionToken": "cmp_plain"}}"#,
+        )
+        .unwrap();
+        let mut settings = SettingsStore::load(settings_path, &dir.path().join("none"));
+        migrate_plaintext_token(&mut settings, &mut vault);
+        assert_eq!(settings.plaintext_token(), Some("cmp_plain"));
+
+        assert_eq!(
+            TokenVault::in_memory().require().unwrap_err(),
+            CompanionError::NoToken
+        );
+    }
+
+    #[test]
+    fn test_tampered_or_keyless_token_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        file_vault(dir.path())
+            .set("cmp_secret123".to_string())
+            .unwrap();
+
+        let path = dir.path().join("companion_token.vault");
+        let mut content = fs::read(&path).unwrap();
+        *content.last_mut().unwrap() ^= 1;
+        fs::write(&path, &content).unwrap();
+        let vault = file_vault(dir.path());
+        assert_eq!(vault.get(), None);
+        assert!(vault.load_error().is_some());
+
+        file_vault(dir.path())
+            .set("cmp_secret123".to_string())
+            .unwrap();
+        fs::remove_file(dir.path().join("companion_token.key")).unwrap();
+        let vault = file_vault(dir.path());
+        assert_eq!(vault.get(), None);
+        assert!(vault.load_error().is_some());
+    }
+
+    #[test]
+    fn test_migrates_plaintext_token_out_of_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings_path = dir.path().join("companion_settings.json");
+        fs::write(
+            &settings_path,
+            r#"{"version": 1, "settings": {"companionToken": "cmp_plain", "autoStart": true}}"#,
+        )
+        .unwrap();
+
+        let mut settings = SettingsStore::load(settings_path.clone(), &dir.path().join("none"));
+        let mut vault = file_vault(dir.path());
+        migrate_plaintext_token(&mut settings, &mut vault);
+
+        assert_eq!(vault.get().map(String::as_str), Some("cmp_plain"));
+        assert_eq!(settings.plaintext_token(), None);
+        let content = fs::read_to_string(&settings_path).unwrap();
+        assert!(!content.contains("cmp_plain"));
+        assert!(settings.get().auto_start);
+
+        // The token survives a restart from the vault alone
+        assert_eq!(
+            file_vault(dir.path()).get().map(String::as_str),
+            Some("cmp_plain")
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! Watcher Supervisor
+//!
+//! Owns the `LogWatcher` lifecycle behind an explicit state machine:
+//! waits for the game install and its Logs folder (the profiles folder for SPT),
+//! starts the watcher, notices when it dies or the folder disappears, and restarts
+//! it with backoff.
+//! State changes are published on a `watch` channel for the tray and frontend.
+
+use log::{info, warn};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::eft_detector::{game_mode, GameMode, SptPaths};
+use crate::error::CompanionError;
+use crate::log_watcher::{LogWatcher, QuestEvent};
+
+/// Supervisor state as shown in the tray and frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WatcherState {
+    /// Not supervising (never started or stopped by the user)
+    Idle,
+    /// No EFT installation found yet
+    WaitingForGame,
+    /// Installation found but its Logs folder does not exist yet
+    WaitingForLogs,
+    /// Watcher running
+    Watching,
+    /// Watcher died or lost its folder; restarting with backoff
+    Degraded,
+    /// Watcher could not be started; retrying with backoff
+    Error,
+}
+
+impl WatcherState {
+    /// Short label for the tray tooltip
+    pub fn label(&self) -> &'static str {
+        match self {
+            WatcherState::Idle => "Idle",
+            WatcherState::WaitingForGame => "Waiting for game",
+            WatcherState::WaitingForLogs => "Waiting for logs",
+            WatcherState::Watching => "Watching",
+            WatcherState::Degraded => "Reconnecting",
+            WatcherState::Error => "Error",
+        }
+    }
+}
+
+/// Payload of the `watcher-state` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatcherStatus {
+    pub state: WatcherState,
+    /// Why the watcher is not running, if it should be
+    pub error: Option<CompanionError>,
+    pub logs_path: Option<String>,
+    pub restarts: u32,
+}
+
+impl WatcherStatus {
+    fn idle() -> Self {
+        Self {
+            state: WatcherState::Idle,
+            error: None,
+            logs_path: None,
+            restarts: 0,
+        }
+    }
+}
+
+/// Timing knobs for the supervisor loop
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// How often to look again while waiting for the game or its Logs folder
+    pub wait_interval: Duration,
+    /// How often a running watcher is checked
+    pub health_check_interval: Duration,
+    /// First restart delay after a failure
+    pub restart_backoff_initial: Duration,
+    /// Restart delay ceiling
+    pub restart_backoff_max: Duration,
+    /// A watcher that stays healthy this long resets the backoff
+    pub backoff_reset_after: Duration,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            wait_interval: Duration::from_secs(5),
+            health_check_interval: Duration::from_secs(2),
+            restart_backoff_initial: Duration::from_secs(1),
+            restart_backoff_max: Duration::from_secs(60),
+            backoff_reset_after: Duration::from_secs(300),
+        }
+    }
+}
+
+type EftPathResolver = Arc<dyn Fn() -> Option<String> + Send + Sync>;
+type EventCallback = Arc<dyn Fn(QuestEvent) + Send + Sync>;
+
+pub struct WatcherSupervisor {
+    config: SupervisorConfig,
+    status_tx: watch::Sender<WatcherStatus>,
+    cancel: Option<CancellationToken>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl WatcherSupervisor {
+    pub fn new(config: SupervisorConfig) -> Self {
+        let (status_tx, _) = watch::channel(WatcherStatus::idle());
+        Self {
+            config,
+            status_tx,
+            cancel: None,
+            task: None,
+        }
+    }
+
+    /// Subscribe to state changes
+    pub fn subscribe(&self) -> watch::Receiver<WatcherStatus> {
+        self.status_tx.subscribe()
+    }
+
+    /// Current status
+    pub fn status(&self) -> WatcherStatus {
+        self.status_tx.borrow().clone()
+    }
+
+    /// Whether supervision is running (regardless of whether the watcher is up)
+    pub fn is_active(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| !task.is_finished())
+    }
+
+    /// Start supervising. Does nothing if already active.
+    /// Must be called from within a tokio runtime.
+    pub fn start<R, F>(&mut self, resolve_eft_path: R, on_event: F)
+    where
+        R: Fn() -> Option<String> + Send + Sync + 'static,
+        F: Fn(QuestEvent) + Send + Sync + 'static,
+    {
+        if self.is_active() {
+            return;
+        }
+
+        let cancel = CancellationToken::new();
+        self.task = Some(tokio::spawn(Self::supervise(
+            self.config.clone(),
+            Arc::new(resolve_eft_path),
+            Arc::new(on_event),
+            self.status_tx.clone(),
+            cancel.clone(),
+        )));
+        self.cancel = Some(cancel);
+    }
+
+    /// Stop supervising, stopping the watcher and waiting for it to exit
+    pub async fn stop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel.cancel();
+        }
+        if let Some(task) = self.task.take() {
+            if let Err(e) = task.await {
+                warn!("Watcher supervisor ended abnormally: {}", e);
+            }
+        }
+        self.status_tx.send_replace(WatcherStatus::idle());
+    }
+
+    async fn supervise(
+        config: SupervisorConfig,
+        resolve_eft_path: EftPathResolver,
+        on_event: EventCallback,
+        status_tx: watch::Sender<WatcherStatus>,
+        cancel: CancellationToken,
+    ) {
+        let mut backoff = config.restart_backoff_initial;
+        let mut restarts = 0;
+
+        let publish = |state: WatcherState,
+                       error: Option<CompanionError>,
+                       logs_path: Option<&Path>,
+                       restarts: u32| {
+            let status = WatcherStatus {
+                state,
+                error,
+                logs_path: logs_path.map(|p| p.to_string_lossy().to_string()),
+                restarts,
+            };
+            status_tx.send_if_modified(|current| {
+                if *current == status {
+                    return false;
+                }
+                info!("Watcher state: {:?}", status.state);
+                *current = status;
+                true
+            });
+        };
+
+        loop {
+            let Some(eft_path) = resolve_eft_path() else {
+                publish(
+                    WatcherState::WaitingForGame,
+                    Some(CompanionError::InstallNotFound),
+                    None,
+                    restarts,
+                );
+                if Self::wait(&cancel, config.wait_interval).await {
+                    break;
+                }
+                continue;
+            };
+
+            let install = PathBuf::from(&eft_path);
+            let mode = game_mode(&install);
+            let logs_path = match mode {
+                GameMode::Live => install.join("Logs"),
+                GameMode::Spt => SptPaths::new(&install).profiles,
+            };
+            if !logs_path.is_dir() {
+                publish(
+                    WatcherState::WaitingForLogs,
+                    Some(CompanionError::LogsMissing(logs_path.clone())),
+                    Some(&logs_path),
+                    restarts,
+                );
+                if Self::wait(&cancel, config.wait_interval).await {
+                    break;
+                }
+                continue;
+            }
+
+            let callback = on_event.clone();
+            let watcher = match mode {
+                GameMode::Live => {
+                    LogWatcher::new(logs_path.to_string_lossy().to_string(), move |event| {
+                        callback(event)
+                    })
+                    .await
+                }
+                GameMode::Spt => LogWatcher::new_spt(eft_path, move |event| callback(event)).await,
+            };
+            match watcher {
+                Ok(watcher) => {
+                    publish(WatcherState::Watching, None, Some(&logs_path), restarts);
+                    let started = Instant::now();
+
+                    let failure = loop {
+                        if Self::wait(&cancel, config.health_check_interval).await {
+                            break None;
+                        }
+                        if !watcher.is_running() {
+                            let reason = "stopped unexpectedly".to_string();
+                            break Some(CompanionError::Watch(reason));
+                        }
+                        if !logs_path.is_dir() {
+                            break Some(CompanionError::LogsMissing(logs_path.clone()));
+                        }
+                    };
+                    watcher.stop().await;
+
+                    let Some(reason) = failure else {
+                        break;
+                    };
+
+                    if started.elapsed() >= config.backoff_reset_after {
+                        backoff = config.restart_backoff_initial;
+                    }
+                    warn!("{}, restarting in {:?}", reason, backoff);
+                    restarts += 1;
+                    publish(
+                        WatcherState::Degraded,
+                        Some(reason),
+                        Some(&logs_path),
+                        restarts,
+                    );
+                }
+                Err(e) => {
+                    warn!("Failed to start log watcher: {}", e);
+                    publish(WatcherState::Error, Some(e), Some(&logs_path), restarts);
+                }
+            }
+
+            if Self::wait(&cancel, backoff).await {
+                break;
+            }
+            backoff = (backoff * 2).min(config.restart_backoff_max);
+        }
+    }
+
+    /// Sleep for `duration`; returns true if cancelled first
+    async fn wait(cancel: &CancellationToken, duration: Duration) -> bool {
+        tokio::select! {
+            _ = cancel.cancelled() => true,
+            _ = tokio::time::sleep(duration) => false,
+        }
+    }
+}
+
+impl Drop for WatcherSupervisor {
+    fn drop(&mut self) {
+        if let Some(cancel) = &self.cancel {
+            cancel.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> SupervisorConfig {
+        SupervisorConfig {
+            wait_interval: Duration::from_millis(20),
+            health_check_interval: Duration::from_millis(20),
+            restart_backoff_initial: Duration::from_millis(20),
+            restart_backoff_max: Duration::from_millis(100),
+            backoff_reset_after: Duration::from_secs(60),
+        }
+    }
+
+    async fn wait_for_state(
+        rx: &mut watch::Receiver<WatcherStatus>,
+        state: WatcherState,
+    ) -> WatcherStatus {
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            rx.wait_for(|status| status.state == state),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("timed out waiting for {:?}", state))
+        .unwrap()
+        .clone()
+    }
+
+    #[test]
+    fn test_state_serialization() {
+        let status = WatcherStatus {
+            state: WatcherState::WaitingForLogs,
+            error: Some(CompanionError::LogsMissing(PathBuf::from("C:\\EFT\\Logs"))),
+            logs_path: Some("C:\\EFT\\Logs".to_string()),
+            restarts: 2,
+        };
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["state"], "waitingForLogs");
+        assert_eq!(json["error"]["code"], "logs_missing");
+        assert_eq!(json["logsPath"], "C:\\EFT\\Logs");
+        assert_eq!(json["restarts"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_game() {
+        let mut supervisor = WatcherSupervisor::new(test_config());
+        let mut rx = supervisor.subscribe();
+
+        supervisor.start(|| None, |_| {});
+        wait_for_state(&mut rx, WatcherState::WaitingForGame).await;
+        assert!(supervisor.is_active());
+
+        supervisor.stop().await;
+        assert_eq!(supervisor.status().state, WatcherState::Idle);
+        assert!(!supervisor.is_active());
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_logs_then_watches() {
+        let dir = tempfile::tempdir().unwrap();
+        let eft_path = dir.path().to_string_lossy().to_string();
+        let mut supervisor = WatcherSupervisor::new(test_config());
+        let mut rx = supervisor.subscribe();
+
+        supervisor.start(move || Some(eft_path.clone()), |_| {});
+        wait_for_state(&mut rx, WatcherState::WaitingForLogs).await;
+
+        std::fs::create_dir(dir.path().join("Logs")).unwrap();
+        let status = wait_for_state(&mut rx, WatcherState::Watching).await;
+        assert_eq!(status.restarts, 0);
+
+        supervisor.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_restarts_after_logs_folder_disappears() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = dir.path().join("Logs");
+        std::fs::create_dir(&logs).unwrap();
+        let eft_path = dir.path().to_string_lossy().to_string();
+        let mut supervisor = WatcherSupervisor::new(test_config());
+        let mut rx = supervisor.subscribe();
+
+        supervisor.start(move || Some(eft_path.clone()), |_| {});
+        wait_for_state(&mut rx, WatcherState::Watching).await;
+
+        std::fs::remove_dir_all(&logs).unwrap();
+        let status = wait_for_state(&mut rx, WatcherState::Degraded).await;
+        assert_eq!(
+            status.error,
+            Some(CompanionError::LogsMissing(logs.clone()))
+        );
+
+        std::fs::create_dir(&logs).unwrap();
+        let status = wait_for_state(&mut rx, WatcherState::Watching).await;
+        assert_eq!(status.restarts, 1);
+
+        supervisor.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_start_is_idempotent() {
+        let mut supervisor = WatcherSupervisor::new(test_config());
+        let mut rx = supervisor.subscribe();
+
+        supervisor.start(|| None, |_| {});
+        wait_for_state(&mut rx, WatcherState::WaitingForGame).await;
+        supervisor.start(|| Some("/does/not/matter".to_string()), |_| {});
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(supervisor.status().state, WatcherState::WaitingForGame);
+
+        supervisor.stop().await;
+    }
+}
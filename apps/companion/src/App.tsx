@@ -14,23 +14,33 @@ import {
   VolumeX,
   ArrowRight,
   ExternalLink,
+  WifiOff,
 } from "lucide-react";
 
 import { useStore } from "./hooks/useStore";
 import {
   getEftPath,
+  getInstallInfo,
   setEftPath,
   startWatching,
   stopWatching,
   isWatching as checkWatching,
+  getWatcherState,
   setCompanionToken,
+  clearCompanionToken,
   getSyncStatus,
-  validateToken,
+  validateStoredToken,
   syncNow,
+  cancelSync,
   type SyncStatus,
+  type SyncError,
+  type SyncProgress,
   type TokenValidation,
   type QuestEvent,
+  type WatcherStatus,
+  type InstallInfo,
 } from "./lib/tauri";
+import { errorMessage } from "./lib/errors";
 import { LinkAccount } from "./components/LinkAccount";
 import { RecentEvents } from "./components/RecentEvents";
 import { SettingsPanel } from "./components/SettingsPanel";
@@ -40,6 +50,21 @@ import { useVersionCheck } from "./hooks/useVersionCheck";
 
 type View = "main" | "link" | "settings";
 
+const WATCHER_STATE_LABELS: Record<WatcherStatus["state"], string> = {
+  idle: "Stopped",
+  waitingForGame: "Waiting for game",
+  waitingForLogs: "Waiting for logs",
+  watching: "Watching",
+  degraded: "Reconnecting",
+  error: "Error",
+};
+
+/** " after syncing N quests" when a failed sync got part way */
+function syncedBefore(err: SyncError): string {
+  const synced = err.partial?.synced ?? 0;
+  return synced > 0 ? ` after syncing ${synced} quest${synced !== 1 ? "s" : ""}` : "";
+}
+
 function App() {
   const { settings, loading: settingsLoading, setSetting } = useStore();
   const toast = useToast();
@@ -47,12 +72,17 @@ function App() {
 
   const [view, setView] = useState<View>("main");
   const [eftPath, setEftPathState] = useState<string | null>(null);
+  const [installInfo, setInstallInfo] = useState<InstallInfo | null>(null);
   const [watching, setWatching] = useState(false);
+  const [watcherStatus, setWatcherStatus] = useState<WatcherStatus | null>(
+    null
+  );
   const [syncStatus, setSyncStatus] = useState<SyncStatus | null>(null);
   const [tokenInfo, setTokenInfo] = useState<TokenValidation | null>(null);
   const [recentEvents, setRecentEvents] = useState<QuestEvent[]>([]);
   const [error, setError] = useState<string | null>(null);
   const [syncing, setSyncing] = useState(false);
+  const [syncProgress, setSyncProgress] = useState<SyncProgress | null>(null);
 
   // Initialize on mount
   useEffect(() => {
@@ -61,45 +91,45 @@ function App() {
         // Get EFT path
         const path = await getEftPath();
         setEftPathState(path);
+        setInstallInfo(await getInstallInfo());
 
         // Check watching state
         const isWatchingNow = await checkWatching();
         setWatching(isWatchingNow);
+        setWatcherStatus(await getWatcherState());
 
         // Get sync status
         const status = await getSyncStatus();
         setSyncStatus(status);
       } catch (err) {
         console.error("Init error:", err);
-        setError(String(err));
+        setError(errorMessage(err));
       }
     }
     init();
   }, []);
 
-  // Validate token when settings load
+  // Validate the token kept by the backend when settings load
   useEffect(() => {
-    async function validateStoredToken() {
-      if (settingsLoading || !settings.companionToken) {
-        setTokenInfo(null);
+    async function checkStoredToken() {
+      if (settingsLoading) {
         return;
       }
 
       try {
-        await setCompanionToken(settings.companionToken);
-        const validation = await validateToken(settings.companionToken);
+        const validation = await validateStoredToken();
         setTokenInfo(validation);
 
         // Auto-start watching if enabled
-        if (validation.valid && settings.autoWatch && eftPath && !watching) {
+        if (validation?.valid && settings.autoWatch && eftPath && !watching) {
           await handleStartWatching();
         }
       } catch (err) {
         console.error("Token validation error:", err);
       }
     }
-    validateStoredToken();
-  }, [settingsLoading, settings.companionToken, settings.autoWatch, eftPath]);
+    checkStoredToken();
+  }, [settingsLoading, settings.autoWatch, eftPath]);
 
   // Listen for quest events from Rust
   useEffect(() => {
@@ -120,10 +150,23 @@ function App() {
     };
   }, [settings.notificationSound]);
 
+  // Follow the watcher supervisor state
+  useEffect(() => {
+    const unlisten = listen<WatcherStatus>("watcher-state", (event) => {
+      setWatcherStatus(event.payload);
+      setWatching(event.payload.state !== "idle");
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   // Listen for auto-sync completion events from Rust backend
   useEffect(() => {
     const unlistenComplete = listen<{ synced: number; errors: unknown[]; pendingCount: number }>("sync-complete", async (event) => {
       console.log("Auto-sync complete:", event.payload);
+      setSyncProgress(null);
       // Refresh sync status immediately after auto-sync
       const status = await getSyncStatus();
       setSyncStatus(status);
@@ -137,14 +180,29 @@ function App() {
       }
     });
 
-    const unlistenError = listen<string>("sync-error", (event) => {
+    const unlistenError = listen<SyncError>("sync-error", (event) => {
       console.error("Auto-sync error:", event.payload);
-      toast.error(`Auto-sync failed: ${event.payload}`);
+      setSyncProgress(null);
+      // Losing the connection shows as "Offline" in the sync panel instead
+      if (event.payload.code === "network") return;
+      toast.error(`Auto-sync failed${syncedBefore(event.payload)}: ${event.payload.message}`);
+    });
+
+    // Progress through a backlog, one chunk at a time
+    const unlistenProgress = listen<SyncProgress>("sync-progress", (event) => {
+      setSyncProgress(event.payload);
+    });
+
+    // Connectivity changes, so going offline or back online shows right away
+    const unlistenStatus = listen<SyncStatus>("sync-status", (event) => {
+      setSyncStatus(event.payload);
     });
 
     return () => {
       unlistenComplete.then((fn) => fn());
       unlistenError.then((fn) => fn());
+      unlistenProgress.then((fn) => fn());
+      unlistenStatus.then((fn) => fn());
     };
   }, [toast]);
 
@@ -168,7 +226,7 @@ function App() {
       await startWatching();
       setWatching(true);
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
     }
   }, []);
 
@@ -177,7 +235,7 @@ function App() {
       await stopWatching();
       setWatching(false);
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
     }
   }, []);
 
@@ -190,7 +248,9 @@ function App() {
       const status = await getSyncStatus();
       setSyncStatus(status);
 
-      if (result.errors.length > 0) {
+      if (result.cancelled) {
+        toast.info(`Sync cancelled after ${result.synced} quest${result.synced !== 1 ? "s" : ""}`);
+      } else if (result.errors.length > 0) {
         toast.error(`Synced ${result.synced}, ${result.errors.length} errors`);
       } else if (result.synced > 0) {
         toast.success(`Synced ${result.synced} quest${result.synced !== 1 ? "s" : ""} successfully`);
@@ -198,26 +258,45 @@ function App() {
         toast.info("No pending events to sync");
       }
     } catch (err) {
-      toast.error(String(err));
+      toast.error(`Sync failed${syncedBefore(err as SyncError)}: ${errorMessage(err)}`);
+      // Chunks synced before the failure still leave the queue
+      getSyncStatus().then(setSyncStatus, console.error);
     } finally {
       setSyncing(false);
+      setSyncProgress(null);
     }
   }, [syncing, toast]);
 
+  const handleCancelSync = useCallback(async () => {
+    try {
+      await cancelSync();
+    } catch (err) {
+      toast.error(errorMessage(err));
+    }
+  }, [toast]);
+
   const handleUnlink = useCallback(async () => {
-    await setSetting("companionToken", null);
+    try {
+      await clearCompanionToken();
+    } catch (err) {
+      toast.error(errorMessage(err));
+    }
     setTokenInfo(null);
     await handleStopWatching();
-  }, [setSetting, handleStopWatching]);
+  }, [toast, handleStopWatching]);
 
   const handleLinkComplete = useCallback(
     async (token: string, validation: TokenValidation) => {
-      await setSetting("companionToken", token);
-      await setCompanionToken(token);
+      try {
+        await setCompanionToken(token);
+      } catch (err) {
+        // Linked for this session, but the token could not be stored
+        toast.error(errorMessage(err));
+      }
       setTokenInfo(validation);
       setView("main");
     },
-    [setSetting]
+    [toast]
   );
 
   if (settingsLoading) {
@@ -247,6 +326,7 @@ function App() {
           const valid = await setEftPath(path);
           if (valid) {
             setEftPathState(path);
+            setInstallInfo(await getInstallInfo());
             await setSetting("eftPath", path);
           }
           return valid;
@@ -377,6 +457,23 @@ function App() {
               <span className="text-tarkov-muted truncate" title={eftPath}>
                 {eftPath}
               </span>
+              {installInfo?.mode === "spt" && (
+                <span
+                  className="ml-auto shrink-0 rounded bg-tarkov-border px-1.5 text-xs text-tarkov-text"
+                  title="Single Player Tarkov: progress is read from the SPT client logs and profile"
+                >
+                  SPT
+                </span>
+              )}
+              {installInfo?.version && (
+                <span
+                  className={`shrink-0 text-xs text-tarkov-muted ${
+                    installInfo.mode === "spt" ? "" : "ml-auto"
+                  }`}
+                >
+                  v{installInfo.version.version}
+                </span>
+              )}
             </div>
           ) : (
             <div className="flex items-center gap-2 text-sm">
@@ -458,11 +555,24 @@ function App() {
             <div className="flex items-center justify-between mb-3">
               <h2 className="font-medium text-tarkov-text">Log Watcher</h2>
               <div className="flex items-center gap-2">
-                {watching ? (
+                {watching &&
+                (!watcherStatus || watcherStatus.state === "watching") ? (
                   <span className="flex items-center gap-1 text-sm text-tarkov-success">
                     <span className="w-2 h-2 rounded-full bg-tarkov-success animate-pulse" />
                     Watching
                   </span>
+                ) : watching && watcherStatus ? (
+                  <span
+                    className={`flex items-center gap-1 text-sm ${
+                      watcherStatus.state === "error"
+                        ? "text-tarkov-error"
+                        : "text-tarkov-warning"
+                    }`}
+                    title={watcherStatus.error?.message}
+                  >
+                    <span className="w-2 h-2 rounded-full bg-current" />
+                    {WATCHER_STATE_LABELS[watcherStatus.state]}
+                  </span>
                 ) : (
                   <span className="text-sm text-tarkov-muted">Stopped</span>
                 )}
@@ -529,12 +639,42 @@ function App() {
               </div>
             </div>
 
+            {syncProgress && syncProgress.remaining > 0 && (
+              <div className="mt-2 text-xs text-tarkov-muted flex items-center gap-1">
+                <RefreshCw className="w-3 h-3 animate-spin" />
+                Synced {syncProgress.synced}, {syncProgress.remaining} left
+                <button
+                  onClick={handleCancelSync}
+                  className="ml-auto text-tarkov-accent hover:text-tarkov-accent/80"
+                >
+                  Cancel
+                </button>
+              </div>
+            )}
+
+            {!syncStatus.online && (
+              <div className="mt-2 text-xs text-tarkov-warning flex items-center gap-1">
+                <WifiOff className="w-3 h-3" />
+                Offline — {syncStatus.pendingCount} queued
+              </div>
+            )}
+
             {syncStatus.lastSync && (
               <div className="mt-2 text-xs text-tarkov-muted flex items-center gap-1">
                 <Clock className="w-3 h-3" />
                 Last sync: {new Date(syncStatus.lastSync).toLocaleTimeString()}
               </div>
             )}
+
+            {syncStatus.online && syncStatus.nextRetryAt && (
+              <div className="mt-1 text-xs text-tarkov-warning flex items-center gap-1">
+                <RefreshCw className="w-3 h-3" />
+                {syncStatus.consecutiveFailures} failed{" "}
+                {syncStatus.consecutiveFailures === 1 ? "attempt" : "attempts"},
+                retrying at{" "}
+                {new Date(syncStatus.nextRetryAt).toLocaleTimeString()}
+              </div>
+            )}
           </div>
         )}
 
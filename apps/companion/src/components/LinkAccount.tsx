@@ -9,6 +9,7 @@ import {
   EyeOff,
 } from "lucide-react";
 import { validateToken, type TokenValidation } from "../lib/tauri";
+import { errorMessage } from "../lib/errors";
 
 interface LinkAccountProps {
   onComplete: (token: string, validation: TokenValidation) => void;
@@ -44,7 +45,7 @@ export function LinkAccount({ onComplete, onBack }: LinkAccountProps) {
         setError(result.error || "Invalid or expired token");
       }
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
     } finally {
       setValidating(false);
     }
@@ -11,12 +11,12 @@ vi.mock("@tauri-apps/plugin-dialog");
 
 describe("SettingsPanel", () => {
   const mockSettings: AppSettings = {
-    companionToken: null,
     eftPath: "C:\\Battlestate Games\\EFT",
     autoStart: true,
     autoWatch: false,
     notifications: true,
     notificationSound: true,
+    apiBase: null,
   };
 
   const mockOnUpdateSetting = vi.fn();
@@ -325,7 +325,6 @@ describe("SettingsPanel", () => {
 
   it("reflects current setting states in toggles", () => {
     const settings: AppSettings = {
-      companionToken: null,
       eftPath: "C:\\EFT",
       autoStart: false,
       autoWatch: true,
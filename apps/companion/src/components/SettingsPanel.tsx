@@ -1,4 +1,4 @@
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import {
   ArrowLeft,
   FolderSearch,
@@ -9,10 +9,27 @@ import {
   Eye,
   CheckCircle,
   AlertCircle,
+  ClipboardCopy,
 } from "lucide-react";
 import { enable, disable } from "@tauri-apps/plugin-autostart";
 import { open } from "@tauri-apps/plugin-dialog";
 import type { AppSettings } from "../hooks/useStore";
+import {
+  diagnoseInstall,
+  listEftInstalls,
+  type InstallCandidate,
+} from "../lib/tauri";
+
+const INSTALL_SOURCE_LABELS: Record<InstallCandidate["source"], string> = {
+  userProvided: "Custom path",
+  runningProcess: "Running game",
+  launcherSettings: "BSG Launcher settings",
+  registry: "BSG Launcher",
+  steam: "Steam",
+  winePrefix: "Wine / Proton prefix",
+  defaultLocation: "Default location",
+  driveScan: "Drive scan",
+};
 
 interface SettingsPanelProps {
   settings: AppSettings;
@@ -36,6 +53,21 @@ export function SettingsPanel({
   const [pathError, setPathError] = useState<string | null>(null);
   const [pathValid, setPathValid] = useState(false);
   const [validating, setValidating] = useState(false);
+  const [installs, setInstalls] = useState<InstallCandidate[]>([]);
+  const [diagnosticsCopied, setDiagnosticsCopied] = useState(false);
+
+  useEffect(() => {
+    async function loadInstalls() {
+      try {
+        setInstalls((await listEftInstalls()) ?? []);
+      } catch (error) {
+        console.error("Failed to list EFT installs:", error);
+      }
+    }
+    loadInstalls();
+  }, []);
+
+  const validInstalls = installs.filter((install) => install.valid);
 
   const handlePathChange = async () => {
     if (!customPath.trim()) {
@@ -87,6 +119,28 @@ export function SettingsPanel({
     }
   };
 
+  const handleSelectInstall = async (path: string) => {
+    setCustomPath(path);
+    setPathError(null);
+    setValidating(true);
+    const valid = await onSetEftPath(path);
+    setValidating(false);
+    setPathValid(valid);
+    if (!valid) {
+      setPathError("Invalid EFT installation path");
+    }
+  };
+
+  const handleCopyDiagnostics = async () => {
+    try {
+      const report = await diagnoseInstall();
+      await navigator.clipboard.writeText(JSON.stringify(report, null, 2));
+      setDiagnosticsCopied(true);
+    } catch (error) {
+      console.error("Failed to copy diagnostics:", error);
+    }
+  };
+
   const handleAutoStartToggle = async () => {
     try {
       const newValue = !settings.autoStart;
@@ -137,6 +191,36 @@ export function SettingsPanel({
               </div>
             )}
 
+            {/* Multiple installs found */}
+            {validInstalls.length > 1 && (
+              <div className="text-sm">
+                <p className="text-tarkov-muted mb-2">
+                  Found {validInstalls.length} installs, which one do you play?
+                </p>
+                <div className="space-y-1">
+                  {validInstalls.map((install) => (
+                    <button
+                      key={install.path}
+                      onClick={() => handleSelectInstall(install.path)}
+                      disabled={validating}
+                      className={`w-full text-left rounded-lg border px-3 py-2 disabled:opacity-50 ${
+                        install.path === eftPath
+                          ? "border-tarkov-accent bg-tarkov-accent/10"
+                          : "border-tarkov-border hover:bg-tarkov-border"
+                      }`}
+                    >
+                      <p className="text-tarkov-text break-all">
+                        {install.path}
+                      </p>
+                      <p className="text-xs text-tarkov-muted">
+                        {INSTALL_SOURCE_LABELS[install.source]}
+                      </p>
+                    </button>
+                  ))}
+                </div>
+              </div>
+            )}
+
             {/* Custom path input */}
             <div>
               <label className="block text-sm text-tarkov-muted mb-1">
@@ -184,6 +268,17 @@ export function SettingsPanel({
                 </p>
               )}
             </div>
+
+            {/* Diagnostics */}
+            <button
+              onClick={handleCopyDiagnostics}
+              className="text-xs text-tarkov-muted hover:text-tarkov-text flex items-center gap-1"
+            >
+              <ClipboardCopy className="w-3 h-3" />
+              {diagnosticsCopied
+                ? "Diagnostics copied, paste them into your bug report"
+                : "Copy detection diagnostics"}
+            </button>
           </div>
         </div>
 
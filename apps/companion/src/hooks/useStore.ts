@@ -1,24 +1,20 @@
 import { useState, useEffect, useCallback } from "react";
-import { LazyStore } from "@tauri-apps/plugin-store";
+import {
+  getSettings,
+  updateSettings,
+  type AppSettings,
+} from "../lib/tauri";
 
-const store = new LazyStore("settings.json");
-
-export interface AppSettings {
-  companionToken: string | null;
-  eftPath: string | null;
-  autoStart: boolean;
-  autoWatch: boolean;
-  notifications: boolean;
-  notificationSound: boolean;
-}
+// Settings are owned and persisted by the backend
+export type { AppSettings };
 
 const defaultSettings: AppSettings = {
-  companionToken: null,
   eftPath: null,
   autoStart: false,
   autoWatch: true,
   notifications: true,
   notificationSound: true,
+  apiBase: null,
 };
 
 export function useStore() {
@@ -29,7 +25,7 @@ export function useStore() {
   useEffect(() => {
     async function loadSettings() {
       try {
-        const saved = await store.get<AppSettings>("settings");
+        const saved = await getSettings();
         if (saved) {
           setSettings({ ...defaultSettings, ...saved });
         }
@@ -45,39 +41,34 @@ export function useStore() {
   // Save individual setting
   const setSetting = useCallback(
     async <K extends keyof AppSettings>(key: K, value: AppSettings[K]) => {
-      const newSettings = { ...settings, [key]: value };
-      setSettings(newSettings);
+      setSettings((current) => ({ ...current, [key]: value }));
       try {
-        await store.set("settings", newSettings);
-        await store.save();
+        setSettings(await updateSettings({ [key]: value }));
       } catch (error) {
         console.error(`Failed to save setting ${key}:`, error);
       }
     },
-    [settings]
+    []
   );
 
-  // Save all settings at once
+  // Save several settings at once
   const saveSettings = useCallback(
-    async (newSettings: Partial<AppSettings>) => {
-      const merged = { ...settings, ...newSettings };
-      setSettings(merged);
+    async (changes: Partial<AppSettings>) => {
+      setSettings((current) => ({ ...current, ...changes }));
       try {
-        await store.set("settings", merged);
-        await store.save();
+        setSettings(await updateSettings(changes));
       } catch (error) {
         console.error("Failed to save settings:", error);
       }
     },
-    [settings]
+    []
   );
 
   // Clear all settings
   const clearSettings = useCallback(async () => {
     setSettings(defaultSettings);
     try {
-      await store.clear();
-      await store.save();
+      setSettings(await updateSettings(defaultSettings));
     } catch (error) {
       console.error("Failed to clear settings:", error);
     }
@@ -0,0 +1,42 @@
+/**
+ * Errors returned by companion commands and events, mirroring `error.rs`.
+ * Match on `code`; the message is for display only.
+ */
+export type CompanionErrorCode =
+  | "network"
+  | "unauthorized"
+  | "rate_limited"
+  | "server"
+  | "parse"
+  | "no_token"
+  | "install_not_found"
+  | "invalid_install"
+  | "logs_missing"
+  | "io"
+  | "watch"
+  | "settings"
+  | "vault"
+  | "sync_unavailable"
+  | "internal";
+
+export interface CompanionError {
+  code: CompanionErrorCode;
+  message: string;
+}
+
+export function isCompanionError(err: unknown): err is CompanionError {
+  return (
+    typeof err === "object" &&
+    err !== null &&
+    "code" in err &&
+    "message" in err
+  );
+}
+
+/** Message to show for anything a command rejected with */
+export function errorMessage(err: unknown): string {
+  if (isCompanionError(err) || err instanceof Error) {
+    return err.message;
+  }
+  return String(err);
+}
@@ -1,12 +1,35 @@
 import { invoke } from "@tauri-apps/api/core";
+import type { CompanionError } from "./errors";
 
 // Types
+export interface AppSettings {
+  eftPath: string | null;
+  autoStart: boolean;
+  autoWatch: boolean;
+  notifications: boolean;
+  notificationSound: boolean;
+  apiBase: string | null;
+}
+
+/** Where the backend keeps the companion token */
+export type TokenStorage = "keyring" | "encrypted-file" | "memory";
+
 export interface SyncStatus {
   hasToken: boolean;
+  tokenStorage: TokenStorage;
+  /** Why the stored token could not be read; the account is still linked */
+  tokenError: CompanionError | null;
   pendingCount: number;
   totalSynced: number;
   totalErrors: number;
   lastSync: string | null;
+  duplicatesDropped: number;
+  gameVersion: string | null;
+  clientMode: GameMode;
+  nextRetryAt: string | null;
+  consecutiveFailures: number;
+  online: boolean;
+  offlineSince: string | null;
 }
 
 export interface TokenValidation {
@@ -30,6 +53,95 @@ export interface SyncResult {
   errors: Array<{ questId: string; error: string }>;
   unlockedQuests: string[];
   pendingCount: number;
+  chunks: number;
+  cancelled: boolean;
+}
+
+/** A sync that failed part way; `partial` is what it synced before failing */
+export interface SyncError extends CompanionError {
+  partial?: SyncResult;
+}
+
+export interface SyncProgress {
+  chunks: number;
+  synced: number;
+  remaining: number;
+}
+
+export type WatcherState =
+  | "idle"
+  | "waitingForGame"
+  | "waitingForLogs"
+  | "watching"
+  | "degraded"
+  | "error";
+
+export interface WatcherStatus {
+  state: WatcherState;
+  error: CompanionError | null;
+  logsPath: string | null;
+  restarts: number;
+}
+
+export type InstallSourceKind =
+  | "userProvided"
+  | "runningProcess"
+  | "launcherSettings"
+  | "registry"
+  | "steam"
+  | "winePrefix"
+  | "defaultLocation"
+  | "driveScan";
+
+export interface InstallCandidate {
+  path: string;
+  source: InstallSourceKind;
+  valid: boolean;
+  rejection: string | null;
+}
+
+export interface GameVersion {
+  version: string;
+  build: number | null;
+  source: "executable" | "logSession";
+}
+
+export type GameMode = "live" | "spt";
+
+export interface InstallInfo {
+  path: string;
+  logsPath: string;
+  version: GameVersion | null;
+  mode: GameMode;
+}
+
+export interface Probe {
+  kind: "registry" | "path" | "directory" | "file" | "directoryListing";
+  target: string;
+  found: boolean;
+  detail: string | null;
+}
+
+export interface LogsDiagnostics {
+  path: string;
+  exists: boolean;
+  readable: boolean;
+  readOnly: boolean | null;
+  entries: number;
+  newestSession: string | null;
+  error: string | null;
+}
+
+export interface DiagnosticsReport {
+  generatedAt: string;
+  companionVersion: string;
+  os: string;
+  customPath: string | null;
+  resolvedPath: string | null;
+  version: GameVersion | null;
+  candidates: InstallCandidate[];
+  logs: LogsDiagnostics | null;
+  probes: Probe[];
 }
 
 export interface QuestEvent {
@@ -40,6 +152,17 @@ export interface QuestEvent {
 }
 
 // Tauri commands
+export async function getSettings(): Promise<AppSettings> {
+  return invoke<AppSettings>("get_settings");
+}
+
+// Only the given fields are saved; the backend returns all settings
+export async function updateSettings(
+  changes: Partial<AppSettings>
+): Promise<AppSettings> {
+  return invoke<AppSettings>("update_settings", { changes });
+}
+
 export async function getEftPath(): Promise<string | null> {
   return invoke<string | null>("get_eft_path");
 }
@@ -48,6 +171,18 @@ export async function setEftPath(path: string): Promise<boolean> {
   return invoke<boolean>("set_eft_path", { path });
 }
 
+export async function getInstallInfo(): Promise<InstallInfo | null> {
+  return invoke<InstallInfo | null>("get_install_info");
+}
+
+export async function listEftInstalls(): Promise<InstallCandidate[]> {
+  return invoke<InstallCandidate[]>("list_eft_installs");
+}
+
+export async function diagnoseInstall(): Promise<DiagnosticsReport> {
+  return invoke<DiagnosticsReport>("diagnose_install");
+}
+
 export async function startWatching(): Promise<void> {
   return invoke("start_watching");
 }
@@ -60,10 +195,18 @@ export async function isWatching(): Promise<boolean> {
   return invoke<boolean>("is_watching");
 }
 
+export async function getWatcherState(): Promise<WatcherStatus> {
+  return invoke<WatcherStatus>("get_watcher_state");
+}
+
 export async function setCompanionToken(token: string): Promise<void> {
   return invoke("set_companion_token", { token });
 }
 
+export async function clearCompanionToken(): Promise<void> {
+  return invoke("clear_companion_token");
+}
+
 export async function getSyncStatus(): Promise<SyncStatus> {
   return invoke<SyncStatus>("get_sync_status");
 }
@@ -72,6 +215,16 @@ export async function validateToken(token: string): Promise<TokenValidation> {
   return invoke<TokenValidation>("validate_token", { token });
 }
 
+/** Validate the token the backend stored; null when no account is linked */
+export async function validateStoredToken(): Promise<TokenValidation | null> {
+  return invoke<TokenValidation | null>("validate_stored_token");
+}
+
+/** Rejects with a `SyncError` */
 export async function syncNow(): Promise<SyncResult> {
   return invoke<SyncResult>("sync_now");
 }
+
+export async function cancelSync(): Promise<void> {
+  return invoke("cancel_sync");
+}
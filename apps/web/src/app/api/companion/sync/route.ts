@@ -2,9 +2,14 @@ import { NextResponse } from "next/server";
 import { prisma } from "@/lib/prisma";
 import { z } from "zod";
 import bcrypt from "bcryptjs";
-import type { Prisma, QuestStatus } from "@prisma/client";
+import { Prisma, type QuestStatus } from "@prisma/client";
 import { logger } from "@/lib/logger";
-import { syncSchema } from "@/types/api-contracts";
+import {
+  IDEMPOTENCY_KEY_HEADER,
+  syncSchema,
+  type SyncEvent,
+  type SyncResponse,
+} from "@/types/api-contracts";
 
 type QuestDependencyWithStatus = Prisma.QuestDependencyGetPayload<{
   select: { requiredId: true; requirementStatus: true };
@@ -86,13 +91,14 @@ function isDependencyMet(
  * Check if all dependencies for a quest are met.
  */
 async function checkAllDependenciesMet(
+  db: Prisma.TransactionClient,
   userId: string,
   dependencies: QuestDependencyWithStatus[]
 ): Promise<boolean> {
   if (dependencies.length === 0) return true;
 
   const requiredQuestIds = dependencies.map((d) => d.requiredId);
-  const progressRecords = await prisma.questProgress.findMany({
+  const progressRecords = await db.questProgress.findMany({
     where: {
       userId,
       questId: { in: requiredQuestIds },
@@ -119,10 +125,11 @@ async function checkAllDependenciesMet(
  * Auto-unlock quests that depend on the completed quest.
  */
 async function autoUnlockDependentQuests(
+  db: Prisma.TransactionClient,
   userId: string,
   completedQuestId: string
 ): Promise<string[]> {
-  const dependentQuests = await prisma.questDependency.findMany({
+  const dependentQuests = await db.questDependency.findMany({
     where: { requiredId: completedQuestId },
     include: {
       dependentQuest: {
@@ -138,12 +145,13 @@ async function autoUnlockDependentQuests(
 
     // Check if ALL dependencies are now met
     const allDepsMet = await checkAllDependenciesMet(
+      db,
       userId,
       quest.dependsOn as QuestDependencyWithStatus[]
     );
 
     if (allDepsMet) {
-      const currentProgress = await prisma.questProgress.findUnique({
+      const currentProgress = await db.questProgress.findUnique({
         where: {
           userId_questId: { userId, questId: quest.id },
         },
@@ -151,7 +159,7 @@ async function autoUnlockDependentQuests(
 
       if (!currentProgress) {
         // Create as AVAILABLE
-        await prisma.questProgress.create({
+        await db.questProgress.create({
           data: {
             userId,
             questId: quest.id,
@@ -162,7 +170,7 @@ async function autoUnlockDependentQuests(
         unlockedQuestIds.push(quest.id);
       } else if (currentProgress.status === "LOCKED") {
         // Unlock it
-        await prisma.questProgress.update({
+        await db.questProgress.update({
           where: {
             userId_questId: { userId, questId: quest.id },
           },
@@ -176,6 +184,131 @@ async function autoUnlockDependentQuests(
   return unlockedQuestIds;
 }
 
+type EventOutcome =
+  | { outcome: "accepted"; unlocked: string[] }
+  | { outcome: "rejected"; error: string };
+
+// Another request claimed the event first; `stored` is the outcome it recorded
+type ClaimedElsewhere = { outcome: "claimed"; eventId: string; stored: string };
+
+/**
+ * Apply one event's status change and the unlocks it causes.
+ */
+async function applyEvent(
+  db: Prisma.TransactionClient,
+  userId: string,
+  event: SyncEvent
+): Promise<EventOutcome> {
+  const newStatus = STATUS_MAP[event.status];
+  if (!newStatus) {
+    return { outcome: "rejected", error: `Unknown status: ${event.status}` };
+  }
+
+  // Check if quest exists
+  const quest = await db.quest.findUnique({
+    where: { id: event.questId },
+    include: { dependsOn: true },
+  });
+  if (!quest) {
+    return { outcome: "rejected", error: "Quest not found" };
+  }
+
+  // Get or create progress record
+  const progress = await db.questProgress.findUnique({
+    where: {
+      userId_questId: { userId, questId: event.questId },
+    },
+  });
+
+  if (!progress) {
+    // Determine initial status based on dependencies
+    let initialStatus: QuestStatus = "AVAILABLE";
+    if (quest.dependsOn.length > 0) {
+      const depsMet = await checkAllDependenciesMet(
+        db,
+        userId,
+        quest.dependsOn as QuestDependencyWithStatus[]
+      );
+      if (!depsMet) {
+        initialStatus = "LOCKED";
+      }
+    }
+
+    await db.questProgress.create({
+      data: {
+        userId,
+        questId: event.questId,
+        status: initialStatus,
+        syncSource: "COMPANION",
+      },
+    });
+  }
+
+  // Apply the status change
+  // Note: We're more permissive here than the web UI since the game
+  // is the source of truth
+  await db.questProgress.update({
+    where: {
+      userId_questId: { userId, questId: event.questId },
+    },
+    data: {
+      status: newStatus,
+      syncSource: "COMPANION",
+    },
+  });
+
+  // Auto-unlock dependent quests if completed
+  const unlocked =
+    newStatus === "COMPLETED"
+      ? await autoUnlockDependentQuests(db, userId, event.questId)
+      : [];
+
+  return { outcome: "accepted", unlocked };
+}
+
+/**
+ * Apply an event at most once. Its (userId, eventId) row is claimed first,
+ * in the same transaction as the change, so a concurrent copy of the event
+ * waits on the claim and then finds it taken.
+ */
+async function claimAndApply(
+  userId: string,
+  event: SyncEvent
+): Promise<EventOutcome | ClaimedElsewhere> {
+  const eventId = event.eventId;
+  if (!eventId) {
+    return prisma.$transaction((tx) => applyEvent(tx, userId, event));
+  }
+
+  try {
+    return await prisma.$transaction(async (tx) => {
+      const claim = await tx.companionSyncEvent.create({
+        data: { userId, eventId, outcome: "accepted" },
+      });
+      const result = await applyEvent(tx, userId, event);
+      if (result.outcome === "rejected") {
+        await tx.companionSyncEvent.update({
+          where: { id: claim.id },
+          data: { outcome: "rejected" },
+        });
+      }
+      return result;
+    });
+  } catch (error) {
+    if (
+      error instanceof Prisma.PrismaClientKnownRequestError &&
+      error.code === "P2002"
+    ) {
+      const stored = await prisma.companionSyncEvent.findUnique({
+        where: { userId_eventId: { userId, eventId } },
+        select: { outcome: true },
+      });
+      return { outcome: "claimed", eventId, stored: stored?.outcome ?? "" };
+    }
+    throw error;
+  }
+}
+
 /**
  * POST /api/companion/sync
  * Sync quest progress events from the companion app.
@@ -196,12 +329,25 @@ export async function POST(request: Request) {
     const body = await request.json();
     const { events } = syncSchema.parse(body);
 
-    const results = {
+    const results: SyncResponse = {
       synced: 0,
-      errors: [] as { questId: string; error: string }[],
-      unlockedQuests: [] as string[],
+      errors: [],
+      unlockedQuests: [],
+      accepted: [],
+      rejected: [],
+      retryable: [],
     };
 
+    // Events processed by an earlier attempt keep their outcome and are not re-applied
+    const eventIds = events.flatMap((e) => (e.eventId ? [e.eventId] : []));
+    const processedEvents = await prisma.companionSyncEvent.findMany({
+      where: { userId, eventId: { in: eventIds } },
+      select: { eventId: true, outcome: true },
+    });
+    const processed = new Map(
+      processedEvents.map((e) => [e.eventId, e.outcome])
+    );
+
     // Process events in order (by timestamp)
     const sortedEvents = [...events].sort(
       (a, b) =>
@@ -209,83 +355,42 @@ export async function POST(request: Request) {
     );
 
     for (const event of sortedEvents) {
+      const previousOutcome = event.eventId && processed.get(event.eventId);
+      if (event.eventId && previousOutcome) {
+        if (previousOutcome === "rejected") {
+          results.rejected.push(event.eventId);
+        } else {
+          results.accepted.push(event.eventId);
+        }
+        continue;
+      }
+
       try {
-        const newStatus = STATUS_MAP[event.status];
-        if (!newStatus) {
-          results.errors.push({
-            questId: event.questId,
-            error: `Unknown status: ${event.status}`,
-          });
+        const result = await claimAndApply(userId, event);
+
+        if (result.outcome === "claimed") {
+          // A concurrent copy of this event was applied; report its outcome
+          if (result.stored === "rejected") {
+            results.rejected.push(result.eventId);
+          } else {
+            results.accepted.push(result.eventId);
+          }
           continue;
         }
 
-        // Check if quest exists
-        const quest = await prisma.quest.findUnique({
-          where: { id: event.questId },
-          include: { dependsOn: true },
-        });
-
-        if (!quest) {
+        if (result.outcome === "rejected") {
           results.errors.push({
             questId: event.questId,
-            error: "Quest not found",
+            eventId: event.eventId,
+            error: result.error,
           });
+          if (event.eventId) results.rejected.push(event.eventId);
           continue;
         }
 
-        // Get or create progress record
-        let progress = await prisma.questProgress.findUnique({
-          where: {
-            userId_questId: { userId, questId: event.questId },
-          },
-        });
-
-        if (!progress) {
-          // Determine initial status based on dependencies
-          let initialStatus: QuestStatus = "AVAILABLE";
-          if (quest.dependsOn.length > 0) {
-            const depsMet = await checkAllDependenciesMet(
-              userId,
-              quest.dependsOn as QuestDependencyWithStatus[]
-            );
-            if (!depsMet) {
-              initialStatus = "LOCKED";
-            }
-          }
-
-          progress = await prisma.questProgress.create({
-            data: {
-              userId,
-              questId: event.questId,
-              status: initialStatus,
-              syncSource: "COMPANION",
-            },
-          });
-        }
-
-        // Apply the status change
-        // Note: We're more permissive here than the web UI since the game
-        // is the source of truth
-        await prisma.questProgress.update({
-          where: {
-            userId_questId: { userId, questId: event.questId },
-          },
-          data: {
-            status: newStatus,
-            syncSource: "COMPANION",
-          },
-        });
-
         results.synced++;
-
-        // Auto-unlock dependent quests if completed
-        if (newStatus === "COMPLETED") {
-          const unlocked = await autoUnlockDependentQuests(
-            userId,
-            event.questId
-          );
-          results.unlockedQuests.push(...unlocked);
-        }
+        results.unlockedQuests.push(...result.unlocked);
+        if (event.eventId) results.accepted.push(event.eventId);
       } catch (eventError) {
         logger.error(
           { err: eventError, questId: event.questId },
@@ -293,11 +398,23 @@ export async function POST(request: Request) {
         );
         results.errors.push({
           questId: event.questId,
+          eventId: event.eventId,
           error: "Failed to process event",
         });
+        if (event.eventId) results.retryable.push(event.eventId);
       }
     }
 
+    if (processed.size > 0) {
+      logger.info(
+        {
+          batchKey: request.headers.get(IDEMPOTENCY_KEY_HEADER),
+          replayed: processed.size,
+        },
+        "Companion sync batch retried"
+      );
+    }
+
     return NextResponse.json(results);
   } catch (error) {
     if (error instanceof z.ZodError) {
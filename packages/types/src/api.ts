@@ -56,8 +56,16 @@ export type ListTokensResponse = {
 /**
  * POST /api/companion/sync
  * Sync quest progress events from the companion app.
+ * See docs/COMPANION_SYNC_PROTOCOL.md for acknowledgement and idempotency rules.
  */
+
+// Header carrying the batch's idempotency key, derived from its event ids
+export const IDEMPOTENCY_KEY_HEADER = "Idempotency-Key";
+
 export const syncEventSchema = z.object({
+  // Client-generated id, stable across retries and the event's idempotency key;
+  // older clients omit it
+  eventId: z.string().min(1).max(64).optional(),
   questId: z.string().min(1),
   status: z.enum(["STARTED", "FINISHED", "FAILED"]),
   timestamp: z.string().datetime(),
@@ -78,8 +86,14 @@ export type SyncRequest = z.infer<typeof syncSchema>;
 
 export type SyncResponse = {
   synced: number;
-  errors: Array<{ questId: string; error: string }>;
+  errors: Array<{ questId: string; eventId?: string; error: string }>;
   unlockedQuests: string[];
+  // Ids of events that were applied; the client drops them from its queue
+  accepted: string[];
+  // Ids of events that can never apply, e.g. an unknown quest; also dropped
+  rejected: string[];
+  // Ids of events that failed for a transient reason; the client sends them again
+  retryable: string[];
 };
 
 /**